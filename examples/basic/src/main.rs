@@ -1,13 +1,39 @@
 use axum::Router;
 use lily::prelude::*;
 
+mod state;
 mod types;
 
 #[tokio::main]
 async fn main() {
-    let app = Router::new().merge(types::content::Content::routes());
+    // The resources are kept across restarts if a snapshot file is given
+    let store = match std::env::var("LILY_SNAPSHOT") {
+        Ok(path) => MemoryStore::with_snapshot(path).expect("Failed to restore the snapshot"),
+        Err(_) => MemoryStore::new(),
+    };
+    types::seed(&store)
+        .await
+        .expect("Failed to store the example data");
+
+    let state = state::AppState {
+        store: store.clone(),
+    };
+    let app = Router::new()
+        .merge(types::content::Content::routes())
+        .merge(types::author::Author::routes())
+        .merge(types::tag::Tag::routes())
+        .with_state(state)
+        .merge(types::comment::Comment::routes())
+        .merge(types::attachment::Attachment::routes());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    println!("Running on http://127.0.0.1:3000/content/51de0ea5-635c-4eee-ab70-9827fd14aaca");
-    axum::serve(listener, app).await.unwrap();
+    println!("Running on http://127.0.0.1:3000/content/content-0");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await
+        .unwrap();
+    // Saves the snapshot, as the router holding the other clones of the store is gone
+    drop(store);
 }
@@ -0,0 +1,17 @@
+use axum::extract::FromRef;
+use lily::prelude::*;
+
+/// The state shared by the handlers of the endpoints
+///
+/// A real application would hold its database pool and configuration here.
+#[derive(Clone)]
+pub struct AppState {
+    /// The store keeping the authors and contents
+    pub store: MemoryStore,
+}
+
+impl FromRef<AppState> for MemoryStore {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
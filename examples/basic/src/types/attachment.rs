@@ -0,0 +1,49 @@
+pub use lily::prelude::*;
+
+/// A file attached to the contents, identified by a UUID instead of a string
+#[endpoint(read, id = uuid::Uuid)]
+pub struct Attachment {
+    file_name: String,
+    size: u64,
+}
+
+impl ReadSingle for Attachment {
+    async fn read_single(
+        _state: &Self::State,
+        _context: &Context,
+        id: &Self::Id,
+    ) -> Result<Option<Self>, Error> {
+        Ok(example_attachments().find(|attachment| &attachment.id == id))
+    }
+}
+
+impl ReadMultiple for Attachment {
+    async fn read_multiple(
+        _state: &Self::State,
+        _context: &Context,
+        query: &ListQuery<Self>,
+    ) -> Result<Page<Self>, Error> {
+        let mut matching: Vec<Attachment> = example_attachments()
+            .filter(|attachment| query.filter.matches(attachment))
+            .collect();
+        matching.sort_by(|a, b| query.compare(a, b));
+        let total = matching.len() as u64;
+        let items = matching
+            .into_iter()
+            .skip(query.pagination.offset as usize)
+            .take(query.pagination.limit as usize)
+            .collect();
+        Ok(Page::new(items, total, &query.pagination))
+    }
+}
+
+/// Returns the hard-coded attachments with the UUIDs `00000000-0000-0000-0000-000000000000`
+/// to `00000000-0000-0000-0000-000000000003`
+fn example_attachments() -> impl Iterator<Item = Attachment> {
+    (0..4).map(|index| Attachment {
+        id: uuid::Uuid::from_u128(index),
+        file_name: format!("attachment-{}.pdf", index),
+        size: 1024 * (index as u64 + 1),
+        created_at: chrono::DateTime::from_timestamp(1_767_225_600, 0).unwrap_or_default(),
+    })
+}
@@ -0,0 +1,58 @@
+pub use lily::prelude::*;
+
+use super::content::Content;
+use crate::state::AppState;
+
+#[endpoint(read, delete_single, delete_multiple, state = AppState, referenced_by(Content))]
+#[persistent(store = MemoryStore)]
+pub struct Author {
+    name: String,
+}
+
+impl DeleteMultiple for Author {
+    async fn delete_multiple(
+        state: &Self::State,
+        _context: &Context,
+        ids: &[Self::Id],
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Option<Self>, BulkItemError>>, Error> {
+        let repository = Repository::<Author, MemoryStore>::from_state(state);
+
+        // In atomic mode, nothing is deleted if any id is unknown
+        if mode == BulkMode::Atomic {
+            let mut found = Vec::with_capacity(ids.len());
+            for id in ids {
+                found.push(repository.find(id).await?);
+            }
+            if found.iter().any(Option::is_none) {
+                return Ok(found
+                    .into_iter()
+                    .map(|author| match author {
+                        Some(_) => Err(BulkItemError::NotApplied),
+                        None => Ok(None),
+                    })
+                    .collect());
+            }
+        }
+
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            results.push(repository.delete(id).await.map_err(BulkItemError::from));
+        }
+        Ok(results)
+    }
+}
+
+/// Stores the example authors, who have written the example contents except for the last
+pub async fn seed(store: &MemoryStore) -> Result<(), Error> {
+    for (index, name) in ["Alice", "Bob", "Carol", "Dave"].into_iter().enumerate() {
+        store
+            .insert(&Author {
+                id: format!("author-{}", index),
+                name: name.to_owned(),
+                created_at: chrono::DateTime::from_timestamp(1_767_225_600, 0).unwrap_or_default(),
+            })
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+pub use lily::prelude::*;
+
+#[endpoint(read, pagination = cursor, naming = plural_snake_case)]
+pub struct Comment {
+    #[lily(id)]
+    id: u32,
+    author_name: String,
+    text: String,
+    #[lily(hidden)]
+    moderation_note: Option<String>,
+    #[lily(created_at)]
+    posted_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ReadSingle for Comment {
+    async fn read_single(
+        _state: &Self::State,
+        _context: &Context,
+        id: &Self::Id,
+    ) -> Result<Option<Self>, Error> {
+        Ok(example_comments().find(|comment| &comment.id == id && comment.is_visible()))
+    }
+}
+
+impl ReadMultiple for Comment {
+    async fn read_multiple(
+        _state: &Self::State,
+        _context: &Context,
+        query: &ListQuery<Self>,
+    ) -> Result<Page<Self>, Error> {
+        let mut matching: Vec<Comment> = example_comments()
+            .filter(|comment| comment.is_visible() && query.filter.matches(comment))
+            .collect();
+        matching.sort_by(|a, b| query.compare(a, b));
+        let total = matching.len() as u64;
+        let items = matching
+            .into_iter()
+            .filter(|comment| query.is_after_cursor(comment))
+            .take(query.pagination.limit as usize)
+            .collect();
+        Ok(Page::new(items, total, &query.pagination))
+    }
+}
+
+impl Comment {
+    /// Comments with a moderation note are withheld from all read routes
+    fn is_visible(&self) -> bool {
+        self.moderation_note.is_none()
+    }
+}
+
+/// Returns the hard-coded comments served by the list route
+fn example_comments() -> impl Iterator<Item = Comment> {
+    (0..25).map(|index| Comment {
+        id: index,
+        author_name: String::from(["Alice", "Bob", "Carol"][index as usize % 3]),
+        text: format!("Comment number {}", index),
+        moderation_note: None,
+        posted_at: chrono::DateTime::from_timestamp(1_767_225_600 + i64::from(index) * 60, 0)
+            .unwrap_or_default(),
+    })
+}
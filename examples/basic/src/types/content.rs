@@ -1,82 +1,304 @@
 pub use lily::prelude::*;
 
-#[endpoint(create_single, read_single, update_single, delete_single)]
+use super::author::Author;
+use super::tag::Tag;
+use crate::state::AppState;
+
+#[endpoint(
+    create_single,
+    create_multiple,
+    read_single,
+    read_multiple,
+    update_single,
+    update_multiple,
+    replace_single,
+    replace_multiple,
+    delete_single,
+    delete_multiple,
+    update_payload(attr(serde(deny_unknown_fields))),
+    state = AppState
+)]
+#[persistent(
+    store = MemoryStore,
+    read_single,
+    read_multiple,
+    update_single,
+    delete_single
+)]
+#[derive(Debug)]
 pub struct Content {
+    #[lily(length(min = 1, max = 200))]
     title: String,
     body: String,
+    #[lily(length(max = 500))]
     summary: Option<String>,
+    #[lily(belongs_to = Author, on_delete = restrict)]
+    author_id: Option<String>,
+    #[serde(default)]
+    #[lily(many = Tag)]
+    tags: Vec<String>,
 }
 
 impl CreateSingle for Content {
-    async fn create_single(payload: &Self::CreatePayload) -> Result<Self, Error> {
-        if let "invalid" = payload.body.as_str() {
+    async fn create_single(
+        state: &Self::State,
+        _context: &Context,
+        payload: &Self::CreatePayload,
+    ) -> Result<Self, Error> {
+        if is_rejected(payload) {
             return Err(Error::Example);
         }
-        Ok(Content {
-            id: String::from("uuid-from-database"),
+        Repository::<Content, MemoryStore>::from_state(state)
+            .create(payload)
+            .await
+    }
+}
+
+impl CreateMultiple for Content {
+    async fn create_multiple(
+        state: &Self::State,
+        context: &Context,
+        payloads: &[Self::CreatePayload],
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Self, BulkItemError>>, Error> {
+        // In atomic mode, nothing is created if any payload is rejected
+        let aborted = mode == BulkMode::Atomic && payloads.iter().any(is_rejected);
+        let mut results = Vec::with_capacity(payloads.len());
+        for payload in payloads {
+            results.push(if is_rejected(payload) {
+                Err(BulkItemError::Rejected(rejection()))
+            } else if aborted {
+                Err(BulkItemError::NotApplied)
+            } else {
+                Content::create_single(state, context, payload)
+                    .await
+                    .map_err(BulkItemError::from)
+            });
+        }
+        Ok(results)
+    }
+}
+
+impl UpdateMultiple for Content {
+    // Also limits the contents a filter may select
+    const MAX_BULK_SIZE: usize = 20;
+
+    async fn update_multiple(
+        state: &Self::State,
+        _context: &Context,
+        selection: &Selection<Self>,
+        payload: &Self::UpdatePayload,
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Option<Self>, BulkItemError>>, Error> {
+        let repository = Repository::<Content, MemoryStore>::from_state(state);
+        let ids: Vec<String> = match selection {
+            Selection::Ids(ids) => ids.clone(),
+            Selection::Filter(filter) => state
+                .store
+                .list_matching(&ListQuery::all(), |content: &Content| {
+                    filter.matches(content)
+                })?
+                .items
+                .into_iter()
+                .map(|content| content.id)
+                .collect(),
+        };
+
+        // In atomic mode, nothing is updated if any id is unknown
+        if mode == BulkMode::Atomic {
+            let mut found = Vec::with_capacity(ids.len());
+            for id in &ids {
+                found.push(repository.find(id).await?);
+            }
+            if found.iter().any(Option::is_none) {
+                return Ok(found
+                    .into_iter()
+                    .map(|content| match content {
+                        Some(_) => Err(BulkItemError::NotApplied),
+                        None => Ok(None),
+                    })
+                    .collect());
+            }
+        }
+
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            results.push(match repository.update(&id, payload).await {
+                Ok(content) => Ok(Some(content)),
+                Err(Error::NotFound) => Ok(None),
+                Err(error) => Err(error.into()),
+            });
+        }
+        Ok(results)
+    }
+}
+
+impl ReplaceSingle for Content {
+    async fn replace_single(
+        state: &Self::State,
+        _context: &Context,
+        id: &Self::Id,
+        payload: &Self::ReplacePayload,
+    ) -> Result<Option<Replacement<Self>>, Error> {
+        let content = Content {
+            id: id.to_owned(),
             title: payload.title.clone(),
             body: payload.body.clone(),
             summary: payload.summary.clone(),
+            author_id: payload.author_id.clone(),
+            tags: payload.tags.clone(),
             created_at: chrono::Utc::now(),
-        })
+        };
+        // Unknown ids are upserted
+        match state.store.get::<Content>(id).await? {
+            Some(existing) => {
+                let content = Content {
+                    created_at: existing.created_at,
+                    ..content
+                };
+                state.store.update(&content).await?;
+                Ok(Some(Replacement::Replaced(content)))
+            }
+            None => {
+                state.store.insert(&content).await?;
+                Ok(Some(Replacement::Created(content)))
+            }
+        }
     }
 }
 
-impl ReadSingle for Content {
-    async fn read_single(id: &Self::Id) -> Result<Option<Self>, Error> {
-        if let "invalid" = id.as_str() {
-            return Err(Error::Unknown); // TODO: The user should not have to use lily errors, those are internal only
+impl ReplaceMultiple for Content {
+    async fn replace_multiple(
+        state: &Self::State,
+        context: &Context,
+        items: &[BulkReplaceItem<Self::Id, Self::ReplacePayload>],
+        _mode: BulkMode,
+    ) -> Result<Vec<Result<Option<Replacement<Self>>, BulkItemError>>, Error> {
+        // Replacing only fails if the store fails, so there is nothing to roll back in atomic mode
+        let mut results = Vec::with_capacity(items.len());
+        for item in items {
+            results.push(
+                Content::replace_single(state, context, &item.id, &item.data)
+                    .await
+                    .map_err(BulkItemError::from),
+            );
         }
-        if let "unknown" = id.as_str() {
-            return Ok(None);
-        }
-        Ok(Some(Content {
-            id: id.to_owned(),
-            title: String::from("Lorem Ipsum"),
-            body: String::from("#Doloribus Quia\nTenetur delectus rem:\n- Eveniet\n- Fugiat"),
-            summary: Some(String::from(
-                "Lorem ipsum dolor sit amet consectetur adipisicing elit.",
-            )),
-            created_at: chrono::Utc::now(),
-        }))
+        Ok(results)
     }
 }
 
-impl UpdateSingle for Content {
-    async fn update_single(id: &Self::Id, payload: &Self::UpdatePayload) -> Result<Self, Error> {
-        let existing_title = payload.title.clone().unwrap_or("existing title".to_owned());
-        if let "invalid" = existing_title.as_str() {
-            return Err(Error::Example);
+impl DeleteMultiple for Content {
+    async fn delete_multiple(
+        state: &Self::State,
+        _context: &Context,
+        ids: &[Self::Id],
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Option<Self>, BulkItemError>>, Error> {
+        let repository = Repository::<Content, MemoryStore>::from_state(state);
+
+        // In atomic mode, nothing is deleted if any id is unknown
+        if mode == BulkMode::Atomic {
+            let mut found = Vec::with_capacity(ids.len());
+            for id in ids {
+                found.push(repository.find(id).await?);
+            }
+            if found.iter().any(Option::is_none) {
+                return Ok(found
+                    .into_iter()
+                    .map(|content| match content {
+                        Some(_) => Err(BulkItemError::NotApplied),
+                        None => Ok(None),
+                    })
+                    .collect());
+            }
         }
-        Ok(Content {
-            id: id.clone(),
-            title: existing_title,
-            body: payload.body.clone().unwrap_or("existing body".to_owned()),
-            summary: payload
-                .summary
-                .clone()
-                .or(Some("existing summary".to_owned())),
-            created_at: chrono::Utc::now(),
-        })
+
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            results.push(repository.delete(id).await.map_err(BulkItemError::from));
+        }
+        Ok(results)
     }
 }
 
-impl DeleteSingle for Content {
-    async fn delete_single(id: &Self::Id) -> Result<Option<Self>, Error> {
-        if let "invalid" = id.as_str() {
-            return Err(Error::Unknown); // TODO: The user should not have to use lily errors, those are internal only
+impl ManyToMany<Tag> for Content {
+    async fn link(
+        state: &Self::State,
+        _context: &Context,
+        id: &Self::Id,
+        related_id: &String,
+    ) -> Result<bool, Error> {
+        let Some(mut content) = state.store.get::<Content>(id).await? else {
+            return Ok(false);
+        };
+        if !content.tags.contains(related_id) {
+            content.tags.push(related_id.clone());
+            state.store.update(&content).await?;
         }
-        if let "unknown" = id.as_str() {
+        Ok(true)
+    }
+
+    async fn unlink(
+        state: &Self::State,
+        _context: &Context,
+        id: &Self::Id,
+        related_id: &String,
+    ) -> Result<Option<bool>, Error> {
+        let Some(mut content) = state.store.get::<Content>(id).await? else {
             return Ok(None);
+        };
+        if !content.tags.contains(related_id) {
+            return Ok(Some(false));
         }
-        Ok(Some(Content {
-            id: id.to_owned(),
-            title: String::from("Lorem Ipsum"),
-            body: String::from("#Doloribus Quia\nTenetur delectus rem:\n- Eveniet\n- Fugiat"),
-            summary: Some(String::from(
-                "Lorem ipsum dolor sit amet consectetur adipisicing elit.",
-            )),
-            created_at: chrono::Utc::now(),
-        }))
+        content.tags.retain(|tag| tag != related_id);
+        state.store.update(&content).await?;
+        Ok(Some(true))
+    }
+
+    async fn read_linked(
+        state: &Self::State,
+        _context: &Context,
+        related_id: &String,
+        query: &ListQuery<Self>,
+    ) -> Result<Page<Self>, Error> {
+        state
+            .store
+            .list_matching(query, |content: &Content| content.tags.contains(related_id))
+    }
+}
+
+/// Rejects contents with the body "invalid", to show how failing implementations are reported
+fn is_rejected(payload: &<Content as Endpoint>::CreatePayload) -> bool {
+    payload.body == "invalid"
+}
+
+/// The problem reported for a rejected content in a bulk request
+fn rejection() -> Problem {
+    Problem::ValidationFailed {
+        errors: vec![ValidationError::new("/body", "must not be \"invalid\"")],
+    }
+}
+
+/// Stores the example contents, written by the first three authors
+pub async fn seed(store: &MemoryStore, count: usize) -> Result<(), Error> {
+    for index in 0..count {
+        store
+            .insert(&Content {
+                id: format!("content-{}", index),
+                title: format!("Lorem Ipsum {}", index),
+                body: String::from("#Doloribus Quia\nTenetur delectus rem:\n- Eveniet\n- Fugiat"),
+                summary: (index % 2 == 0).then(|| {
+                    String::from("Lorem ipsum dolor sit amet consectetur adipisicing elit.")
+                }),
+                author_id: Some(format!("author-{}", index % 3)),
+                tags: vec![format!("tag-{}", index % 2)],
+                created_at: chrono::DateTime::from_timestamp(
+                    1_767_225_600 + index as i64 * 3600,
+                    0,
+                )
+                .unwrap_or_default(),
+            })
+            .await?;
     }
+    Ok(())
 }
@@ -1 +1,16 @@
+use lily::prelude::*;
+
+pub mod attachment;
+pub mod author;
+pub mod comment;
 pub mod content;
+pub mod tag;
+
+/// Fills an empty store with the example authors and contents
+pub async fn seed(store: &MemoryStore) -> Result<(), Error> {
+    if store.list(&ListQuery::<author::Author>::all()).await?.total > 0 {
+        return Ok(());
+    }
+    author::seed(store).await?;
+    content::seed(store, 42).await
+}
@@ -0,0 +1,52 @@
+pub use lily::prelude::*;
+
+use crate::state::AppState;
+
+#[endpoint(read, state = AppState)]
+pub struct Tag {
+    name: String,
+}
+
+impl ReadSingle for Tag {
+    async fn read_single(
+        _state: &Self::State,
+        context: &Context,
+        id: &Self::Id,
+    ) -> Result<Option<Self>, Error> {
+        Ok(example_tags(context).find(|tag| &tag.id == id))
+    }
+}
+
+impl ReadMultiple for Tag {
+    async fn read_multiple(
+        _state: &Self::State,
+        context: &Context,
+        query: &ListQuery<Self>,
+    ) -> Result<Page<Self>, Error> {
+        let mut matching: Vec<Tag> = example_tags(context)
+            .filter(|tag| query.filter.matches(tag))
+            .collect();
+        matching.sort_by(|a, b| query.compare(a, b));
+        let total = matching.len() as u64;
+        let items = matching
+            .into_iter()
+            .skip(query.pagination.offset as usize)
+            .take(query.pagination.limit as usize)
+            .collect();
+        Ok(Page::new(items, total, &query.pagination))
+    }
+}
+
+/// Returns the hard-coded tags, which are linked to the example contents, named in the requested
+/// language
+fn example_tags(context: &Context) -> impl Iterator<Item = Tag> {
+    let names = match context.locale() {
+        Some(locale) if locale.starts_with("de") => ["Neuigkeiten", "Anleitung", "Archiv"],
+        _ => ["news", "guide", "archive"],
+    };
+    names.into_iter().enumerate().map(|(index, name)| Tag {
+        id: format!("tag-{}", index),
+        name: name.to_owned(),
+        created_at: chrono::DateTime::from_timestamp(1_767_225_600, 0).unwrap_or_default(),
+    })
+}
@@ -0,0 +1,33 @@
+use axum::Router;
+use axum::extract::FromRef;
+use lily::prelude::*;
+
+mod post;
+
+/// The state shared by the handlers of the endpoints
+#[derive(Clone)]
+pub struct AppState {
+    pub store: SqliteStore,
+}
+
+impl FromRef<AppState> for SqliteStore {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let path = std::env::var("LILY_DATABASE").unwrap_or_else(|_| "example.db".to_owned());
+    let store = SqliteStore::open(&path).expect("Failed to open the database");
+    store
+        .create_table::<post::Post>()
+        .expect("Failed to create the tables");
+
+    let state = AppState { store };
+    let app = Router::new().merge(post::Post::routes()).with_state(state);
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    println!("Running on http://127.0.0.1:3000/post, storing to {}", path);
+    axum::serve(listener, app).await.unwrap();
+}
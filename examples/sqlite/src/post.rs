@@ -0,0 +1,28 @@
+pub use lily::prelude::*;
+
+use crate::AppState;
+
+/// A post of a small site, kept in the `post` table
+///
+/// The table has a nullable `TEXT` column for the summary, a `TIMESTAMP`
+/// column for the publication date and a `TEXT` column holding the tags as JSON.
+#[endpoint(
+    create_single,
+    read,
+    update_single,
+    delete_single,
+    pagination = cursor,
+    state = AppState
+)]
+#[persistent(store = SqliteStore)]
+pub struct Post {
+    #[lily(length(min = 1, max = 200))]
+    title: String,
+    body: String,
+    summary: Option<String>,
+    #[serde(default)]
+    published: bool,
+    published_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
@@ -10,4 +10,12 @@ pub enum Error {
     Example,
     #[error("An unknown error occured.")]
     Unknown,
+    #[error("The resource was not found.")]
+    NotFound,
+    #[error("The storage backend failed: {0}")]
+    Storage(String),
+    #[error("The stored record is invalid: {0}")]
+    InvalidRecord(String),
+    #[error("The id is already taken: {0}")]
+    IdTaken(String),
 }
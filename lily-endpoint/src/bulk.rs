@@ -0,0 +1,258 @@
+//! Provides the types shared by bulk routes, which apply one operation to
+//! many resources in a single request.
+//!
+//! Bulk routes report the outcome of every item individually in a
+//! [`BulkReport`]. Depending on the [`BulkMode`] requested by the client, the
+//! implementation either applies as many items as possible or none at all.
+
+use crate::problems::{JsonProblem, Problem};
+use axum::http::StatusCode;
+use lily_core::Error;
+use serde::{Deserialize, Serialize};
+
+/// Decides how a bulk operation deals with failing items.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BulkMode {
+    /// Every item is applied on its own; failing items do not affect the others.
+    #[default]
+    Partial,
+    /// Either all items are applied or none. Implementations must roll back
+    /// already applied items as soon as one item fails.
+    Atomic,
+}
+
+/// The query parameters accepted by bulk routes.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BulkQuery {
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+impl BulkQuery {
+    pub fn mode(&self) -> BulkMode {
+        if self.atomic {
+            BulkMode::Atomic
+        } else {
+            BulkMode::Partial
+        }
+    }
+}
+
+/// The request body of a bulk update, applying one patch to a set of resources.
+///
+/// Without `ids`, the patch is applied to every resource matching the filter
+/// given in the query string.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BulkUpdate<Id, P> {
+    pub ids: Option<Vec<Id>>,
+    pub patch: P,
+}
+
+/// A single item of a bulk replace, pairing the id with the new resource.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BulkReplaceItem<Id, P> {
+    pub id: Id,
+    pub data: P,
+}
+
+/// The request body of a bulk delete.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BulkDelete<Id> {
+    pub ids: Vec<Id>,
+}
+
+/// The failure of a single item, returned by the implementations of the bulk
+/// traits.
+pub enum BulkItemError {
+    /// The item was rejected and is reported with the given problem, e.g. a
+    /// `422` for a payload that breaks a business rule.
+    Rejected(Problem),
+    /// The item was not applied because another item of a
+    /// [`BulkMode::Atomic`] request failed.
+    NotApplied,
+    /// The item failed unexpectedly and is reported as an internal error.
+    Failed(Error),
+}
+
+impl From<Error> for BulkItemError {
+    fn from(error: Error) -> Self {
+        BulkItemError::Failed(error)
+    }
+}
+
+impl From<Problem> for BulkItemError {
+    fn from(problem: Problem) -> Self {
+        BulkItemError::Rejected(problem)
+    }
+}
+
+impl From<BulkItemError> for Problem {
+    fn from(error: BulkItemError) -> Self {
+        match error {
+            BulkItemError::Rejected(problem) => problem,
+            BulkItemError::NotApplied => Problem::BulkItemNotApplied,
+            BulkItemError::Failed(_) => Problem::InternalError,
+        }
+    }
+}
+
+/// The items of a [`BulkMode::Partial`] request that were rejected before
+/// reaching the implementation, e.g. because their payload is invalid.
+///
+/// The implementation only receives the remaining items; their results are
+/// merged back with the rejected items in the order of the request.
+///
+/// # Examples
+/// ```
+/// use lily_endpoint::bulk::BulkRejections;
+/// use lily_endpoint::problems::Problem;
+///
+/// let mut rejections = BulkRejections::new(3);
+/// let items = rejections.reject(vec!["a", "", "c"], vec![None, Some(Problem::InternalError), None]);
+/// assert_eq!(items, vec!["a", "c"]);
+///
+/// let results = rejections.merge(vec![Ok("A"), Ok("C")]);
+/// assert!(results[1].is_err());
+/// assert_eq!(results[2].as_ref().ok(), Some(&"C"));
+/// ```
+pub struct BulkRejections {
+    /// The positions in the request of the items that are not rejected.
+    positions: Vec<usize>,
+    rejected: Vec<(usize, Problem)>,
+}
+
+impl BulkRejections {
+    /// Starts with none of the `len` items of a request rejected.
+    pub fn new(len: usize) -> Self {
+        BulkRejections {
+            positions: (0..len).collect(),
+            rejected: Vec::new(),
+        }
+    }
+
+    /// Rejects every item with a problem, returning the remaining items.
+    ///
+    /// `items` are the remaining items of earlier calls, `problems` holds an
+    /// entry for each of them.
+    pub fn reject<P>(&mut self, items: Vec<P>, problems: Vec<Option<Problem>>) -> Vec<P> {
+        let mut positions = Vec::with_capacity(items.len());
+        let mut remaining = Vec::with_capacity(items.len());
+        for ((position, item), problem) in self.positions.iter().zip(items).zip(problems) {
+            match problem {
+                Some(problem) => self.rejected.push((*position, problem)),
+                None => {
+                    positions.push(*position);
+                    remaining.push(item);
+                }
+            }
+        }
+        self.positions = positions;
+        remaining
+    }
+
+    /// Returns `true` if any item was rejected.
+    pub fn is_any_rejected(&self) -> bool {
+        !self.rejected.is_empty()
+    }
+
+    /// Merges the results of the remaining items with the problems of the
+    /// rejected items, in the order of the request.
+    pub fn merge<T>(self, results: Vec<Result<T, Problem>>) -> Vec<Result<T, Problem>> {
+        let mut merged: Vec<(usize, Result<T, Problem>)> = self
+            .positions
+            .into_iter()
+            .zip(results)
+            .chain(
+                self.rejected
+                    .into_iter()
+                    .map(|(position, problem)| (position, Err(problem))),
+            )
+            .collect();
+        merged.sort_by_key(|(position, _)| *position);
+        merged.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+/// The outcome of a single item of a bulk operation.
+#[derive(Serialize)]
+pub struct BulkItem<T: Serialize> {
+    /// The position of the item in the request.
+    pub index: usize,
+    #[serde(with = "http_serde::status_code")]
+    pub status: StatusCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<JsonProblem>,
+}
+
+/// The response body of a bulk route, listing the outcome of every item.
+#[derive(Serialize)]
+pub struct BulkReport<T: Serialize> {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItem<T>>,
+}
+
+impl<T: Serialize> BulkReport<T> {
+    /// Creates a report from the per-item results of a bulk operation.
+    ///
+    /// Successful items are reported with the given `success` status. In
+    /// [`BulkMode::Atomic`], a single failing item causes all successful items
+    /// to be reported as not applied.
+    pub fn from_results(
+        results: Vec<Result<T, Problem>>,
+        success: StatusCode,
+        mode: BulkMode,
+    ) -> Self {
+        let results = results
+            .into_iter()
+            .map(|result| result.map(|data| (success, data)))
+            .collect();
+        Self::from_statuses(results, mode)
+    }
+
+    /// Creates a report from per-item results that carry their own success
+    /// status, e.g. `201 Created` for upserted items of a bulk replace.
+    pub fn from_statuses(results: Vec<Result<(StatusCode, T), Problem>>, mode: BulkMode) -> Self {
+        let aborted = mode == BulkMode::Atomic && results.iter().any(Result::is_err);
+        let results: Vec<BulkItem<T>> = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| match result {
+                Ok(_) if aborted => BulkItem::failed(index, Problem::BulkItemNotApplied),
+                Ok((status, data)) => BulkItem {
+                    index,
+                    status,
+                    data: Some(data),
+                    problem: None,
+                },
+                Err(problem) => BulkItem::failed(index, problem),
+            })
+            .collect();
+        let succeeded = results.iter().filter(|item| item.problem.is_none()).count();
+
+        BulkReport {
+            failed: results.len() - succeeded,
+            succeeded,
+            results,
+        }
+    }
+
+    /// Returns `true` if every item of the bulk operation was applied.
+    pub fn is_success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl<T: Serialize> BulkItem<T> {
+    fn failed(index: usize, problem: Problem) -> Self {
+        let problem: JsonProblem = problem.into();
+        BulkItem {
+            index,
+            status: problem.status,
+            data: None,
+            problem: Some(problem),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Provides the context of a request, handed to every trait method.
+//!
+//! The [`Context`] is extracted in every generated handler. Besides the
+//! request id, the requested locale and the raw headers, it carries the
+//! extensions of the request, so middleware can stash typed values like the
+//! authenticated principal for the implementations:
+//!
+//! ```
+//! use axum::{extract::Request, middleware::Next, response::Response};
+//!
+//! #[derive(Clone)]
+//! struct User {
+//!     name: String,
+//! }
+//!
+//! async fn authenticate(mut request: Request, next: Next) -> Response {
+//!     request.extensions_mut().insert(User { name: "alice".to_owned() });
+//!     next.run(request).await
+//! }
+//! ```
+//!
+//! The implementations then read it with `context.get::<User>()`.
+
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{Extensions, HeaderMap, header};
+use std::convert::Infallible;
+
+/// The header carrying the id of a request, generated if the client or a
+/// proxy does not send one.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The context of a single request.
+#[derive(Clone, Debug)]
+pub struct Context {
+    request_id: String,
+    locale: Option<String>,
+    headers: HeaderMap,
+    extensions: Extensions,
+}
+
+impl Context {
+    /// Creates the context of a request from its parts.
+    ///
+    /// # Examples
+    /// ```
+    /// use axum::http::Request;
+    /// use lily_endpoint::context::Context;
+    ///
+    /// let request = Request::builder()
+    ///     .header("x-request-id", "abc")
+    ///     .header("accept-language", "de-CH, de;q=0.9, en;q=0.8")
+    ///     .body(())
+    ///     .unwrap();
+    /// let (parts, _) = request.into_parts();
+    /// let context = Context::from_parts(&parts);
+    /// assert_eq!(context.request_id(), "abc");
+    /// assert_eq!(context.locale(), Some("de-CH"));
+    /// ```
+    pub fn from_parts(parts: &Parts) -> Self {
+        let request_id = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+            .unwrap_or_else(generate_request_id);
+        let locale = parts
+            .headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(preferred_locale);
+        Context {
+            request_id,
+            locale,
+            headers: parts.headers.clone(),
+            extensions: parts.extensions.clone(),
+        }
+    }
+
+    /// The id of the request, taken from the `x-request-id` header or generated.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// The most preferred locale of the `Accept-Language` header, if any.
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    /// The raw headers of the request.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns a typed value stashed in the extensions of the request.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
+    /// Stashes a typed value, replacing and returning the previous one of the same type.
+    pub fn insert<T: Clone + Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.extensions.insert(value)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Context {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Context::from_parts(parts))
+    }
+}
+
+/// Returns the locale with the highest quality, ignoring the wildcard.
+fn preferred_locale(accept_language: &str) -> Option<String> {
+    accept_language
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let locale = parts.next()?.trim();
+            let quality = parts
+                .find_map(|parameter| parameter.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |quality| quality.parse::<f32>().ok())?;
+            (!locale.is_empty() && locale != "*" && quality > 0.0).then_some((locale, quality))
+        })
+        .fold(
+            None,
+            |best: Option<(&str, f32)>, (locale, quality)| match best {
+                Some((_, best_quality)) if best_quality >= quality => best,
+                _ => Some((locale, quality)),
+            },
+        )
+        .map(|(locale, _)| locale.to_owned())
+}
+
+fn generate_request_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("Failed to generate request id");
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,405 @@
+//! Provides cursor-based pagination for list routes.
+//!
+//! Offset pagination skips or repeats items when the collection changes
+//! between two requests. In cursor mode, every page instead carries an opaque
+//! `next_cursor`, which encodes the sort key and id of the last item of the
+//! page. The next page starts right after that item, no matter how many items
+//! were added or removed in the meantime.
+//!
+//! Cursors are signed with HMAC-SHA256, so clients can not forge or modify
+//! them. The signing secret is random per process unless it is configured via
+//! [`set_secret`], which is required when running several instances.
+
+use crate::problems::Problem;
+use crate::routing::Endpoint;
+use crate::sorting::{Sort, SortDirection, SortField};
+use axum::Json;
+use axum::http::{HeaderMap, HeaderValue, Uri, header::LINK};
+use axum::response::{IntoResponse, Response};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::Sha256;
+use std::cmp::Ordering;
+use std::sync::OnceLock;
+
+static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Sets the secret used to sign cursors.
+///
+/// Must be called before the first cursor is created or decoded, which
+/// otherwise generates a random secret. Returns `false` if the same secret has
+/// already been set.
+///
+/// # Panics
+/// Panics if a different secret has already been set or generated.
+#[must_use]
+pub fn set_secret(secret: impl Into<Vec<u8>>) -> bool {
+    match SECRET.set(secret.into()) {
+        Ok(()) => true,
+        Err(secret) if secret == self::secret() => false,
+        Err(_) => panic!("The cursor secret can not be changed once it is set or used"),
+    }
+}
+
+fn secret() -> &'static [u8] {
+    SECRET.get_or_init(|| {
+        let mut secret = vec![0; 32];
+        getrandom::fill(&mut secret).expect("Failed to generate cursor secret");
+        secret
+    })
+}
+
+/// The raw query parameters of a list request in cursor mode.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct CursorQuery {
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+    pub cursor: Option<String>,
+}
+
+/// The position of an item in a sorted list, used as the start of a page.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// The sort keys the cursor was created for, e.g. `-created_at,title`.
+    #[serde(rename = "s")]
+    pub sort: String,
+    /// The values of the sort keys of the item.
+    #[serde(rename = "k")]
+    pub key: Vec<serde_json::Value>,
+    /// The id of the item, breaking ties between equal sort keys.
+    #[serde(rename = "i")]
+    pub id: serde_json::Value,
+}
+
+impl Cursor {
+    /// Creates a cursor pointing at `item`, sorted by `sort`.
+    pub fn after<E: Endpoint>(item: &E, sort: &[Sort<E::SortField>]) -> Self
+    where
+        E::Id: Serialize,
+    {
+        Cursor {
+            sort: sort_spec(sort),
+            key: sort.iter().map(|sort| sort.field.value(item)).collect(),
+            id: serde_json::to_value(item.id()).unwrap_or_default(),
+        }
+    }
+
+    /// Checks whether the cursor was created for the given sort keys.
+    pub fn matches_sort<T, F: SortField<T>>(&self, sort: &[Sort<F>]) -> bool {
+        self.sort == sort_spec(sort) && self.key.len() == sort.len()
+    }
+
+    /// Checks that the cursor was created for the given sort keys and that it
+    /// holds values of the sort fields and an id of type `Id`, which
+    /// [`Cursor::precedes`] compares the items to.
+    pub fn check<T, F: SortField<T>, Id: DeserializeOwned>(
+        &self,
+        sort: &[Sort<F>],
+    ) -> Result<(), Problem> {
+        if !self.matches_sort(sort) {
+            return Err(Problem::InvalidQuery {
+                detail: "The cursor was created for a different sort order.".to_owned(),
+            });
+        }
+        let is_valid = sort
+            .iter()
+            .zip(&self.key)
+            .all(|(sort, value)| sort.field.accepts_value(value))
+            && serde_json::from_value::<Id>(self.id.clone()).is_ok();
+        if is_valid {
+            Ok(())
+        } else {
+            Err(invalid_cursor())
+        }
+    }
+
+    /// Checks whether `item` comes strictly after the cursor, where items are
+    /// ordered by `sort` and then by id.
+    ///
+    /// Items are never after a cursor that fails [`Cursor::check`], which the
+    /// list route rejects before the items are read.
+    pub fn precedes<E: Endpoint>(&self, item: &E, sort: &[Sort<E::SortField>]) -> bool
+    where
+        E::Id: PartialOrd + DeserializeOwned,
+    {
+        for (sort, value) in sort.iter().zip(&self.key) {
+            let Some(ordering) = sort.field.compare_value(item, value) else {
+                return false;
+            };
+            let ordering = match sort.direction {
+                SortDirection::Ascending => ordering,
+                SortDirection::Descending => ordering.reverse(),
+            };
+            if ordering.is_ne() {
+                return ordering == Ordering::Greater;
+            }
+        }
+        serde_json::from_value::<E::Id>(self.id.clone())
+            .is_ok_and(|id| item.id().partial_cmp(&id) == Some(Ordering::Greater))
+    }
+
+    /// Encodes and signs the cursor.
+    pub fn encode(&self) -> String {
+        let payload = serde_json::to_vec(self).unwrap_or_default();
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret()).expect("HMAC accepts any key length");
+        mac.update(&payload);
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// Verifies and decodes a cursor created by [`Cursor::encode`].
+    pub fn decode(raw: &str) -> Result<Self, Problem> {
+        let (payload, signature) = raw.split_once('.').ok_or_else(invalid_cursor)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| invalid_cursor())?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| invalid_cursor())?;
+
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret()).expect("HMAC accepts any key length");
+        mac.update(&payload);
+        mac.verify_slice(&signature).map_err(|_| invalid_cursor())?;
+        serde_json::from_slice(&payload).map_err(|_| invalid_cursor())
+    }
+}
+
+fn invalid_cursor() -> Problem {
+    Problem::InvalidQuery {
+        detail: "The cursor is invalid.".to_owned(),
+    }
+}
+
+/// Formats sort keys the way they are passed in the query string.
+fn sort_spec<T, F: SortField<T>>(sort: &[Sort<F>]) -> String {
+    sort.iter()
+        .map(|sort| match sort.direction {
+            SortDirection::Ascending => sort.field.name().to_owned(),
+            SortDirection::Descending => format!("-{}", sort.field.name()),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A single page of items returned by a list route in cursor mode.
+///
+/// The response carries a `Link` header with `rel="next"` (RFC 8288) as long
+/// as there are more items.
+#[derive(Clone, Debug, Serialize)]
+pub struct CursorPage<T: Serialize> {
+    pub items: Vec<T>,
+    pub limit: u64,
+    pub next_cursor: Option<String>,
+    #[serde(skip)]
+    next_link: Option<String>,
+}
+
+impl<T: Serialize> CursorPage<T> {
+    /// Creates a page from up to `limit + 1` items; the additional item only
+    /// signals that there is a next page and is dropped.
+    ///
+    /// The `next_cursor` is created from the last item of the page, the link
+    /// to the next page from the URI of the current request.
+    pub fn new<E: Endpoint>(
+        mut items: Vec<E>,
+        limit: u64,
+        sort: &[Sort<E::SortField>],
+        uri: &Uri,
+        project: impl FnMut(E) -> T,
+    ) -> Self
+    where
+        E::Id: Serialize,
+    {
+        let has_next = items.len() as u64 > limit;
+        items.truncate(limit as usize);
+        let next_cursor = items
+            .last()
+            .filter(|_| has_next)
+            .map(|item| Cursor::after(item, sort).encode());
+        let next_link = next_cursor.as_ref().map(|cursor| next_link(uri, cursor));
+
+        CursorPage {
+            items: items.into_iter().map(project).collect(),
+            limit,
+            next_cursor,
+            next_link,
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for CursorPage<T> {
+    fn into_response(self) -> Response {
+        let mut headers = HeaderMap::new();
+        if let Some(link) = self
+            .next_link
+            .as_ref()
+            .and_then(|link| HeaderValue::from_str(&format!("<{}>; rel=\"next\"", link)).ok())
+        {
+            headers.insert(LINK, link);
+        }
+        (headers, Json(self)).into_response()
+    }
+}
+
+/// Replaces the `cursor` parameter of the request URI with the next cursor.
+fn next_link(uri: &Uri, cursor: &str) -> String {
+    let mut pairs: Vec<(String, String)> =
+        serde_urlencoded::from_str(uri.query().unwrap_or_default()).unwrap_or_default();
+    pairs.retain(|(name, _)| name != "cursor");
+    pairs.push(("cursor".to_owned(), cursor.to_owned()));
+    format!(
+        "{}?{}",
+        uri.path(),
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Item {
+        title: String,
+        rating: u8,
+    }
+
+    #[derive(Clone, Copy)]
+    enum ItemSortField {
+        Title,
+        Rating,
+    }
+
+    impl SortField<Item> for ItemSortField {
+        const FIELDS: &'static [&'static str] = &["title", "rating"];
+
+        fn from_name(name: &str) -> Option<Self> {
+            match name {
+                "title" => Some(ItemSortField::Title),
+                "rating" => Some(ItemSortField::Rating),
+                _ => None,
+            }
+        }
+
+        fn name(&self) -> &'static str {
+            match self {
+                ItemSortField::Title => "title",
+                ItemSortField::Rating => "rating",
+            }
+        }
+
+        fn compare(&self, a: &Item, b: &Item) -> Ordering {
+            match self {
+                ItemSortField::Title => a.title.cmp(&b.title),
+                ItemSortField::Rating => a.rating.cmp(&b.rating),
+            }
+        }
+
+        fn value(&self, item: &Item) -> serde_json::Value {
+            match self {
+                ItemSortField::Title => serde_json::json!(item.title),
+                ItemSortField::Rating => serde_json::json!(item.rating),
+            }
+        }
+
+        fn accepts_value(&self, value: &serde_json::Value) -> bool {
+            match self {
+                ItemSortField::Title => value.is_string(),
+                ItemSortField::Rating => serde_json::from_value::<u8>(value.clone()).is_ok(),
+            }
+        }
+
+        fn compare_value(&self, _item: &Item, _value: &serde_json::Value) -> Option<Ordering> {
+            unimplemented!("cursors are not compared to items here")
+        }
+    }
+
+    fn cursor() -> Cursor {
+        Cursor {
+            sort: "-rating,title".to_owned(),
+            key: vec![serde_json::json!(4), serde_json::json!("Rust")],
+            id: serde_json::json!("content-1"),
+        }
+    }
+
+    fn sort(spec: &[(ItemSortField, SortDirection)]) -> Vec<Sort<ItemSortField>> {
+        spec.iter()
+            .map(|&(field, direction)| Sort { field, direction })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_an_encoded_cursor() {
+        let encoded = cursor().encode();
+        assert_eq!(Cursor::decode(&encoded).ok(), Some(cursor()));
+    }
+
+    #[test]
+    fn rejects_a_modified_payload() {
+        let encoded = cursor().encode();
+        let (payload, signature) = encoded.split_once('.').unwrap();
+        let mut payload = URL_SAFE_NO_PAD.decode(payload).unwrap();
+        // Turns the id `content-1` into `content-0`
+        let position = payload.len() - 3;
+        payload[position] ^= 1;
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode(&payload), signature);
+        assert!(matches!(
+            Cursor::decode(&forged),
+            Err(Problem::InvalidQuery { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_modified_signature() {
+        let encoded = cursor().encode();
+        let (payload, signature) = encoded.split_once('.').unwrap();
+        let mut signature = URL_SAFE_NO_PAD.decode(signature).unwrap();
+        signature[0] ^= 1;
+        let forged = format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(&signature));
+        assert!(Cursor::decode(&forged).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        for raw in ["", "abc", "abc.def", "not base64!.abc"] {
+            assert!(Cursor::decode(raw).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn only_matches_the_sort_it_was_created_for() {
+        let cursor = cursor();
+        assert!(cursor.matches_sort(&sort(&[
+            (ItemSortField::Rating, SortDirection::Descending),
+            (ItemSortField::Title, SortDirection::Ascending),
+        ])));
+        assert!(!cursor.matches_sort(&sort(&[
+            (ItemSortField::Rating, SortDirection::Ascending),
+            (ItemSortField::Title, SortDirection::Ascending),
+        ])));
+        assert!(!cursor.matches_sort(&sort(&[(ItemSortField::Rating, SortDirection::Descending)])));
+        assert!(!cursor.matches_sort::<Item, ItemSortField>(&[]));
+    }
+
+    #[test]
+    fn rejects_values_of_other_types() {
+        let sort = sort(&[
+            (ItemSortField::Rating, SortDirection::Descending),
+            (ItemSortField::Title, SortDirection::Ascending),
+        ]);
+        assert!(cursor().check::<Item, _, String>(&sort).is_ok());
+
+        let mut cursor = cursor();
+        cursor.key[0] = serde_json::json!("four");
+        assert!(matches!(
+            cursor.check::<Item, _, String>(&sort),
+            Err(Problem::InvalidQuery { detail }) if detail == "The cursor is invalid."
+        ));
+        assert!(self::cursor().check::<Item, _, u32>(&sort).is_err());
+    }
+}
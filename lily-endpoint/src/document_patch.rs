@@ -0,0 +1,322 @@
+//! Provides JSON Merge Patch (RFC 7396) and JSON Patch (RFC 6902) support for
+//! update routes.
+//!
+//! Besides the generated update payload sent as `application/json`, update
+//! routes accept `application/merge-patch+json` and `application/json-patch+json`
+//! documents. These are applied to the serialized resource, and the result is
+//! converted back into an update payload:
+//!
+//! 1. The patched resource is re-validated against the shape of the create
+//!    payload, so that required fields can not be removed.
+//! 2. Fields that are not part of the update payload, like the id, must not
+//!    be changed by the patch.
+//!
+//! Required write-only fields are never serialized, so resources with such
+//! fields can only be updated with the update payload.
+
+use crate::problems::Problem;
+use crate::routing::Endpoint;
+use crate::validation::{Validate, ValidationError};
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+pub const MERGE_PATCH_MEDIA_TYPE: &str = "application/merge-patch+json";
+pub const JSON_PATCH_MEDIA_TYPE: &str = "application/json-patch+json";
+
+/// A single operation of a JSON Patch document.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Test { path: String, value: Value },
+}
+
+/// A patch document applied to the serialized resource.
+#[derive(Clone, Debug)]
+pub enum DocumentPatch {
+    /// A JSON Merge Patch (RFC 7396).
+    Merge(Value),
+    /// A JSON Patch (RFC 6902).
+    Json(Vec<PatchOperation>),
+}
+
+impl DocumentPatch {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            DocumentPatch::Merge(_) => MERGE_PATCH_MEDIA_TYPE,
+            DocumentPatch::Json(_) => JSON_PATCH_MEDIA_TYPE,
+        }
+    }
+
+    /// Applies the patch to a JSON document.
+    ///
+    /// # Examples
+    /// ```
+    /// use lily_endpoint::document_patch::{DocumentPatch, PatchOperation};
+    /// use serde_json::json;
+    ///
+    /// let mut document = json!({ "title": "a", "summary": "b" });
+    /// let patch = DocumentPatch::Merge(json!({ "summary": null }));
+    /// patch.apply_to(&mut document).ok().unwrap();
+    /// assert_eq!(document, json!({ "title": "a" }));
+    ///
+    /// let patch = DocumentPatch::Json(vec![
+    ///     PatchOperation::Test { path: "/title".into(), value: json!("a") },
+    ///     PatchOperation::Replace { path: "/title".into(), value: json!("c") },
+    /// ]);
+    /// patch.apply_to(&mut document).ok().unwrap();
+    /// assert_eq!(document, json!({ "title": "c" }));
+    /// ```
+    pub fn apply_to(&self, document: &mut Value) -> Result<(), Problem> {
+        match self {
+            DocumentPatch::Merge(patch) => {
+                merge_patch(document, patch);
+                Ok(())
+            }
+            DocumentPatch::Json(operations) => operations
+                .iter()
+                .try_for_each(|operation| apply_operation(document, operation)),
+        }
+    }
+
+    /// Applies the patch to a resource and converts the result into an update payload.
+    pub fn to_update_payload<E: Endpoint>(&self, current: &E) -> Result<E::UpdatePayload, Problem> {
+        let original = serde_json::to_value(current).map_err(|error| {
+            eprintln!("Error serializing resource for patch: {}", error);
+            Problem::InternalError
+        })?;
+        let mut document = original.clone();
+        self.apply_to(&mut document)?;
+        let (Value::Object(original), Value::Object(document)) = (original, document) else {
+            return Err(Problem::PatchNotApplicable {
+                detail: "The patched resource is not an object.".to_owned(),
+            });
+        };
+
+        // Fields outside of the update payload must keep their value
+        let errors: Vec<ValidationError> = original
+            .keys()
+            .chain(document.keys())
+            .filter(|key| !E::UPDATE_FIELDS.contains(&key.as_str()))
+            .filter(|key| original.get(*key) != document.get(*key))
+            .map(|key| ValidationError::new(format!("/{}", key), "can not be changed"))
+            .collect();
+        if !errors.is_empty() {
+            return Err(Problem::ValidationFailed { errors });
+        }
+
+        // The patched resource has to be a valid resource on its own
+        let create_payload: E::CreatePayload = from_fields(&document, E::CREATE_FIELDS)?;
+        create_payload
+            .validate()
+            .map_err(|errors| Problem::ValidationFailed { errors })?;
+
+        // Fields removed by the patch are cleared
+        let mut update_document = document;
+        for field in E::UPDATE_FIELDS {
+            if original.contains_key(*field) && !update_document.contains_key(*field) {
+                update_document.insert((*field).to_owned(), Value::Null);
+            }
+        }
+        from_fields(&update_document, E::UPDATE_FIELDS)
+    }
+}
+
+/// Deserializes a payload from the given fields of a document.
+fn from_fields<T: DeserializeOwned>(
+    document: &Map<String, Value>,
+    fields: &[&str],
+) -> Result<T, Problem> {
+    let fields: Map<String, Value> = document
+        .iter()
+        .filter(|(key, _)| fields.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    serde_json::from_value(Value::Object(fields)).map_err(|error| Problem::ValidationFailed {
+        errors: vec![ValidationError::new("", error.to_string())],
+    })
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to a document.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn apply_operation(document: &mut Value, operation: &PatchOperation) -> Result<(), Problem> {
+    match operation {
+        PatchOperation::Add { path, value } => {
+            let (parent, last) = match parse_pointer(path)?.split_last() {
+                Some((last, parent)) => (parent.to_vec(), last.clone()),
+                None => {
+                    *document = value.clone();
+                    return Ok(());
+                }
+            };
+            match resolve_mut(document, &parent) {
+                Some(Value::Object(object)) => {
+                    object.insert(last, value.clone());
+                }
+                Some(Value::Array(array)) if last == "-" => array.push(value.clone()),
+                Some(Value::Array(array)) => {
+                    let index = array_index(&last, array.len() + 1, path)?;
+                    array.insert(index, value.clone());
+                }
+                _ => return Err(path_not_found(path)),
+            }
+        }
+        PatchOperation::Remove { path } => {
+            let tokens = parse_pointer(path)?;
+            let Some((last, parent)) = tokens.split_last() else {
+                return Err(path_not_found(path));
+            };
+            match resolve_mut(document, parent) {
+                Some(Value::Object(object)) => {
+                    object.remove(last).ok_or_else(|| path_not_found(path))?;
+                }
+                Some(Value::Array(array)) => {
+                    let index = array_index(last, array.len(), path)?;
+                    array.remove(index);
+                }
+                _ => return Err(path_not_found(path)),
+            }
+        }
+        PatchOperation::Replace { path, value } => {
+            let target =
+                resolve_mut(document, &parse_pointer(path)?).ok_or_else(|| path_not_found(path))?;
+            *target = value.clone();
+        }
+        PatchOperation::Test { path, value } => {
+            let target = resolve_mut(document, &parse_pointer(path)?);
+            if target.as_deref() != Some(value) {
+                return Err(Problem::PatchTestFailed {
+                    pointer: path.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits a JSON pointer (RFC 6901) into its unescaped reference tokens.
+fn parse_pointer(path: &str) -> Result<Vec<String>, Problem> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(path) = path.strip_prefix('/') else {
+        return Err(Problem::PatchNotApplicable {
+            detail: format!("'{}' is not a valid JSON pointer.", path),
+        });
+    };
+    Ok(path
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn resolve_mut<'a>(document: &'a mut Value, tokens: &[String]) -> Option<&'a mut Value> {
+    tokens
+        .iter()
+        .try_fold(document, |value, token| match value {
+            Value::Object(object) => object.get_mut(token),
+            Value::Array(array) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| array.get_mut(index)),
+            _ => None,
+        })
+}
+
+fn array_index(token: &str, len: usize, path: &str) -> Result<usize, Problem> {
+    token
+        .parse::<usize>()
+        .ok()
+        .filter(|index| *index < len)
+        .ok_or_else(|| path_not_found(path))
+}
+
+fn path_not_found(path: &str) -> Problem {
+    Problem::PatchNotApplicable {
+        detail: format!("The path '{}' does not exist.", path),
+    }
+}
+
+/// The body of an update request, either the generated update payload or a patch document.
+///
+/// The variant is chosen by the `Content-Type` of the request. Any type other
+/// than the two patch media types is handled like [`axum::Json`], which also
+/// rejects requests without a JSON content type.
+pub enum UpdateBody<E: Endpoint> {
+    Payload(E::UpdatePayload),
+    Document(DocumentPatch),
+}
+
+impl<E, S> FromRequest<S> for UpdateBody<E>
+where
+    E: Endpoint,
+    E::UpdatePayload: Send,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let media_type = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase());
+
+        let malformed = |error: serde_json::Error| {
+            let problem: crate::problems::JsonProblem = Problem::MalformedPatch {
+                detail: error.to_string(),
+            }
+            .into();
+            problem.into_response()
+        };
+        match media_type.as_deref() {
+            Some(MERGE_PATCH_MEDIA_TYPE) => {
+                let body = Bytes::from_request(request, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                let patch = serde_json::from_slice(&body).map_err(malformed)?;
+                Ok(UpdateBody::Document(DocumentPatch::Merge(patch)))
+            }
+            Some(JSON_PATCH_MEDIA_TYPE) => {
+                let body = Bytes::from_request(request, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                let operations = serde_json::from_slice(&body).map_err(malformed)?;
+                Ok(UpdateBody::Document(DocumentPatch::Json(operations)))
+            }
+            _ => {
+                let Json(payload) = Json::<E::UpdatePayload>::from_request(request, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                Ok(UpdateBody::Payload(payload))
+            }
+        }
+    }
+}
@@ -0,0 +1,571 @@
+//! Provides the query filter language of list routes.
+//!
+//! Filters are passed as query parameters of the form `field[operator]=value`,
+//! e.g. `?title[contains]=rust&created_at[gte]=2026-01-01&summary[null]=true`.
+//! A parameter without an operator (`?title=rust`) is a shorthand for `eq`.
+//! All conditions of a request must hold for an item to match.
+//!
+//! The `endpoint` macro generates a typed [`Filter`] for every struct, holding
+//! a list of [`Condition`]s per field.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::Serialize;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Query parameters that are never interpreted as filters.
+pub const RESERVED_PARAMETERS: &[&str] = &[
+    "atomic", "limit", "offset", "cursor", "sort", "fields", "include",
+];
+
+/// An operator of the filter language.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Contains,
+    StartsWith,
+    EndsWith,
+    In,
+    Null,
+}
+
+impl FromStr for Operator {
+    type Err = ();
+
+    fn from_str(operator: &str) -> Result<Self, Self::Err> {
+        match operator {
+            "eq" => Ok(Operator::Eq),
+            "ne" => Ok(Operator::Ne),
+            "gt" => Ok(Operator::Gt),
+            "gte" => Ok(Operator::Gte),
+            "lt" => Ok(Operator::Lt),
+            "lte" => Ok(Operator::Lte),
+            "contains" => Ok(Operator::Contains),
+            "starts_with" => Ok(Operator::StartsWith),
+            "ends_with" => Ok(Operator::EndsWith),
+            "in" => Ok(Operator::In),
+            "null" => Ok(Operator::Null),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An error raised while parsing a filter from the query string.
+#[derive(Clone, Debug)]
+pub struct FilterError {
+    /// The offending query parameter, e.g. `title[contains]`.
+    pub parameter: String,
+    pub detail: String,
+}
+
+/// A value type that can be used in filter conditions.
+///
+/// Fields of other types are excluded from the filter with
+/// `#[lily(no_filter)]`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can not be used in query filters",
+    label = "`FilterValue` is not implemented for `{Self}`",
+    note = "mark the field with `#[lily(no_filter)]` to exclude it from the filter"
+)]
+pub trait FilterValue: Sized + PartialEq + PartialOrd {
+    /// Whether the text operators (`contains`, `starts_with`, `ends_with`)
+    /// are supported for this type.
+    const TEXT: bool = false;
+
+    /// Parses a single value from the query string.
+    fn parse_filter_value(raw: &str) -> Result<Self, String>;
+
+    /// Returns the value as text, used by the text operators.
+    fn as_text(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// A single condition on a field of type `T`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition<T> {
+    Eq(T),
+    Ne(T),
+    Gt(T),
+    Gte(T),
+    Lt(T),
+    Lte(T),
+    Contains(String),
+    StartsWith(String),
+    EndsWith(String),
+    In(Vec<T>),
+    /// Matches if the field is (`true`) or is not (`false`) null.
+    Null(bool),
+}
+
+impl<T: FilterValue> Condition<T> {
+    /// Parses a condition on the field `field`.
+    ///
+    /// The `null` operator is only allowed on `nullable` fields, the text
+    /// operators only on text fields.
+    pub fn parse(
+        field: &str,
+        operator: Operator,
+        raw: &str,
+        nullable: bool,
+    ) -> Result<Self, FilterError> {
+        let error = |detail: String| FilterError {
+            parameter: field.to_owned(),
+            detail,
+        };
+        let value = || T::parse_filter_value(raw).map_err(error);
+        let text = |operator: &str| {
+            if T::TEXT {
+                Ok(raw.to_owned())
+            } else {
+                Err(error(format!(
+                    "The operator '{}' is only supported on text fields.",
+                    operator
+                )))
+            }
+        };
+
+        Ok(match operator {
+            Operator::Eq => Condition::Eq(value()?),
+            Operator::Ne => Condition::Ne(value()?),
+            Operator::Gt => Condition::Gt(value()?),
+            Operator::Gte => Condition::Gte(value()?),
+            Operator::Lt => Condition::Lt(value()?),
+            Operator::Lte => Condition::Lte(value()?),
+            Operator::Contains => Condition::Contains(text("contains")?),
+            Operator::StartsWith => Condition::StartsWith(text("starts_with")?),
+            Operator::EndsWith => Condition::EndsWith(text("ends_with")?),
+            Operator::In => Condition::In(
+                raw.split(',')
+                    .map(T::parse_filter_value)
+                    .collect::<Result<_, _>>()
+                    .map_err(error)?,
+            ),
+            Operator::Null if nullable => {
+                Condition::Null(bool::parse_filter_value(raw).map_err(error)?)
+            }
+            Operator::Null => {
+                return Err(error(
+                    "The operator 'null' is only supported on optional fields.".to_owned(),
+                ));
+            }
+        })
+    }
+
+    /// Checks whether the condition holds for the given field value, where
+    /// `None` represents null.
+    ///
+    /// Like in SQL, every condition except `null` fails on null values.
+    pub fn matches(&self, value: Option<&T>) -> bool {
+        let Some(value) = value else {
+            return matches!(self, Condition::Null(true));
+        };
+        let text = value.as_text().unwrap_or_default();
+        match self {
+            Condition::Eq(expected) => value == expected,
+            Condition::Ne(expected) => value != expected,
+            Condition::Gt(expected) => value > expected,
+            Condition::Gte(expected) => value >= expected,
+            Condition::Lt(expected) => value < expected,
+            Condition::Lte(expected) => value <= expected,
+            Condition::Contains(needle) => text.contains(needle.as_str()),
+            Condition::StartsWith(prefix) => text.starts_with(prefix.as_str()),
+            Condition::EndsWith(suffix) => text.ends_with(suffix.as_str()),
+            Condition::In(expected) => expected.contains(value),
+            Condition::Null(is_null) => !is_null,
+        }
+    }
+}
+
+impl<T: Serialize> Condition<T> {
+    /// Serializes the compared values, e.g. to bind them as SQL parameters.
+    ///
+    /// Values that fail to serialize become `null`.
+    pub fn to_value(&self) -> Condition<serde_json::Value> {
+        let value = |value: &T| serde_json::to_value(value).unwrap_or_default();
+        match self {
+            Condition::Eq(expected) => Condition::Eq(value(expected)),
+            Condition::Ne(expected) => Condition::Ne(value(expected)),
+            Condition::Gt(expected) => Condition::Gt(value(expected)),
+            Condition::Gte(expected) => Condition::Gte(value(expected)),
+            Condition::Lt(expected) => Condition::Lt(value(expected)),
+            Condition::Lte(expected) => Condition::Lte(value(expected)),
+            Condition::Contains(needle) => Condition::Contains(needle.clone()),
+            Condition::StartsWith(prefix) => Condition::StartsWith(prefix.clone()),
+            Condition::EndsWith(suffix) => Condition::EndsWith(suffix.clone()),
+            Condition::In(expected) => Condition::In(expected.iter().map(value).collect()),
+            Condition::Null(is_null) => Condition::Null(*is_null),
+        }
+    }
+}
+
+/// A typed filter over the fields of the endpoint type `T`.
+///
+/// Implementations are generated by the `endpoint` macro.
+pub trait Filter<T>: Default {
+    /// The names of all fields that can be filtered on.
+    const FIELDS: &'static [&'static str];
+
+    /// Adds a condition on `field`, parsed from the raw query value.
+    fn add_condition(
+        &mut self,
+        field: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<(), FilterError>;
+
+    /// Returns `true` if the filter has no conditions and matches every item.
+    fn is_empty(&self) -> bool;
+
+    /// Checks whether all conditions hold for the given item.
+    fn matches(&self, item: &T) -> bool;
+
+    /// Returns all conditions with the name of their field, for stores that
+    /// evaluate the filter themselves, e.g. as SQL.
+    fn conditions(&self) -> Vec<(&'static str, Condition<serde_json::Value>)>;
+
+    /// Parses a filter from a raw query string, skipping the
+    /// [`RESERVED_PARAMETERS`].
+    fn from_query(query: Option<&str>) -> Result<Self, FilterError> {
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query.unwrap_or_default())
+            .map_err(|error| FilterError {
+                parameter: query.unwrap_or_default().to_owned(),
+                detail: error.to_string(),
+            })?;
+
+        let mut filter = Self::default();
+        for (parameter, value) in pairs {
+            let (field, operator) = split_parameter(&parameter)?;
+            if operator.is_none() && RESERVED_PARAMETERS.contains(&field) {
+                continue;
+            }
+            if !Self::FIELDS.contains(&field) {
+                return Err(FilterError {
+                    detail: format!(
+                        "Unknown field '{}', expected one of: {}.",
+                        field,
+                        Self::FIELDS.join(", ")
+                    ),
+                    parameter,
+                });
+            }
+            let operator = match operator {
+                Some(operator) => operator.parse().map_err(|_| FilterError {
+                    detail: format!(
+                        "Unknown operator '{}', expected one of: eq, ne, gt, gte, lt, lte, contains, starts_with, ends_with, in, null.",
+                        operator
+                    ),
+                    parameter: parameter.clone(),
+                })?,
+                None => Operator::Eq,
+            };
+            filter
+                .add_condition(field, operator, &value)
+                .map_err(|error| FilterError {
+                    parameter: parameter.clone(),
+                    ..error
+                })?;
+        }
+        Ok(filter)
+    }
+}
+
+/// Splits a query parameter like `title[contains]` into field and operator.
+fn split_parameter(parameter: &str) -> Result<(&str, Option<&str>), FilterError> {
+    match parameter.split_once('[') {
+        None => Ok((parameter, None)),
+        Some((field, rest)) => match rest.strip_suffix(']') {
+            Some(operator) if !operator.contains(['[', ']']) => Ok((field, Some(operator))),
+            _ => Err(FilterError {
+                parameter: parameter.to_owned(),
+                detail: "Expected a parameter of the form 'field[operator]'.".to_owned(),
+            }),
+        },
+    }
+}
+
+impl FilterValue for String {
+    const TEXT: bool = true;
+
+    fn parse_filter_value(raw: &str) -> Result<Self, String> {
+        Ok(raw.to_owned())
+    }
+
+    fn as_text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl FilterValue for bool {
+    fn parse_filter_value(raw: &str) -> Result<Self, String> {
+        raw.parse()
+            .map_err(|_| format!("Expected 'true' or 'false', got '{}'.", raw))
+    }
+}
+
+/// Implements [`FilterValue`] for types that are parsed with [`FromStr`].
+macro_rules! impl_filter_value_from_str {
+    ($($ty:ty),*) => {
+        $(
+            impl FilterValue for $ty {
+                fn parse_filter_value(raw: &str) -> Result<Self, String> {
+                    parse_from_str(raw)
+                }
+            }
+        )*
+    };
+}
+
+impl_filter_value_from_str!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64
+);
+impl_filter_value_from_str!(
+    char,
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr
+);
+
+#[cfg(feature = "uuid")]
+impl_filter_value_from_str!(uuid::Uuid);
+
+impl FilterValue for DateTime<Utc> {
+    /// Accepts RFC 3339 timestamps as well as plain dates, which are
+    /// interpreted as midnight UTC.
+    fn parse_filter_value(raw: &str) -> Result<Self, String> {
+        if let Ok(date) = raw.parse::<NaiveDate>() {
+            return Ok(date.and_time(Default::default()).and_utc());
+        }
+        parse_from_str(raw)
+    }
+}
+
+fn parse_from_str<T: FromStr>(raw: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    raw.parse()
+        .map_err(|error| format!("Invalid value '{}': {}", raw, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Item {
+        title: String,
+        rating: Option<u8>,
+    }
+
+    #[derive(Default)]
+    struct ItemFilter {
+        title: Vec<Condition<String>>,
+        rating: Vec<Condition<u8>>,
+    }
+
+    impl Filter<Item> for ItemFilter {
+        const FIELDS: &'static [&'static str] = &["title", "rating"];
+
+        fn add_condition(
+            &mut self,
+            field: &str,
+            operator: Operator,
+            value: &str,
+        ) -> Result<(), FilterError> {
+            match field {
+                "title" => self
+                    .title
+                    .push(Condition::parse(field, operator, value, false)?),
+                _ => self
+                    .rating
+                    .push(Condition::parse(field, operator, value, true)?),
+            }
+            Ok(())
+        }
+
+        fn is_empty(&self) -> bool {
+            self.title.is_empty() && self.rating.is_empty()
+        }
+
+        fn matches(&self, item: &Item) -> bool {
+            self.title
+                .iter()
+                .all(|condition| condition.matches(Some(&item.title)))
+                && self
+                    .rating
+                    .iter()
+                    .all(|condition| condition.matches(item.rating.as_ref()))
+        }
+
+        fn conditions(&self) -> Vec<(&'static str, Condition<serde_json::Value>)> {
+            Vec::new()
+        }
+    }
+
+    fn item(title: &str, rating: Option<u8>) -> Item {
+        Item {
+            title: title.to_owned(),
+            rating,
+        }
+    }
+
+    #[test]
+    fn parses_every_operator() {
+        let operators = [
+            ("eq", Operator::Eq),
+            ("ne", Operator::Ne),
+            ("gt", Operator::Gt),
+            ("gte", Operator::Gte),
+            ("lt", Operator::Lt),
+            ("lte", Operator::Lte),
+            ("contains", Operator::Contains),
+            ("starts_with", Operator::StartsWith),
+            ("ends_with", Operator::EndsWith),
+            ("in", Operator::In),
+            ("null", Operator::Null),
+        ];
+        for (name, operator) in operators {
+            assert_eq!(name.parse(), Ok(operator));
+        }
+        assert_eq!("like".parse::<Operator>(), Err(()));
+    }
+
+    #[test]
+    fn parses_conditions_from_the_query() {
+        let filter = ItemFilter::from_query(Some(
+            "title[starts_with]=Rust&rating[gte]=3&rating[lt]=5&limit=10",
+        ))
+        .unwrap();
+        assert_eq!(filter.title, vec![Condition::StartsWith("Rust".to_owned())]);
+        assert_eq!(filter.rating, vec![Condition::Gte(3), Condition::Lt(5)]);
+        assert!(filter.matches(&item("Rust in action", Some(4))));
+        assert!(!filter.matches(&item("Rust in action", Some(5))));
+        assert!(!filter.matches(&item("Learning Rust", Some(4))));
+    }
+
+    #[test]
+    fn defaults_to_eq_without_an_operator() {
+        let filter = ItemFilter::from_query(Some("title=Rust")).unwrap();
+        assert_eq!(filter.title, vec![Condition::Eq("Rust".to_owned())]);
+    }
+
+    #[test]
+    fn skips_reserved_parameters() {
+        let filter =
+            ItemFilter::from_query(Some("limit=5&offset=10&sort=-title&fields=title")).unwrap();
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let error = ItemFilter::from_query(Some("author[eq]=Alice"))
+            .err()
+            .unwrap();
+        assert_eq!(error.parameter, "author[eq]");
+        assert!(error.detail.contains("Unknown field 'author'"));
+    }
+
+    #[test]
+    fn rejects_unknown_operators() {
+        let error = ItemFilter::from_query(Some("title[like]=Rust"))
+            .err()
+            .unwrap();
+        assert_eq!(error.parameter, "title[like]");
+        assert!(error.detail.contains("Unknown operator 'like'"));
+    }
+
+    #[test]
+    fn rejects_malformed_parameters() {
+        for query in ["title[eq=Rust", "title[eq]x=Rust", "title[[eq]]=Rust"] {
+            assert!(ItemFilter::from_query(Some(query)).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let error = ItemFilter::from_query(Some("rating[gt]=high"))
+            .err()
+            .unwrap();
+        assert_eq!(error.parameter, "rating[gt]");
+        assert!(error.detail.contains("Invalid value 'high'"));
+    }
+
+    #[test]
+    fn rejects_text_operators_on_other_fields() {
+        let error = ItemFilter::from_query(Some("rating[contains]=3"))
+            .err()
+            .unwrap();
+        assert!(error.detail.contains("only supported on text fields"));
+    }
+
+    #[test]
+    fn matches_null_on_optional_fields() {
+        let filter = ItemFilter::from_query(Some("rating[null]=true")).unwrap();
+        assert!(filter.matches(&item("Draft", None)));
+        assert!(!filter.matches(&item("Draft", Some(1))));
+
+        let filter = ItemFilter::from_query(Some("rating[null]=false")).unwrap();
+        assert!(!filter.matches(&item("Draft", None)));
+        assert!(filter.matches(&item("Draft", Some(1))));
+    }
+
+    #[test]
+    fn fails_every_other_condition_on_null() {
+        let filter = ItemFilter::from_query(Some("rating[ne]=3")).unwrap();
+        assert!(!filter.matches(&item("Draft", None)));
+    }
+
+    #[test]
+    fn rejects_null_on_required_fields() {
+        let error = ItemFilter::from_query(Some("title[null]=true"))
+            .err()
+            .unwrap();
+        assert!(error.detail.contains("only supported on optional fields"));
+
+        let error = ItemFilter::from_query(Some("rating[null]=maybe"))
+            .err()
+            .unwrap();
+        assert!(error.detail.contains("Expected 'true' or 'false'"));
+    }
+
+    #[test]
+    fn splits_in_values_at_commas() {
+        let filter = ItemFilter::from_query(Some("rating[in]=1,3,5")).unwrap();
+        assert_eq!(filter.rating, vec![Condition::In(vec![1, 3, 5])]);
+        assert!(filter.matches(&item("Draft", Some(3))));
+        assert!(!filter.matches(&item("Draft", Some(4))));
+
+        // Encoded commas split values as well, as the query is decoded first
+        let filter = ItemFilter::from_query(Some("title[in]=a%2Cb,c")).unwrap();
+        assert_eq!(
+            filter.title,
+            vec![Condition::In(vec![
+                "a".to_owned(),
+                "b".to_owned(),
+                "c".to_owned()
+            ])]
+        );
+
+        assert!(ItemFilter::from_query(Some("rating[in]=1,,3")).is_err());
+    }
+
+    #[test]
+    fn reads_plain_dates_as_midnight_utc() {
+        let date = DateTime::<Utc>::parse_filter_value("2026-01-01").unwrap();
+        assert_eq!(date.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+
+        let timestamp = DateTime::<Utc>::parse_filter_value("2026-01-01T12:00:00+02:00").unwrap();
+        assert_eq!(timestamp.to_rfc3339(), "2026-01-01T10:00:00+00:00");
+
+        assert!(DateTime::<Utc>::parse_filter_value("2026-13-01").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Provides the inclusion of related resources in read responses.
+//!
+//! The `include` query parameter takes a comma-separated list of relation
+//! names, e.g. `?include=author`. Every related resource is looked up through
+//! the `ReadSingle` implementation of its endpoint and embedded in the
+//! response under the name of the relation, saving clients one request per
+//! relation. Nested relations are separated by dots, e.g.
+//! `?include=author.team`, up to the depth set with [`set_max_depth`].
+//!
+//! The name of a `belongs_to` relation is the name of its field without the
+//! `_id` suffix, so `author_id` is included as `author`. Fields without the
+//! suffix are expanded in place, replacing the id with the resource.
+
+use crate::context::Context;
+use crate::problems::Problem;
+use crate::routing::{Endpoint, ReadSingle};
+use axum::extract::FromRef;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::OnceLock;
+
+static MAX_DEPTH: OnceLock<usize> = OnceLock::new();
+
+/// The default number of nested levels of the `include` parameter.
+pub const DEFAULT_MAX_DEPTH: usize = 2;
+
+/// Sets how deeply relations may be nested in the `include` parameter.
+///
+/// Must be called before the first request, which otherwise uses the
+/// [`DEFAULT_MAX_DEPTH`]. Returns `false` if the same depth has already been
+/// set.
+///
+/// # Panics
+/// Panics if a different depth has already been set or used by a request.
+#[must_use]
+pub fn set_max_depth(depth: usize) -> bool {
+    match MAX_DEPTH.set(depth) {
+        Ok(()) => true,
+        Err(depth) if depth == max_depth() => false,
+        Err(depth) => panic!(
+            "The maximum include depth can not be set to {}, as the depth {} is already in use",
+            depth,
+            max_depth()
+        ),
+    }
+}
+
+/// Returns the maximum depth of the `include` parameter.
+pub fn max_depth() -> usize {
+    *MAX_DEPTH.get_or_init(|| DEFAULT_MAX_DEPTH)
+}
+
+/// The raw include query parameter of a read request.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct IncludeQuery {
+    pub include: Option<String>,
+}
+
+/// The requested relations, each with the relations to include in turn.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IncludeTree(BTreeMap<String, IncludeTree>);
+
+impl IncludeQuery {
+    /// Parses the requested relations, rejecting unknown relations and paths
+    /// deeper than [`max_depth`].
+    pub fn parse<E: Includes>(&self) -> Result<IncludeTree, Problem> {
+        let includes = match &self.include {
+            Some(requested) => IncludeTree::from_paths(requested)?,
+            None => IncludeTree::default(),
+        };
+        E::check_includes(&includes)?;
+        Ok(includes)
+    }
+}
+
+impl IncludeTree {
+    /// Parses comma-separated paths of relation names into a tree, rejecting
+    /// paths deeper than [`max_depth`].
+    ///
+    /// # Examples
+    /// ```
+    /// use lily_endpoint::include::IncludeTree;
+    ///
+    /// let includes = IncludeTree::from_paths("author.team,tags").ok().unwrap();
+    /// let names: Vec<&String> = includes.iter().map(|(name, _)| name).collect();
+    /// assert_eq!(names, ["author", "tags"]);
+    ///
+    /// assert!(IncludeTree::from_paths("author.team.owner").is_err());
+    /// ```
+    pub fn from_paths(paths: &str) -> Result<Self, Problem> {
+        let mut tree = IncludeTree::default();
+        for path in paths.split(',') {
+            let names: Vec<&str> = path.split('.').collect();
+            if names.iter().any(|name| name.is_empty()) {
+                return Err(Problem::InvalidQuery {
+                    detail: format!("The include '{}' is not a valid path.", path),
+                });
+            }
+            if names.len() > max_depth() {
+                return Err(Problem::InvalidQuery {
+                    detail: format!(
+                        "The include '{}' is nested deeper than {} levels.",
+                        path,
+                        max_depth()
+                    ),
+                });
+            }
+            names.into_iter().fold(&mut tree, |tree, name| {
+                tree.0.entry(name.to_owned()).or_default()
+            });
+        }
+        Ok(tree)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over the requested relations and their nested includes.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &IncludeTree)> {
+        self.0.iter()
+    }
+}
+
+/// The future of a single resolved relation, boxed as relations may be nested recursively.
+pub type IncludeFuture<'a> = Pin<Box<dyn Future<Output = Result<Value, Problem>> + Send + 'a>>;
+
+/// Resolves the relations of an endpoint, implemented by the endpoint macro.
+pub trait Includes: Endpoint {
+    /// Checks the requested relations, including the nested ones.
+    fn check_includes(includes: &IncludeTree) -> Result<(), Problem>;
+
+    /// Resolves a single checked relation as JSON, with its nested relations embedded.
+    fn resolve_include<'a>(
+        &'a self,
+        state: &'a Self::State,
+        context: &'a Context,
+        name: &'a str,
+        includes: &'a IncludeTree,
+    ) -> IncludeFuture<'a>;
+}
+
+/// Returns the problem of a relation that does not exist on `E`.
+pub fn unknown_include<E: Endpoint>(name: &str) -> Problem {
+    let names: Vec<&str> = E::RELATIONS.iter().map(|relation| relation.name).collect();
+    let detail = if names.is_empty() {
+        format!(
+            "Unknown include '{}', '{}' has no relations.",
+            name,
+            E::get_name()
+        )
+    } else {
+        format!(
+            "Unknown include '{}' on '{}', expected one of: {}.",
+            name,
+            E::get_name(),
+            names.join(", ")
+        )
+    };
+    Problem::InvalidQuery { detail }
+}
+
+/// Resolves all requested relations of an item.
+pub async fn resolve_includes<E: Includes + Sync>(
+    item: &E,
+    state: &E::State,
+    context: &Context,
+    includes: &IncludeTree,
+) -> Result<Map<String, Value>, Problem> {
+    let mut included = Map::new();
+    for (name, nested) in includes.iter() {
+        included.insert(
+            name.clone(),
+            item.resolve_include(state, context, name, nested).await?,
+        );
+    }
+    Ok(included)
+}
+
+/// Resolves all requested relations of every item of a list, in the order of the items.
+pub async fn resolve_includes_all<'a, E: Includes + Sync + 'a>(
+    items: impl IntoIterator<Item = &'a E>,
+    state: &E::State,
+    context: &Context,
+    includes: &IncludeTree,
+) -> Result<Vec<Map<String, Value>>, Problem> {
+    let mut included = Vec::new();
+    for item in items {
+        included.push(resolve_includes(item, state, context, includes).await?);
+    }
+    Ok(included)
+}
+
+/// Reads a related resource by its id and embeds its own relations, or
+/// returns `null` if it does not exist.
+///
+/// The state of `T` is taken from the state `S` of the including endpoint.
+pub async fn include_single<T: Includes + ReadSingle + Sync, S>(
+    state: &S,
+    context: &Context,
+    id: &T::Id,
+    includes: &IncludeTree,
+) -> Result<Value, Problem>
+where
+    T::State: FromRef<S>,
+{
+    let state = T::State::from_ref(state);
+    let item = match T::read_single(&state, context, id).await {
+        Ok(Some(item)) => item,
+        Ok(None) => return Ok(Value::Null),
+        Err(error_msg) => {
+            eprintln!("Error reading included [{}]: {}", T::get_name(), error_msg);
+            return Err(Problem::InternalError);
+        }
+    };
+    let included = resolve_includes(&item, &state, context, includes).await?;
+    let mut value = serde_json::to_value(&item).map_err(|error| {
+        eprintln!("Error serializing included [{}]: {}", T::get_name(), error);
+        Problem::InternalError
+    })?;
+    embed(&mut value, included);
+    Ok(value)
+}
+
+/// Embeds the resolved relations into a serialized resource.
+pub fn embed(value: &mut Value, included: Map<String, Value>) {
+    if let Value::Object(map) = value {
+        map.extend(included);
+    }
+}
+
+/// An item together with its resolved relations.
+#[derive(Clone, Debug)]
+pub struct Included<T> {
+    item: T,
+    included: Map<String, Value>,
+}
+
+impl<T> Included<T> {
+    pub fn new(item: T, included: Map<String, Value>) -> Self {
+        Included { item, included }
+    }
+}
+
+impl<T: Serialize> Serialize for Included<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.included.is_empty() {
+            return self.item.serialize(serializer);
+        }
+
+        let mut value = serde_json::to_value(&self.item).map_err(serde::ser::Error::custom)?;
+        embed(&mut value, self.included.clone());
+        value.serialize(serializer)
+    }
+}
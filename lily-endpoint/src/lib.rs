@@ -1,3 +1,16 @@
+pub mod bulk;
+pub mod context;
+pub mod cursor;
+pub mod document_patch;
+pub mod filtering;
+pub mod include;
+pub mod pagination;
+pub mod patch;
 pub mod problems;
+pub mod projection;
+pub mod query;
+pub mod relations;
 pub mod responses;
 pub mod routing;
+pub mod sorting;
+pub mod validation;
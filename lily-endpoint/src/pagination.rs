@@ -0,0 +1,99 @@
+//! Provides the types used to paginate list endpoints.
+//!
+//! List routes accept `limit` and `offset` query parameters ([`PageQuery`]),
+//! which are clamped to the server-enforced page sizes and handed to the
+//! implementation as a [`Pagination`]. Implementations answer with a [`Page`],
+//! which carries the requested items together with the metadata clients need
+//! to fetch the next page.
+
+use crate::cursor::Cursor;
+use serde::{Deserialize, Serialize};
+
+/// The raw pagination query parameters of a list request.
+///
+/// Both parameters are optional; missing values fall back to the defaults of
+/// the endpoint (see [`Pagination::from_query`]).
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PageQuery {
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+/// A validated page request, handed to list implementations.
+///
+/// In cursor mode, the `offset` is always `0` and the page starts after the
+/// `cursor` instead (see [`crate::routing::ListQuery::is_after_cursor`]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pagination {
+    /// The number of items to return, between `1` and the maximum page size of
+    /// the endpoint.
+    ///
+    /// In cursor mode, the limit is one more than the page size, as the
+    /// additional item tells whether there is a next page. Implementations
+    /// must return up to `limit` items without capping it to
+    /// `MAX_PAGE_SIZE`, or pages of the maximum size never get a `next_cursor`.
+    pub limit: u64,
+    pub offset: u64,
+    pub cursor: Option<Cursor>,
+}
+
+impl Pagination {
+    /// Creates a pagination from the raw query parameters, applying the given
+    /// default and clamping the limit to `1..=max_limit`.
+    ///
+    /// # Examples
+    /// ```
+    /// use lily_endpoint::pagination::{PageQuery, Pagination};
+    ///
+    /// let query = PageQuery { limit: Some(500), offset: None };
+    /// let pagination = Pagination::from_query(&query, 20, 100);
+    /// assert_eq!(pagination, Pagination { limit: 100, offset: 0, cursor: None });
+    /// ```
+    pub fn from_query(query: &PageQuery, default_limit: u64, max_limit: u64) -> Self {
+        let max_limit = max_limit.max(1);
+        Pagination {
+            limit: query.limit.unwrap_or(default_limit).clamp(1, max_limit),
+            offset: query.offset.unwrap_or(0),
+            cursor: None,
+        }
+    }
+}
+
+/// A single page of items returned by a list route.
+#[derive(Clone, Debug, Serialize)]
+pub struct Page<T: Serialize> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub limit: u64,
+    pub offset: u64,
+    pub next_offset: Option<u64>,
+}
+
+impl<T: Serialize> Page<T> {
+    /// Creates a page from the items of the requested slice and the total
+    /// number of items in the collection.
+    ///
+    /// The `next_offset` is derived from the pagination and is `None` once the
+    /// end of the collection has been reached.
+    pub fn new(items: Vec<T>, total: u64, pagination: &Pagination) -> Self {
+        let next_offset = pagination.offset.saturating_add(items.len() as u64);
+        Page {
+            next_offset: (!items.is_empty() && next_offset < total).then_some(next_offset),
+            items,
+            total,
+            limit: pagination.limit,
+            offset: pagination.offset,
+        }
+    }
+
+    /// Converts the items of the page, keeping its metadata.
+    pub fn map<U: Serialize>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            limit: self.limit,
+            offset: self.offset,
+            next_offset: self.next_offset,
+        }
+    }
+}
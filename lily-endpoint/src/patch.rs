@@ -0,0 +1,80 @@
+//! Provides the tri-state type of optional fields in update payloads.
+//!
+//! A PATCH request has to distinguish between a field that was not sent,
+//! which keeps its current value, and a field that was explicitly set to
+//! `null`, which clears it. `Option<T>` can only express one of these, so the
+//! update payload uses a [`Patch<T>`] for every optional field.
+
+use serde::{Deserialize, Deserializer};
+
+/// The change of an optional field requested by a PATCH payload.
+///
+/// Fields of this type must be annotated with `#[serde(default)]`, so that an
+/// absent field deserializes to [`Patch::Missing`]; the endpoint macro does
+/// this for all generated update payloads.
+///
+/// # Examples
+/// ```
+/// use lily_endpoint::patch::Patch;
+///
+/// #[derive(serde::Deserialize)]
+/// struct UpdateContent {
+///     #[serde(default)]
+///     summary: Patch<String>,
+/// }
+///
+/// let mut summary = Some("old".to_owned());
+/// let update: UpdateContent = serde_json::from_str("{}").unwrap();
+/// update.summary.apply_to(&mut summary);
+/// assert_eq!(summary.as_deref(), Some("old"));
+///
+/// let update: UpdateContent = serde_json::from_str(r#"{ "summary": null }"#).unwrap();
+/// update.summary.apply_to(&mut summary);
+/// assert_eq!(summary, None);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Patch<T> {
+    /// The field was not sent and keeps its current value.
+    #[default]
+    Missing,
+    /// The field was sent as `null` and is cleared.
+    Null,
+    /// The field was sent with a new value.
+    Value(T),
+}
+
+impl<T> Patch<T> {
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Patch::Missing)
+    }
+
+    /// Returns the new value, if one was sent.
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Patch::Value(value) => Some(value),
+            Patch::Missing | Patch::Null => None,
+        }
+    }
+
+    /// Applies the change to the current value of an optional field.
+    pub fn apply_to(&self, target: &mut Option<T>)
+    where
+        T: Clone,
+    {
+        match self {
+            Patch::Missing => {}
+            Patch::Null => *target = None,
+            Patch::Value(value) => *target = Some(value.clone()),
+        }
+    }
+}
+
+/// A present field is either `null` or a value; absent fields are handled by `#[serde(default)]`.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Patch::Value(value),
+            None => Patch::Null,
+        })
+    }
+}
@@ -17,6 +17,10 @@ use axum::{
 };
 use serde::Serialize;
 
+use crate::filtering::FilterError;
+use crate::relations::Dependents;
+use crate::validation::ValidationError;
+
 /// A struct representing an RFC 7807 "Problem Details for HTTP APIs".
 ///
 /// This struct is the serializable format sent to clients when an error occurs.
@@ -31,6 +35,10 @@ pub struct JsonProblem {
     #[serde(with = "http_serde::status_code")]
     pub status: StatusCode,
     detail: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<ValidationError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dependents: Vec<Dependents>,
 }
 
 /// Converts the `JsonProblem` into a well-formed Axum `Response`.
@@ -55,10 +63,67 @@ impl IntoResponse for JsonProblem {
 /// before being sent to the client.
 pub enum Problem {
     EndpointNotFound,
-    ResourceNotFound { resource: String, id: String },
+    ResourceNotFound {
+        resource: String,
+        id: String,
+    },
+    /// Both resources exist, but are not linked.
+    LinkNotFound {
+        resource: String,
+        id: String,
+        related_resource: String,
+        related_id: String,
+    },
+    InvalidId {
+        resource: String,
+        id: String,
+    },
+    InvalidQuery {
+        detail: String,
+    },
+    InvalidFilter {
+        parameter: String,
+        detail: String,
+    },
+    ValidationFailed {
+        errors: Vec<ValidationError>,
+    },
+    InvalidReference {
+        errors: Vec<ValidationError>,
+    },
+    BulkTooLarge {
+        max: usize,
+    },
+    BulkItemNotApplied,
+    UnsupportedMediaType {
+        media_type: String,
+    },
+    MalformedPatch {
+        detail: String,
+    },
+    PatchNotApplicable {
+        detail: String,
+    },
+    PatchTestFailed {
+        pointer: String,
+    },
+    DeleteRestricted {
+        resource: String,
+        id: String,
+        dependents: Vec<Dependents>,
+    },
     InternalError,
 }
 
+impl From<FilterError> for Problem {
+    fn from(error: FilterError) -> Self {
+        Problem::InvalidFilter {
+            parameter: error.parameter,
+            detail: error.detail,
+        }
+    }
+}
+
 impl From<Problem> for JsonProblem {
     fn from(problem: Problem) -> Self {
         match problem {
@@ -75,6 +140,47 @@ impl From<Problem> for JsonProblem {
                         "The resource '{}' with id '{}' was not found.",
                         resource, id
                     ),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::LinkNotFound {
+                resource,
+                id,
+                related_resource,
+                related_id,
+            } => {
+                let status_code = StatusCode::NOT_FOUND;
+                JsonProblem {
+                    type_uri: "/errors/link-not-found".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!(
+                        "The resource '{}' with id '{}' is not linked to the resource '{}' with id '{}'.",
+                        resource, id, related_resource, related_id
+                    ),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::InvalidId { resource, id } => {
+                let status_code = StatusCode::BAD_REQUEST;
+                JsonProblem {
+                    type_uri: "/errors/invalid-id".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!(
+                        "'{}' is not a valid id for the resource '{}'.",
+                        id, resource
+                    ),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
                 }
             }
             Problem::EndpointNotFound => {
@@ -87,6 +193,180 @@ impl From<Problem> for JsonProblem {
                         .to_owned(),
                     status: status_code,
                     detail: "The endpoint was not found.".to_string(),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::InvalidQuery { detail } => {
+                let status_code = StatusCode::BAD_REQUEST;
+                JsonProblem {
+                    type_uri: "/errors/invalid-query".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!("The query string is invalid: {}", detail),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::InvalidFilter { parameter, detail } => {
+                let status_code = StatusCode::BAD_REQUEST;
+                JsonProblem {
+                    type_uri: "/errors/invalid-filter".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!("The filter '{}' is invalid: {}", parameter, detail),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::ValidationFailed { errors } => {
+                let status_code = StatusCode::UNPROCESSABLE_ENTITY;
+                JsonProblem {
+                    type_uri: "/errors/validation-failed".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!("The payload is invalid in {} places.", errors.len()),
+                    errors,
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::InvalidReference { errors } => {
+                let status_code = StatusCode::BAD_REQUEST;
+                JsonProblem {
+                    type_uri: "/errors/invalid-reference".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!(
+                        "The payload references {} resources that do not exist.",
+                        errors.len()
+                    ),
+                    errors,
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::BulkTooLarge { max } => {
+                let status_code = StatusCode::PAYLOAD_TOO_LARGE;
+                JsonProblem {
+                    type_uri: "/errors/bulk-too-large".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!("A bulk request may contain at most {} items.", max),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::BulkItemNotApplied => {
+                let status_code = StatusCode::FAILED_DEPENDENCY;
+                JsonProblem {
+                    type_uri: "/errors/bulk-item-not-applied".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: "The item was not applied because another item of the atomic bulk request failed.".to_string(),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::UnsupportedMediaType { media_type } => {
+                let status_code = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+                JsonProblem {
+                    type_uri: "/errors/unsupported-media-type".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!("The media type '{}' is not supported.", media_type),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::MalformedPatch { detail } => {
+                let status_code = StatusCode::BAD_REQUEST;
+                JsonProblem {
+                    type_uri: "/errors/malformed-patch".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!("The patch document is malformed: {}", detail),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::PatchNotApplicable { detail } => {
+                let status_code = StatusCode::UNPROCESSABLE_ENTITY;
+                JsonProblem {
+                    type_uri: "/errors/patch-not-applicable".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!("The patch can not be applied: {}", detail),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::PatchTestFailed { pointer } => {
+                let status_code = StatusCode::CONFLICT;
+                JsonProblem {
+                    type_uri: "/errors/patch-test-failed".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!(
+                        "The value at '{}' does not match the tested value.",
+                        pointer
+                    ),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
+                }
+            }
+            Problem::DeleteRestricted {
+                resource,
+                id,
+                dependents,
+            } => {
+                let status_code = StatusCode::CONFLICT;
+                JsonProblem {
+                    type_uri: "/errors/delete-restricted".to_string(),
+                    title: status_code
+                        .canonical_reason()
+                        .unwrap_or(status_code.as_str())
+                        .to_owned(),
+                    status: status_code,
+                    detail: format!(
+                        "The resource '{}' with id '{}' is still referenced by {} resources.",
+                        resource,
+                        id,
+                        dependents
+                            .iter()
+                            .map(|dependents| dependents.ids.len())
+                            .sum::<usize>()
+                    ),
+                    errors: Vec::new(),
+                    dependents,
                 }
             }
             Problem::InternalError => {
@@ -99,6 +379,8 @@ impl From<Problem> for JsonProblem {
                         .to_owned(),
                     status: status_code,
                     detail: "An unexpected error occurred on the server.".to_string(),
+                    errors: Vec::new(),
+                    dependents: Vec::new(),
                 }
             }
         }
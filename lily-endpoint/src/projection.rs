@@ -0,0 +1,76 @@
+//! Provides sparse fieldsets for read routes.
+//!
+//! The `fields` query parameter takes a comma-separated list of field names,
+//! e.g. `?fields=id,title`. Only the requested fields are serialized, so
+//! clients can skip large fields they do not need.
+
+use crate::problems::Problem;
+use serde::{Deserialize, Serialize, Serializer, ser::Error};
+
+/// The raw fields query parameter of a read request.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct FieldsQuery {
+    pub fields: Option<String>,
+}
+
+/// A validated set of fields to include in a response; `None` includes all.
+#[derive(Clone, Debug, Default)]
+pub struct FieldSet(Option<Vec<String>>);
+
+impl FieldsQuery {
+    /// Parses the requested fields, rejecting names that are not in `fields`.
+    pub fn parse(&self, fields: &[&str]) -> Result<FieldSet, Problem> {
+        let Some(requested) = &self.fields else {
+            return Ok(FieldSet(None));
+        };
+
+        requested
+            .split(',')
+            .map(|name| {
+                if fields.contains(&name) {
+                    Ok(name.to_owned())
+                } else {
+                    Err(Problem::InvalidQuery {
+                        detail: format!(
+                            "Unknown field '{}', expected one of: {}.",
+                            name,
+                            fields.join(", ")
+                        ),
+                    })
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(|fields| FieldSet(Some(fields)))
+    }
+}
+
+impl FieldSet {
+    /// Wraps an item so that only the fields of this set are serialized.
+    pub fn project<T: Serialize>(&self, item: T) -> Projected<T> {
+        Projected {
+            item,
+            fields: self.0.clone(),
+        }
+    }
+}
+
+/// An item that only serializes the fields of a [`FieldSet`].
+#[derive(Clone, Debug)]
+pub struct Projected<T> {
+    item: T,
+    fields: Option<Vec<String>>,
+}
+
+impl<T: Serialize> Serialize for Projected<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(fields) = &self.fields else {
+            return self.item.serialize(serializer);
+        };
+
+        let mut value = serde_json::to_value(&self.item).map_err(S::Error::custom)?;
+        if let serde_json::Value::Object(map) = &mut value {
+            map.retain(|name, _| fields.contains(name));
+        }
+        value.serialize(serializer)
+    }
+}
@@ -0,0 +1,23 @@
+//! Provides helpers for parsing the query string of a request.
+
+use crate::problems::Problem;
+use serde::de::DeserializeOwned;
+
+/// Deserializes the raw query string of a request into `T`.
+///
+/// Unknown parameters are ignored, so several structs can be parsed from the
+/// same query string. Errors are reported as [`Problem::InvalidQuery`].
+///
+/// # Examples
+/// ```
+/// use lily_endpoint::pagination::PageQuery;
+/// use lily_endpoint::query::parse_query;
+///
+/// let query: PageQuery = parse_query(Some("limit=10&title=rust")).ok().unwrap();
+/// assert_eq!(query.limit, Some(10));
+/// ```
+pub fn parse_query<T: DeserializeOwned>(query: Option<&str>) -> Result<T, Problem> {
+    serde_urlencoded::from_str(query.unwrap_or_default()).map_err(|error| Problem::InvalidQuery {
+        detail: error.to_string(),
+    })
+}
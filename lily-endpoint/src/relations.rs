@@ -0,0 +1,620 @@
+//! Provides relationships between endpoints.
+//!
+//! A field marked with `#[lily(belongs_to = Author)]` holds the id of another
+//! endpoint. The endpoint macro then:
+//!
+//! 1. lists the relation in [`Endpoint::RELATIONS`](crate::routing::Endpoint::RELATIONS),
+//! 2. checks that the referenced resource exists whenever a payload is
+//!    accepted, rejecting the request with a
+//!    [`Problem::InvalidReference`](crate::problems::Problem::InvalidReference)
+//!    otherwise,
+//! 3. adds a nested list route like `GET /author/{id}/content`, if the list
+//!    route is enabled.
+//!
+//! A field marked with `#[lily(many = Tag)]` holds a list of ids of another
+//! endpoint. Its references are checked in the same way, and the endpoint
+//! must implement [`ManyToMany`], which stores the links between the two. The
+//! macro adds the routes `POST /content/{id}/tags/{tag_id}` and
+//! `DELETE /content/{id}/tags/{tag_id}` to link and unlink resources, and a
+//! nested list route like `GET /tag/{id}/content`, if the list route is
+//! enabled.
+//!
+//! The referenced endpoint has to implement `ReadSingle`, which is used to
+//! look the referenced resources up.
+//!
+//! Deleting a resource that is still referenced through a `belongs_to`
+//! relation follows the `on_delete` policy of the relation, e.g.
+//! `#[lily(belongs_to = Author, on_delete = cascade)]`:
+//!
+//! - `restrict` (the default) rejects the deletion with a
+//!   [`Problem::DeleteRestricted`](crate::problems::Problem::DeleteRestricted)
+//!   listing the dependent resources,
+//! - `cascade` deletes the dependent resources with `DeleteSingle`,
+//! - `set_null` clears the reference with `UpdateSingle`.
+//!
+//! The dependent resources are looked up with `ReadMultiple`, filtering on the
+//! field of the relation. Their endpoint takes its state from the state of
+//! the referenced endpoint with [`FromRef`]. The referenced endpoint lists the
+//! endpoints referencing it, e.g. `#[endpoint(referenced_by(Content))]`, which
+//! gives it their relations through [`DependentOf`]. A `belongs_to` relation to
+//! an endpoint that does not list the referencing endpoint does not compile,
+//! so no policy is skipped. The policies are applied by both delete routes: `restrict` is checked before
+//! anything is deleted, `cascade` and `set_null` are only applied to the
+//! dependents of resources that were deleted. A bulk delete reports its
+//! restricted resources like other failing items. Cascades through cycles of
+//! relations visit every resource once.
+
+use crate::bulk::{BulkReplaceItem, BulkUpdate};
+use crate::context::Context;
+use crate::filtering::{Filter, Operator};
+use crate::pagination::Page;
+use crate::problems::Problem;
+use crate::routing::{DeleteSingle, Endpoint, ListQuery, ReadMultiple, UpdateSingle};
+use crate::validation::ValidationError;
+use axum::extract::FromRef;
+use lily_core::Error;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::Mutex;
+
+/// The kind of a relation between two endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelationKind {
+    /// The field holds the id of a single resource of the other endpoint.
+    BelongsTo,
+    /// The field holds the ids of any number of resources of the other endpoint.
+    ManyToMany,
+}
+
+/// What happens to the referencing resources when a referenced resource is deleted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnDelete {
+    /// The deletion is rejected while the resource is referenced.
+    #[default]
+    Restrict,
+    /// The referencing resources are deleted as well.
+    Cascade,
+    /// The references are set to `null`.
+    SetNull,
+}
+
+/// A relation of an endpoint to another endpoint.
+#[derive(Clone, Copy, Debug)]
+pub struct Relation {
+    pub kind: RelationKind,
+    /// The policy applied to the referencing resources on deletion.
+    pub on_delete: OnDelete,
+    /// The name of the relation, used by the `include` query parameter.
+    pub name: &'static str,
+    /// The name of the field holding the reference.
+    pub field: &'static str,
+    /// Returns the name of the referenced endpoint.
+    pub target: fn() -> String,
+}
+
+/// Stores the links of a many-to-many relation to `T`, declared with `#[lily(many = T)]`.
+///
+/// An endpoint can only have one many-to-many relation to each other endpoint.
+#[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` has a many-to-many relation to `{T}`, but does not implement `ManyToMany<{T}>`",
+    label = "`ManyToMany<{T}>` is not implemented for `{Self}`",
+    note = "implement `ManyToMany<{T}>` for `{Self}` to store the links between both"
+)]
+pub trait ManyToMany<T: Endpoint>: Endpoint {
+    /// Links a resource to an existing resource of `T`.
+    ///
+    /// Returns `false` if the resource does not exist. Linking resources that
+    /// are already linked has no effect.
+    async fn link(
+        state: &Self::State,
+        context: &Context,
+        id: &Self::Id,
+        related_id: &T::Id,
+    ) -> Result<bool, Error>;
+
+    /// Removes the link between a resource and a resource of `T`.
+    ///
+    /// Returns `None` if the resource does not exist, and `Some(false)` if it
+    /// was not linked to the resource of `T`.
+    async fn unlink(
+        state: &Self::State,
+        context: &Context,
+        id: &Self::Id,
+        related_id: &T::Id,
+    ) -> Result<Option<bool>, Error>;
+
+    /// Lists the resources linked to an existing resource of `T`.
+    async fn read_linked(
+        state: &Self::State,
+        context: &Context,
+        related_id: &T::Id,
+        query: &ListQuery<Self>,
+    ) -> Result<Page<Self>, Error>;
+}
+
+/// Looks up the resources referenced by a payload, with the state `S` of its endpoint.
+#[allow(async_fn_in_trait)]
+pub trait References<S> {
+    /// Returns a [`ValidationError`] for every referenced resource that does not exist.
+    ///
+    /// Every resource is looked up through `lookups`, which is shared by the
+    /// items of a bulk request.
+    async fn missing_references(
+        &self,
+        state: &S,
+        context: &Context,
+        lookups: &ReferenceLookups,
+    ) -> Result<Vec<ValidationError>, Error>;
+}
+
+/// Remembers which referenced resources exist, so every resource is only
+/// looked up once per request.
+///
+/// # Examples
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// use lily_endpoint::relations::ReferenceLookups;
+///
+/// let lookups = ReferenceLookups::default();
+/// assert!(lookups.exists("author", "author-1".to_owned(), async { Ok(true) }).await.unwrap());
+/// // Known resources are not looked up again
+/// assert!(lookups.exists("author", "author-1".to_owned(), async { Ok(false) }).await.unwrap());
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ReferenceLookups {
+    /// Whether a resource exists, by the name of its endpoint and its id.
+    known: Mutex<HashMap<(String, String), bool>>,
+}
+
+impl ReferenceLookups {
+    /// Checks whether a resource exists, running `lookup` unless the resource
+    /// was looked up before.
+    pub async fn exists(
+        &self,
+        resource: &str,
+        id: String,
+        lookup: impl Future<Output = Result<bool, Error>>,
+    ) -> Result<bool, Error> {
+        let key = (resource.to_owned(), id);
+        let known = self
+            .known
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .get(&key)
+            .copied();
+        if let Some(exists) = known {
+            return Ok(exists);
+        }
+        let exists = lookup.await?;
+        self.known
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .insert(key, exists);
+        Ok(exists)
+    }
+}
+
+impl<S, Id, P: References<S>> References<S> for BulkUpdate<Id, P> {
+    async fn missing_references(
+        &self,
+        state: &S,
+        context: &Context,
+        lookups: &ReferenceLookups,
+    ) -> Result<Vec<ValidationError>, Error> {
+        Ok(prefix_all(
+            self.patch
+                .missing_references(state, context, lookups)
+                .await?,
+            "/patch",
+        ))
+    }
+}
+
+impl<S, Id, P: References<S>> References<S> for BulkReplaceItem<Id, P> {
+    async fn missing_references(
+        &self,
+        state: &S,
+        context: &Context,
+        lookups: &ReferenceLookups,
+    ) -> Result<Vec<ValidationError>, Error> {
+        Ok(prefix_all(
+            self.data
+                .missing_references(state, context, lookups)
+                .await?,
+            "/data",
+        ))
+    }
+}
+
+/// Checks that every resource referenced by a payload exists.
+pub async fn check_references<S, P: References<S>>(
+    state: &S,
+    context: &Context,
+    payload: &P,
+) -> Result<(), Problem> {
+    let lookups = ReferenceLookups::default();
+    match payload.missing_references(state, context, &lookups).await {
+        Ok(errors) if errors.is_empty() => Ok(()),
+        Ok(errors) => Err(Problem::InvalidReference { errors }),
+        Err(error_msg) => {
+            eprintln!("Error checking references: {}", error_msg);
+            Err(Problem::InternalError)
+        }
+    }
+}
+
+/// Checks the references of every item of a bulk request, prefixing the
+/// pointers with the index of the item.
+pub async fn check_references_bulk<S, P: References<S>>(
+    state: &S,
+    context: &Context,
+    items: &[P],
+) -> Result<(), Problem> {
+    let errors: Vec<ValidationError> = check_references_each(state, context, items)
+        .await?
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index, problem)| match problem {
+            Some(Problem::InvalidReference { errors }) => {
+                prefix_all(errors, &format!("/{}", index))
+            }
+            _ => Vec::new(),
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Problem::InvalidReference { errors })
+    }
+}
+
+/// Checks the references of every item of a
+/// [`BulkMode::Partial`](crate::bulk::BulkMode::Partial) bulk request on its
+/// own, returning the problem of each item with missing references.
+///
+/// Resources referenced by several items are only looked up once.
+pub async fn check_references_each<S, P: References<S>>(
+    state: &S,
+    context: &Context,
+    items: &[P],
+) -> Result<Vec<Option<Problem>>, Problem> {
+    let lookups = ReferenceLookups::default();
+    let mut problems: Vec<Option<Problem>> = Vec::with_capacity(items.len());
+    for item in items {
+        match item.missing_references(state, context, &lookups).await {
+            Ok(errors) if errors.is_empty() => problems.push(None),
+            Ok(errors) => problems.push(Some(Problem::InvalidReference { errors })),
+            Err(error_msg) => {
+                eprintln!("Error checking references: {}", error_msg);
+                return Err(Problem::InternalError);
+            }
+        }
+    }
+    Ok(problems)
+}
+
+fn prefix_all(errors: Vec<ValidationError>, prefix: &str) -> Vec<ValidationError> {
+    errors
+        .into_iter()
+        .map(|error| error.prefixed(prefix))
+        .collect()
+}
+
+/// The resources of an endpoint referencing a resource that is being deleted.
+#[derive(Clone, Debug, Serialize)]
+pub struct Dependents {
+    pub resource: String,
+    pub ids: Vec<String>,
+}
+
+/// The state of an endpoint, type-erased as cascades pass through the relations of several endpoints.
+pub type AnyState = dyn Any + Send + Sync;
+
+/// The future of a lookup or change of dependent resources, boxed as cascades
+/// are applied recursively.
+pub type DependentFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Problem>> + Send + 'a>>;
+
+/// A `belongs_to` relation of an endpoint, seen from the referenced endpoint.
+#[derive(Clone, Copy)]
+pub struct Dependent {
+    /// Returns the name of the endpoint holding the reference.
+    pub source: fn() -> String,
+    /// Returns the relations referencing the endpoint holding the reference,
+    /// followed by cascades.
+    pub dependents: fn() -> &'static [&'static [Dependent]],
+    pub relation: Relation,
+    /// Takes the state of the endpoint holding the reference from the state of
+    /// the referenced endpoint.
+    pub state: fn(&AnyState) -> Result<Box<AnyState>, Problem>,
+    /// Lists the ids of the resources referencing the given id.
+    pub find: for<'a> fn(&'a AnyState, &'a Context, &'a str) -> DependentFuture<'a, Vec<String>>,
+    /// Deletes a referencing resource or clears its reference, following the
+    /// policy of the relation. Not used for `restrict`.
+    pub resolve: for<'a> fn(&'a AnyState, &'a Context, &'a str) -> DependentFuture<'a, ()>,
+}
+
+/// The `belongs_to` relations of an endpoint to the endpoint `T`, implemented
+/// by the endpoint macro for endpoints with a list route.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is listed in `referenced_by` of `{T}`, but has no `belongs_to` relation to it",
+    label = "`{Self}` does not reference `{T}`",
+    note = "the relations of an endpoint are only known if it enables the `read_multiple` action"
+)]
+pub trait DependentOf<T: Endpoint>: Endpoint {
+    const DEPENDENTS: &'static [Dependent];
+}
+
+/// Marks that the endpoint lists `S` in its `referenced_by` argument,
+/// implemented by the endpoint macro.
+///
+/// Every `belongs_to` relation requires it, so the policies of a relation can
+/// not be skipped by the referenced endpoint.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is referenced by `{S}`, but does not list it in `referenced_by`",
+    label = "`{Self}` does not list `{S}`",
+    note = "add `referenced_by({S})` to the endpoint macro of `{Self}`, so that deleting a `{Self}` follows the `on_delete` policy of `{S}`"
+)]
+pub trait ReferencedBy<S: Endpoint>: Endpoint {}
+
+/// Applies the `cascade` and `set_null` policies of all relations referencing
+/// a deleted resource, with the state of its endpoint and the context of the
+/// deleting request.
+///
+/// The delete routes only call this once the resource is deleted, after
+/// checking its `restrict` relations with [`check_on_delete`].
+pub async fn apply_on_delete<E: Endpoint>(
+    id: &E::Id,
+    state: &E::State,
+    context: &Context,
+) -> Result<(), Problem> {
+    let (target, id) = (E::get_name(), id.to_string());
+    let visited = Visited::new(&target, &id);
+    resolve_dependents(E::DEPENDENTS, id, state, context, &visited).await
+}
+
+/// Checks that no `restrict` relation, including those of cascaded resources,
+/// references a resource that is about to be deleted.
+pub async fn check_on_delete<E: Endpoint>(
+    id: &E::Id,
+    state: &E::State,
+    context: &Context,
+) -> Result<(), Problem> {
+    let (target, id) = (E::get_name(), id.to_string());
+    let visited = Visited::new(&target, &id);
+    let restricted = find_restricted(E::DEPENDENTS, id.clone(), state, context, &visited).await?;
+    if restricted.is_empty() {
+        Ok(())
+    } else {
+        Err(Problem::DeleteRestricted {
+            resource: target,
+            id,
+            dependents: restricted,
+        })
+    }
+}
+
+/// Checks the resources of a bulk delete with [`check_on_delete`], returning
+/// the problem of every restricted resource.
+///
+/// Fails as a whole if the dependent resources can not be looked up.
+pub async fn check_on_delete_each<E: Endpoint>(
+    ids: &[E::Id],
+    state: &E::State,
+    context: &Context,
+) -> Result<Vec<Option<Problem>>, Problem> {
+    let mut problems: Vec<Option<Problem>> = Vec::with_capacity(ids.len());
+    for id in ids {
+        match check_on_delete::<E>(id, state, context).await {
+            Ok(()) => problems.push(None),
+            Err(problem @ Problem::DeleteRestricted { .. }) => problems.push(Some(problem)),
+            Err(problem) => return Err(problem),
+        }
+    }
+    Ok(problems)
+}
+
+/// The resources a cascade has reached, by the name of their endpoint and
+/// their id, so that cycles of `cascade` relations end.
+struct Visited(Mutex<HashSet<(String, String)>>);
+
+impl Visited {
+    /// Starts with the deleted resource.
+    fn new(target: &str, id: &str) -> Self {
+        Visited(Mutex::new(HashSet::from([(
+            target.to_owned(),
+            id.to_owned(),
+        )])))
+    }
+
+    /// Marks a resource as reached, returning `false` if it was reached before.
+    fn insert(&self, target: String, id: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .insert((target, id.to_owned()))
+    }
+}
+
+/// Collects the resources that prevent the deletion, following cascades.
+fn find_restricted<'a>(
+    dependents: &'static [&'static [Dependent]],
+    id: String,
+    state: &'a AnyState,
+    context: &'a Context,
+    visited: &'a Visited,
+) -> DependentFuture<'a, Vec<Dependents>> {
+    Box::pin(async move {
+        let mut restricted = Vec::new();
+        for dependent in dependents.concat() {
+            let source_state = (dependent.state)(state)?;
+            let ids = (dependent.find)(&*source_state, context, &id).await?;
+            match dependent.relation.on_delete {
+                OnDelete::Restrict if !ids.is_empty() => restricted.push(Dependents {
+                    resource: (dependent.source)(),
+                    ids,
+                }),
+                OnDelete::Cascade => {
+                    for dependent_id in ids {
+                        if !visited.insert((dependent.source)(), &dependent_id) {
+                            continue;
+                        }
+                        restricted.extend(
+                            find_restricted(
+                                (dependent.dependents)(),
+                                dependent_id,
+                                &*source_state,
+                                context,
+                                visited,
+                            )
+                            .await?,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(restricted)
+    })
+}
+
+/// Deletes or detaches the referencing resources, following cascades.
+fn resolve_dependents<'a>(
+    dependents: &'static [&'static [Dependent]],
+    id: String,
+    state: &'a AnyState,
+    context: &'a Context,
+    visited: &'a Visited,
+) -> DependentFuture<'a, ()> {
+    Box::pin(async move {
+        for dependent in dependents.concat() {
+            if dependent.relation.on_delete == OnDelete::Restrict {
+                continue;
+            }
+            let source_state = (dependent.state)(state)?;
+            for dependent_id in (dependent.find)(&*source_state, context, &id).await? {
+                if dependent.relation.on_delete == OnDelete::Cascade {
+                    // Resources that are deleted already, like the deleted resource itself in a
+                    // cycle, are skipped
+                    if !visited.insert((dependent.source)(), &dependent_id) {
+                        continue;
+                    }
+                    resolve_dependents(
+                        (dependent.dependents)(),
+                        dependent_id.clone(),
+                        &*source_state,
+                        context,
+                        visited,
+                    )
+                    .await?;
+                }
+                (dependent.resolve)(&*source_state, context, &dependent_id).await?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Takes the state of `E` from the type-erased state of `T`.
+pub fn state_from<E: Endpoint, T: Endpoint>(state: &AnyState) -> Result<Box<AnyState>, Problem>
+where
+    E::State: FromRef<T::State>,
+{
+    Ok(Box::new(E::State::from_ref(downcast_state::<T>(state)?)))
+}
+
+/// Restores the state of `E` from its type-erased form.
+fn downcast_state<E: Endpoint>(state: &AnyState) -> Result<&E::State, Problem> {
+    state.downcast_ref().ok_or_else(|| {
+        eprintln!("Error restoring the state of [{}]", E::get_name());
+        Problem::InternalError
+    })
+}
+
+/// Lists the ids of the resources of `E` whose `field` holds the id, in a single page.
+pub async fn find_dependents<E: ReadMultiple>(
+    state: &AnyState,
+    context: &Context,
+    field: &str,
+    id: &str,
+) -> Result<Vec<String>, Problem> {
+    let state = downcast_state::<E>(state)?;
+    let mut filter = E::Filter::default();
+    filter.add_condition(field, Operator::Eq, id)?;
+    let query = ListQuery {
+        filter,
+        ..ListQuery::all()
+    };
+    match E::read_multiple(state, context, &query).await {
+        Ok(page) => Ok(page
+            .items
+            .iter()
+            .map(|item| item.id().to_string())
+            .collect()),
+        Err(error_msg) => {
+            eprintln!(
+                "Error finding dependents [{}]: {}",
+                E::get_name(),
+                error_msg
+            );
+            Err(Problem::InternalError)
+        }
+    }
+}
+
+/// Deletes a resource of `E` that references a deleted resource.
+pub async fn delete_dependent<E: DeleteSingle>(
+    state: &AnyState,
+    context: &Context,
+    id: &str,
+) -> Result<(), Problem> {
+    let state = downcast_state::<E>(state)?;
+    let id = E::parse_id(id)?;
+    match E::delete_single(state, context, &id).await {
+        Ok(_) => Ok(()),
+        Err(error_msg) => {
+            eprintln!(
+                "Error deleting dependent [{}]: {}",
+                E::get_name(),
+                error_msg
+            );
+            Err(Problem::InternalError)
+        }
+    }
+}
+
+/// Sets the `field` of a resource of `E` that references a deleted resource to `null`.
+pub async fn nullify_dependent<E: UpdateSingle>(
+    state: &AnyState,
+    context: &Context,
+    field: &str,
+    id: &str,
+) -> Result<(), Problem> {
+    let state = downcast_state::<E>(state)?;
+    let id = E::parse_id(id)?;
+    let payload: E::UpdatePayload = serde_json::from_value(serde_json::json!({ field: null }))
+        .map_err(|error| {
+            eprintln!("Error clearing dependent [{}]: {}", E::get_name(), error);
+            Problem::InternalError
+        })?;
+    match E::update_single(state, context, &id, &payload).await {
+        Ok(_) => Ok(()),
+        Err(error_msg) => {
+            eprintln!(
+                "Error clearing dependent [{}]: {}",
+                E::get_name(),
+                error_msg
+            );
+            Err(Problem::InternalError)
+        }
+    }
+}
+
+/// Leaves a dependent resource as it is, used by `restrict` relations.
+pub fn resolve_nothing<'a>(_: &'a AnyState, _: &'a Context, _: &'a str) -> DependentFuture<'a, ()> {
+    Box::pin(async { Ok(()) })
+}
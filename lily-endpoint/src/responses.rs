@@ -18,6 +18,7 @@ pub enum ApiResponse<T: Serialize> {
     Ok(T),
     Created(T),
     NoContent,
+    MultiStatus(T),
     NotFound(Problem),
     Erroneous(Problem),
     // Escape hatch for special cases, avoid using
@@ -32,13 +33,16 @@ impl<T: Serialize> IntoResponse for ApiResponse<T> {
             ApiResponse::Ok(content) => (StatusCode::OK, Json(content)).into_response(),
             ApiResponse::Created(content) => (StatusCode::CREATED, Json(content)).into_response(),
             ApiResponse::NoContent => StatusCode::NO_CONTENT.into_response(),
+            ApiResponse::MultiStatus(content) => {
+                (StatusCode::MULTI_STATUS, Json(content)).into_response()
+            }
             ApiResponse::NotFound(problem) => {
                 let json_problem: JsonProblem = problem.into();
-                (json_problem.status, Json(json_problem)).into_response()
+                json_problem.into_response()
             }
             ApiResponse::Erroneous(problem) => {
                 let json_problem: JsonProblem = problem.into();
-                (json_problem.status, Json(json_problem)).into_response()
+                json_problem.into_response()
             }
             ApiResponse::Custom(status_code, content) => {
                 (status_code, Json(content)).into_response()
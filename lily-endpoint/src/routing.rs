@@ -1,59 +1,394 @@
 //! Provides traits and functions for building REST API endpoints from structs
 
+use crate::bulk::{BulkItemError, BulkMode, BulkReplaceItem};
+use crate::context::Context;
+use crate::filtering::Filter;
+use crate::pagination::{Page, Pagination};
+use crate::problems::Problem;
+use crate::relations::{Dependent, References, Relation};
+use crate::sorting::{Sort, SortField, compare_by};
+use crate::validation::Validate;
 use axum::Router;
 use lily_core::Error;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
+static PATH_PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Sets a global prefix like `/api/v1`, under which [`RouteBuilder::routes`]
+/// nests the routes of every endpoint.
+///
+/// Must be called before the first router is built, which otherwise keeps
+/// the routes at the root. Returns `false` if the same prefix has already
+/// been set.
+///
+/// # Panics
+/// Panics if a different prefix has already been set or used by a router.
+#[must_use]
+pub fn set_path_prefix(prefix: &str) -> bool {
+    let prefix = format!("/{}", prefix.trim_matches('/'));
+    match PATH_PREFIX.set(prefix) {
+        Ok(()) => true,
+        Err(prefix) if prefix == path_prefix() => false,
+        Err(prefix) => panic!(
+            "The path prefix can not be set to '{}', as the prefix '{}' is already in use",
+            prefix,
+            path_prefix()
+        ),
+    }
+}
+
+/// Returns the global path prefix, or `/` if none has been set.
+pub fn path_prefix() -> &'static str {
+    PATH_PREFIX.get_or_init(|| "/".to_owned())
+}
+
+/// Builds the routes of an endpoint, generated by the endpoint macro.
+///
+/// The routes expect the [`Endpoint::State`] of the endpoint, which is
+/// provided with [`Router::with_state`].
 pub trait RouteBuilder: Endpoint {
-    fn add_create_single_route(router: Router) -> Router;
-    fn add_read_single_route(router: Router) -> Router;
-    fn add_update_single_route(router: Router) -> Router;
-    // fn add_replace_single_route(router: Router) -> Router;
-    fn add_delete_single_route(router: Router) -> Router;
-
-    fn routes() -> Router {
-        let router: Router = Router::new();
+    fn add_create_single_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_create_multiple_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_read_single_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_read_multiple_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_update_single_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_update_multiple_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_replace_single_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_replace_multiple_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_delete_single_route(router: Router<Self::State>) -> Router<Self::State>;
+    fn add_delete_multiple_route(router: Router<Self::State>) -> Router<Self::State>;
+    /// Adds the routes linking the resources of many-to-many relations.
+    fn add_relation_routes(router: Router<Self::State>) -> Router<Self::State>;
+
+    fn routes() -> Router<Self::State> {
+        let router: Router<Self::State> = Router::new();
         let router = Self::add_create_single_route(router);
+        let router = Self::add_create_multiple_route(router);
         let router = Self::add_read_single_route(router);
+        let router = Self::add_read_multiple_route(router);
         let router = Self::add_update_single_route(router);
-        // let router = Self::add_replace_single_route(router);
+        let router = Self::add_update_multiple_route(router);
+        let router = Self::add_replace_single_route(router);
+        let router = Self::add_replace_multiple_route(router);
         let router = Self::add_delete_single_route(router);
-        router
+        let router = Self::add_delete_multiple_route(router);
+        let router = Self::add_relation_routes(router);
+        match path_prefix() {
+            "/" => router,
+            prefix => Router::new().nest(prefix, router),
+        }
     }
 }
 
 /// Defines the contract for a type that can be exposed as a REST API endpoint.
 pub trait Endpoint: Serialize + Sized {
-    type Id;
-    type CreatePayload: for<'de> Deserialize<'de>;
-    type UpdatePayload: for<'de> Deserialize<'de>;
+    /// The type of the `id` field, parsed from the path of single-resource routes.
+    type Id: FromStr + Display + Serialize + DeserializeOwned;
+    /// The application state handed to the handlers and to every trait
+    /// method, set with `#[endpoint(state = AppState)]` and `()` by default.
+    ///
+    /// Relations look the resources of other endpoints up with their state,
+    /// which is taken from this one with [`FromRef`](axum::extract::FromRef).
+    type State: Clone + Send + Sync + 'static;
+    type CreatePayload: for<'de> Deserialize<'de> + Validate + References<Self::State>;
+    type UpdatePayload: for<'de> Deserialize<'de> + Validate + References<Self::State>;
+    type ReplacePayload: for<'de> Deserialize<'de> + Validate + References<Self::State>;
+    type Filter: Filter<Self>;
+    type SortField: SortField<Self>;
+
+    /// The names of all serialized fields, used to validate sparse fieldsets.
+    const FIELDS: &'static [&'static str];
+    /// The names of the fields of the create payload.
+    const CREATE_FIELDS: &'static [&'static str];
+    /// The names of the fields of the update payload, the only fields a patch document may change.
+    const UPDATE_FIELDS: &'static [&'static str];
+    /// The relations to other endpoints, declared with `#[lily(belongs_to = ..)]` and `#[lily(many = ..)]`.
+    const RELATIONS: &'static [Relation];
+    /// The `belongs_to` relations of other endpoints to this one, one slice per
+    /// endpoint listed with `#[endpoint(referenced_by(..))]`.
+    const DEPENDENTS: &'static [&'static [Dependent]];
 
     fn get_name() -> String;
     fn get_path() -> String;
     fn get_path_with_id() -> String;
+    fn get_bulk_path() -> String;
+
+    fn id(&self) -> &Self::Id;
+
+    /// Parses the id segment of a path, failing with a 400 problem if it is not a valid id.
+    fn parse_id(raw: &str) -> Result<Self::Id, Problem> {
+        raw.parse().map_err(|_| Problem::InvalidId {
+            resource: Self::get_name(),
+            id: raw.to_owned(),
+        })
+    }
+}
+
+/// The parameters of a list request, handed to [`ReadMultiple::read_multiple`].
+pub struct ListQuery<E: Endpoint> {
+    pub pagination: Pagination,
+    pub filter: E::Filter,
+    pub sort: Vec<Sort<E::SortField>>,
+}
+
+impl<E: Endpoint> ListQuery<E> {
+    /// A query for every item on a single page, unfiltered and unsorted.
+    pub fn all() -> Self {
+        ListQuery {
+            pagination: Pagination {
+                limit: u64::MAX,
+                offset: 0,
+                cursor: None,
+            },
+            filter: E::Filter::default(),
+            sort: Vec::new(),
+        }
+    }
+
+    /// Compares two items by the requested sort keys, using the id as the
+    /// final tie-breaker so that the order is stable across requests.
+    pub fn compare(&self, a: &E, b: &E) -> Ordering
+    where
+        E::Id: PartialOrd,
+    {
+        compare_by(&self.sort, a, b)
+            .then_with(|| a.id().partial_cmp(b.id()).unwrap_or(Ordering::Equal))
+    }
+
+    /// Checks whether an item belongs on the requested page in cursor mode,
+    /// i.e. whether it comes after the cursor in the order of
+    /// [`ListQuery::compare`]. Always `true` if there is no cursor.
+    pub fn is_after_cursor(&self, item: &E) -> bool
+    where
+        E::Id: PartialOrd + DeserializeOwned,
+    {
+        self.pagination
+            .cursor
+            .as_ref()
+            .is_none_or(|cursor| cursor.precedes(item, &self.sort))
+    }
+}
+
+/// Selects the resources a bulk operation applies to.
+pub enum Selection<E: Endpoint> {
+    /// An explicit list of ids.
+    Ids(Vec<E::Id>),
+    /// Every resource matching a non-empty filter.
+    Filter(E::Filter),
 }
 
 #[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `create_single` action, but does not implement `CreateSingle`",
+    label = "`CreateSingle` is not implemented for `{Self}`",
+    note = "implement `CreateSingle` for `{Self}`, or remove `create_single` from the arguments of `#[endpoint]`"
+)]
 pub trait CreateSingle: Endpoint {
-    async fn create_single(payload: &Self::CreatePayload) -> Result<Self, Error>;
+    async fn create_single(
+        state: &Self::State,
+        context: &Context,
+        payload: &Self::CreatePayload,
+    ) -> Result<Self, Error>;
+}
+
+#[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `create_multiple` action, but does not implement `CreateMultiple`",
+    label = "`CreateMultiple` is not implemented for `{Self}`",
+    note = "implement `CreateMultiple` for `{Self}`, or remove `create_multiple` from the arguments of `#[endpoint]`"
+)]
+pub trait CreateMultiple: Endpoint {
+    /// The largest number of items a single bulk request may contain.
+    const MAX_BULK_SIZE: usize = 1000;
+
+    /// Creates a resource for every payload.
+    ///
+    /// The returned results must be in the same order as the payloads. In
+    /// [`BulkMode::Atomic`], no resource may be persisted if any item fails;
+    /// the items that were not persisted are [`BulkItemError::NotApplied`].
+    ///
+    /// In [`BulkMode::Partial`], payloads that fail validation are reported by
+    /// the route and not passed on.
+    async fn create_multiple(
+        state: &Self::State,
+        context: &Context,
+        payloads: &[Self::CreatePayload],
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Self, BulkItemError>>, Error>;
 }
 
 #[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `read_single` action, but does not implement `ReadSingle`",
+    label = "`ReadSingle` is not implemented for `{Self}`",
+    note = "implement `ReadSingle` for `{Self}`, or remove `read_single` from the arguments of `#[endpoint]`"
+)]
 pub trait ReadSingle: Endpoint {
-    async fn read_single(id: &Self::Id) -> Result<Option<Self>, Error>;
+    async fn read_single(
+        state: &Self::State,
+        context: &Context,
+        id: &Self::Id,
+    ) -> Result<Option<Self>, Error>;
+}
+
+#[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `read_multiple` action, but does not implement `ReadMultiple`",
+    label = "`ReadMultiple` is not implemented for `{Self}`",
+    note = "implement `ReadMultiple` for `{Self}`, or remove `read_multiple` from the arguments of `#[endpoint]`"
+)]
+pub trait ReadMultiple: Endpoint {
+    /// The page size used when a request does not specify a `limit`.
+    const DEFAULT_PAGE_SIZE: u64 = 20;
+    /// The largest page size a client may request; larger limits are clamped.
+    ///
+    /// In cursor mode, [`Pagination::limit`] exceeds it by one item.
+    const MAX_PAGE_SIZE: u64 = 100;
+
+    async fn read_multiple(
+        state: &Self::State,
+        context: &Context,
+        query: &ListQuery<Self>,
+    ) -> Result<Page<Self>, Error>;
 }
 
 #[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `update_single` action, but does not implement `UpdateSingle`",
+    label = "`UpdateSingle` is not implemented for `{Self}`",
+    note = "implement `UpdateSingle` for `{Self}`, or remove `update_single` from the arguments of `#[endpoint]`"
+)]
 pub trait UpdateSingle: Endpoint {
-    async fn update_single(id: &Self::Id, payload: &Self::UpdatePayload) -> Result<Self, Error>;
+    async fn update_single(
+        state: &Self::State,
+        context: &Context,
+        id: &Self::Id,
+        payload: &Self::UpdatePayload,
+    ) -> Result<Self, Error>;
+}
+
+#[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `update_multiple` action, but does not implement `UpdateMultiple`",
+    label = "`UpdateMultiple` is not implemented for `{Self}`",
+    note = "implement `UpdateMultiple` for `{Self}`, or remove `update_multiple` from the arguments of `#[endpoint]`"
+)]
+pub trait UpdateMultiple: Endpoint {
+    /// The largest number of resources a single bulk request may change,
+    /// whether they are selected by ids or by a filter.
+    ///
+    /// Resources selected by a filter are counted with
+    /// [`ReadMultiple::read_multiple`] first, so selecting by filter requires
+    /// the `read_multiple` action.
+    const MAX_BULK_SIZE: usize = 1000;
+
+    /// Applies the same patch to every selected resource.
+    ///
+    /// For [`Selection::Ids`], the returned results must be in the same order
+    /// as the ids, with `None` for unknown ids. For [`Selection::Filter`], one
+    /// result per matching resource is expected. In [`BulkMode::Atomic`], no
+    /// resource may be changed if any item fails; the items that were not
+    /// applied are [`BulkItemError::NotApplied`].
+    async fn update_multiple(
+        state: &Self::State,
+        context: &Context,
+        selection: &Selection<Self>,
+        payload: &Self::UpdatePayload,
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Option<Self>, BulkItemError>>, Error>;
 }
 
-// #[allow(async_fn_in_trait)]
-// pub trait ReplaceSingle: Endpoint {
-//     async fn replace_single(id: &Self::Id, payload: &Self::UpdatePayload) -> Result<Self, Error>;
-// }
+/// The outcome of a successful [`ReplaceSingle::replace_single`] call.
+pub enum Replacement<T> {
+    /// An existing resource was replaced (`200 OK`).
+    Replaced(T),
+    /// The resource did not exist and was created (`201 Created`).
+    ///
+    /// Only implementations that support upserting return this variant; all
+    /// others return `Ok(None)` for unknown ids.
+    Created(T),
+}
 
 #[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `replace_single` action, but does not implement `ReplaceSingle`",
+    label = "`ReplaceSingle` is not implemented for `{Self}`",
+    note = "implement `ReplaceSingle` for `{Self}`, or remove `replace_single` from the arguments of `#[endpoint]`"
+)]
+pub trait ReplaceSingle: Endpoint {
+    async fn replace_single(
+        state: &Self::State,
+        context: &Context,
+        id: &Self::Id,
+        payload: &Self::ReplacePayload,
+    ) -> Result<Option<Replacement<Self>>, Error>;
+}
+
+#[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `replace_multiple` action, but does not implement `ReplaceMultiple`",
+    label = "`ReplaceMultiple` is not implemented for `{Self}`",
+    note = "implement `ReplaceMultiple` for `{Self}`, or remove `replace_multiple` from the arguments of `#[endpoint]`"
+)]
+pub trait ReplaceMultiple: Endpoint {
+    /// The largest number of items a single bulk request may contain.
+    const MAX_BULK_SIZE: usize = 1000;
+
+    /// Replaces every resource with the payload of its item.
+    ///
+    /// The returned results must be in the same order as the items, with
+    /// `None` for unknown ids. In [`BulkMode::Atomic`], no resource may be
+    /// changed if any item fails; the items that were not applied are
+    /// [`BulkItemError::NotApplied`].
+    ///
+    /// In [`BulkMode::Partial`], items that fail validation are reported by
+    /// the route and not passed on.
+    async fn replace_multiple(
+        state: &Self::State,
+        context: &Context,
+        items: &[BulkReplaceItem<Self::Id, Self::ReplacePayload>],
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Option<Replacement<Self>>, BulkItemError>>, Error>;
+}
+
+#[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `delete_single` action, but does not implement `DeleteSingle`",
+    label = "`DeleteSingle` is not implemented for `{Self}`",
+    note = "implement `DeleteSingle` for `{Self}`, or remove `delete_single` from the arguments of `#[endpoint]`"
+)]
 pub trait DeleteSingle: Endpoint {
-    async fn delete_single(id: &Self::Id) -> Result<Option<Self>, Error>;
+    async fn delete_single(
+        state: &Self::State,
+        context: &Context,
+        id: &Self::Id,
+    ) -> Result<Option<Self>, Error>;
+}
+
+#[allow(async_fn_in_trait)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` enables the `delete_multiple` action, but does not implement `DeleteMultiple`",
+    label = "`DeleteMultiple` is not implemented for `{Self}`",
+    note = "implement `DeleteMultiple` for `{Self}`, or remove `delete_multiple` from the arguments of `#[endpoint]`"
+)]
+pub trait DeleteMultiple: Endpoint {
+    /// The largest number of ids a single bulk request may contain.
+    const MAX_BULK_SIZE: usize = 1000;
+
+    /// Deletes every resource in `ids`.
+    ///
+    /// The returned results must be in the same order as the ids, with `None`
+    /// for unknown ids. In [`BulkMode::Atomic`], no resource may be deleted if
+    /// any item fails; the items that were not applied are
+    /// [`BulkItemError::NotApplied`].
+    async fn delete_multiple(
+        state: &Self::State,
+        context: &Context,
+        ids: &[Self::Id],
+        mode: BulkMode,
+    ) -> Result<Vec<Result<Option<Self>, BulkItemError>>, Error>;
 }
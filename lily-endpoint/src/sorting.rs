@@ -0,0 +1,98 @@
+//! Provides the `sort` query parameter of list routes.
+//!
+//! The parameter takes a comma-separated list of field names, each optionally
+//! prefixed with `-` for descending order, e.g. `?sort=-created_at,title`.
+//! Later fields break ties of earlier ones.
+//!
+//! The `endpoint` macro generates a [`SortField`] enum for every struct,
+//! listing all fields that can be sorted by.
+
+use crate::problems::Problem;
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+/// The raw sort query parameter of a list request.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SortQuery {
+    pub sort: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// A single sort key of a list request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sort<F> {
+    pub field: F,
+    pub direction: SortDirection,
+}
+
+/// A field of the endpoint type `T` that list results can be sorted by.
+///
+/// Implementations are generated by the `endpoint` macro.
+pub trait SortField<T>: Sized + Copy {
+    /// The names of all sortable fields.
+    const FIELDS: &'static [&'static str];
+
+    fn from_name(name: &str) -> Option<Self>;
+    fn name(&self) -> &'static str;
+
+    /// Compares two items by this field in ascending order.
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+
+    /// Returns the serialized value of this field, used to create cursors.
+    fn value(&self, item: &T) -> serde_json::Value;
+
+    /// Checks whether a serialized value, e.g. of a cursor, is a value of this field.
+    fn accepts_value(&self, value: &serde_json::Value) -> bool;
+
+    /// Compares the field of an item to a serialized value in ascending order.
+    ///
+    /// Returns `None` if the value is not a value of this field.
+    fn compare_value(&self, item: &T, value: &serde_json::Value) -> Option<Ordering>;
+}
+
+impl SortQuery {
+    /// Parses and validates the sort keys of the request.
+    pub fn parse<T, F: SortField<T>>(&self) -> Result<Vec<Sort<F>>, Problem> {
+        let Some(sort) = &self.sort else {
+            return Ok(Vec::new());
+        };
+
+        sort.split(',')
+            .map(|key| {
+                let (name, direction) = match key.strip_prefix('-') {
+                    Some(name) => (name, SortDirection::Descending),
+                    None => (key, SortDirection::Ascending),
+                };
+                let field = F::from_name(name).ok_or_else(|| Problem::InvalidQuery {
+                    detail: format!(
+                        "Unknown sort field '{}', expected one of: {}.",
+                        name,
+                        F::FIELDS.join(", ")
+                    ),
+                })?;
+                Ok(Sort { field, direction })
+            })
+            .collect()
+    }
+}
+
+/// Compares two items by all sort keys, e.g. to sort a `Vec` in memory.
+///
+/// # Examples
+/// ```ignore
+/// items.sort_by(|a, b| compare_by(&query.sort, a, b));
+/// ```
+pub fn compare_by<T, F: SortField<T>>(sort: &[Sort<F>], a: &T, b: &T) -> Ordering {
+    sort.iter()
+        .map(|sort| match sort.direction {
+            SortDirection::Ascending => sort.field.compare(a, b),
+            SortDirection::Descending => sort.field.compare(b, a),
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
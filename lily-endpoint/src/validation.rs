@@ -0,0 +1,241 @@
+//! Provides declarative validation of request payloads.
+//!
+//! The endpoint macro implements [`Validate`] for every generated payload,
+//! based on field attributes like `#[lily(length(min = 1, max = 200))]`,
+//! `#[lily(range(min = 0, max = 5))]`, `#[lily(regex = "^[a-z-]+$")]` and
+//! `#[lily(email)]`. Payloads are validated before they reach the
+//! implementation; failing payloads are rejected with a
+//! [`Problem::ValidationFailed`](crate::problems::Problem::ValidationFailed)
+//! listing every failing field.
+
+use crate::bulk::{BulkReplaceItem, BulkUpdate};
+use crate::problems::Problem;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// A single failing field of a payload.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ValidationError {
+    /// A JSON pointer (RFC 6901) to the failing field, e.g. `/title`.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(pointer: impl Into<String>, message: impl Into<String>) -> Self {
+        ValidationError {
+            pointer: pointer.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Prepends a prefix like `/3` to the pointer, used for nested payloads.
+    pub fn prefixed(self, prefix: &str) -> Self {
+        ValidationError {
+            pointer: format!("{}{}", prefix, self.pointer),
+            message: self.message,
+        }
+    }
+}
+
+/// Validates a payload, collecting every failing field.
+pub trait Validate {
+    fn validate(&self) -> Result<(), Vec<ValidationError>>;
+}
+
+impl<Id, P: Validate> Validate for BulkUpdate<Id, P> {
+    fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        self.patch
+            .validate()
+            .map_err(|errors| prefix_all(errors, "/patch"))
+    }
+}
+
+impl<Id, P: Validate> Validate for BulkReplaceItem<Id, P> {
+    fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        self.data
+            .validate()
+            .map_err(|errors| prefix_all(errors, "/data"))
+    }
+}
+
+/// Validates every item of a bulk request, prefixing the pointers with the
+/// index of the item.
+///
+/// # Examples
+/// ```
+/// use lily_endpoint::validation::{Validate, ValidationError, validate_bulk};
+///
+/// struct Payload(u8);
+///
+/// impl Validate for Payload {
+///     fn validate(&self) -> Result<(), Vec<ValidationError>> {
+///         match self.0 {
+///             0 => Err(vec![ValidationError::new("/value", "must not be zero")]),
+///             _ => Ok(()),
+///         }
+///     }
+/// }
+///
+/// let errors = validate_bulk(&[Payload(1), Payload(0)]).unwrap_err();
+/// assert_eq!(errors[0].pointer, "/1/value");
+/// ```
+pub fn validate_bulk<'a, T: Validate + 'a>(
+    items: impl IntoIterator<Item = &'a T>,
+) -> Result<(), Vec<ValidationError>> {
+    let errors: Vec<ValidationError> = items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, item)| item.validate().err().map(|errors| (index, errors)))
+        .flat_map(|(index, errors)| prefix_all(errors, &format!("/{}", index)))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Validates every item of a [`BulkMode::Partial`](crate::bulk::BulkMode::Partial)
+/// bulk request on its own, returning the problem of each failing item.
+///
+/// Unlike [`validate_bulk`], the pointers are relative to the item.
+pub fn validate_each<'a, T: Validate + 'a>(
+    items: impl IntoIterator<Item = &'a T>,
+) -> Vec<Option<Problem>> {
+    items
+        .into_iter()
+        .map(|item| {
+            item.validate()
+                .err()
+                .map(|errors| Problem::ValidationFailed { errors })
+        })
+        .collect()
+}
+
+fn prefix_all(errors: Vec<ValidationError>, prefix: &str) -> Vec<ValidationError> {
+    errors
+        .into_iter()
+        .map(|error| error.prefixed(prefix))
+        .collect()
+}
+
+/// A value with a length, checked by `#[lily(length(..))]`.
+///
+/// The length of a string is its number of characters, the length of a
+/// collection its number of items.
+pub trait HasLength {
+    fn length(&self) -> usize;
+}
+
+impl HasLength for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl HasLength for String {
+    fn length(&self) -> usize {
+        self.as_str().length()
+    }
+}
+
+impl<T> HasLength for [T] {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+macro_rules! impl_has_length {
+    ($($collection:ident<$($param:ident),+>),*) => {
+        $(
+            impl<$($param),+> HasLength for $collection<$($param),+> {
+                fn length(&self) -> usize {
+                    self.len()
+                }
+            }
+        )*
+    };
+}
+
+impl_has_length!(Vec<T>, VecDeque<T>, HashSet<T>, BTreeSet<T>, HashMap<K, V>, BTreeMap<K, V>);
+
+/// Checks the length of a value, returning a message if it is out of bounds.
+pub fn check_length<T: HasLength + ?Sized>(
+    value: &T,
+    min: Option<usize>,
+    max: Option<usize>,
+) -> Option<String> {
+    let length = value.length();
+    match (min, max) {
+        (Some(min), Some(max)) if length < min || length > max => {
+            Some(format!("must have a length between {} and {}", min, max))
+        }
+        (Some(min), None) if length < min => {
+            Some(format!("must have a length of at least {}", min))
+        }
+        (None, Some(max)) if length > max => Some(format!("must have a length of at most {}", max)),
+        _ => None,
+    }
+}
+
+/// Checks whether a value lies in a range, returning a message if it does not.
+pub fn check_range<T: PartialOrd + Display>(
+    value: &T,
+    min: Option<T>,
+    max: Option<T>,
+) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) if *value < min || *value > max => {
+            Some(format!("must be between {} and {}", min, max))
+        }
+        (Some(min), None) if *value < min => Some(format!("must be at least {}", min)),
+        (None, Some(max)) if *value > max => Some(format!("must be at most {}", max)),
+        _ => None,
+    }
+}
+
+/// Checks whether a value looks like an email address, returning a message if
+/// it does not.
+///
+/// This only checks the overall shape `local@domain.tld`; whether the address
+/// actually exists can only be verified by sending an email to it.
+pub fn check_email(value: &str) -> Option<String> {
+    let valid = value.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && !domain.contains('@')
+            && domain
+                .split_once('.')
+                .is_some_and(|(name, _)| !name.is_empty())
+            && !domain.ends_with('.')
+            && !value.chars().any(char::is_whitespace)
+    });
+    (!valid).then(|| "must be a valid email address".to_owned())
+}
+
+/// A regular expression checked by `#[lily(regex = "..")]`, compiled on first use.
+pub struct Pattern {
+    source: &'static str,
+    regex: OnceLock<Regex>,
+}
+
+impl Pattern {
+    pub const fn new(source: &'static str) -> Self {
+        Pattern {
+            source,
+            regex: OnceLock::new(),
+        }
+    }
+
+    /// Checks whether a value matches the pattern, returning a message if it
+    /// does not. The pattern itself is validated when the macro expands.
+    pub fn check(&self, value: &str) -> Option<String> {
+        let regex = self
+            .regex
+            .get_or_init(|| Regex::new(self.source).expect("Invalid validation pattern"));
+        (!regex.is_match(value)).then(|| format!("must match the pattern '{}'", self.source))
+    }
+}
@@ -1,13 +1,25 @@
 use proc_macro::TokenStream;
+use proc_macro2::Span;
 use quote::quote;
-use std::collections::HashSet;
+use std::collections::HashMap;
 use strum::IntoEnumIterator;
 use syn::{Ident, Token, parse::Parser, punctuated::Punctuated};
 
 use crate::StructNames;
+use crate::persistent;
+use crate::util::{closest_match, pluralize, to_kebab_case};
 
+pub mod field_attrs;
+pub mod filter;
+pub mod metadata;
 pub mod payload;
+pub mod relations;
 pub mod route_gen;
+pub mod sort;
+pub mod validate;
+
+/// The actions that enable several routes at once
+const SHORTHANDS: [&str; 1] = ["read"];
 
 pub fn expand_shorthand(action: &str) -> Vec<String> {
     match action {
@@ -16,68 +28,409 @@ pub fn expand_shorthand(action: &str) -> Vec<String> {
     }
 }
 
-pub fn parse_macro_args(attr: TokenStream) -> HashSet<String> {
+/// The pagination mode of the list route
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaginationMode {
+    Offset,
+    Cursor,
+}
+
+/// The strategy used to derive the resource name from the struct name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NamingStrategy {
+    Snake,
+    Kebab,
+    PluralSnake,
+    PluralKebab,
+}
+
+impl NamingStrategy {
+    /// Applies the strategy to the snake_case struct name
+    pub fn apply(&self, snake_case: &str) -> String {
+        match self {
+            NamingStrategy::Snake => snake_case.to_owned(),
+            NamingStrategy::Kebab => to_kebab_case(snake_case),
+            NamingStrategy::PluralSnake => pluralize(snake_case),
+            NamingStrategy::PluralKebab => to_kebab_case(&pluralize(snake_case)),
+        }
+    }
+}
+
+/// Additional derives and attributes of a generated payload
+///
+/// # Examples
+/// ```ignore
+/// #[endpoint(create_payload(derive(Default), attr(serde(deny_unknown_fields))))]
+/// ```
+#[derive(Default)]
+pub struct PayloadOptions {
+    pub derives: Vec<syn::Path>,
+    pub attrs: Vec<syn::Meta>,
+}
+
+impl PayloadOptions {
+    fn parse(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Self> {
+        let mut options = PayloadOptions::default();
+        meta.parse_nested_meta(|option| {
+            let content;
+            syn::parenthesized!(content in option.input);
+            if option.path.is_ident("derive") {
+                options
+                    .derives
+                    .extend(Punctuated::<syn::Path, Token![,]>::parse_terminated(
+                        &content,
+                    )?);
+            } else if option.path.is_ident("attr") {
+                options
+                    .attrs
+                    .extend(Punctuated::<syn::Meta, Token![,]>::parse_terminated(
+                        &content,
+                    )?);
+            } else {
+                return Err(option.error("expected `derive(..)` or `attr(..)`"));
+            }
+            Ok(())
+        })?;
+        Ok(options)
+    }
+}
+
+/// The parsed arguments of the endpoint macro
+///
+/// # Examples
+/// ```ignore
+/// #[endpoint(read, create_single, pagination = cursor, id = uuid::Uuid)]
+/// #[endpoint(naming = plural_kebab_case)]
+/// #[endpoint(path = "/articles")]
+/// #[endpoint(no_timestamps)]
+/// #[endpoint(update_payload(attr(serde(deny_unknown_fields))))]
+/// #[endpoint(state = AppState)]
+/// #[endpoint(referenced_by(Content, Comment))]
+/// ```
+pub struct EndpointArgs {
+    /// The enabled actions, with the span of the argument that enabled them
+    pub enabled_actions: HashMap<String, Span>,
+    pub pagination: PaginationMode,
+    /// The type of the injected id field, if the struct declares none
+    pub id_type: Option<syn::Type>,
+    /// Whether a missing `created_at` field is injected
+    pub timestamps: bool,
+    pub naming: NamingStrategy,
+    pub path: Option<String>,
+    pub create_payload: PayloadOptions,
+    pub update_payload: PayloadOptions,
+    pub replace_payload: PayloadOptions,
+    /// The application state passed to the handlers and the trait methods
+    pub state: Option<syn::Type>,
+    /// The endpoints with a `belongs_to` relation to this one, whose `on_delete` policies are
+    /// followed when a resource is deleted
+    pub referenced_by: Vec<syn::Path>,
+}
+
+pub fn parse_macro_args(attr: TokenStream) -> syn::Result<EndpointArgs> {
+    let mut actions: Vec<Ident> = Vec::new();
+    let mut pagination = PaginationMode::Offset;
+    let mut id_type: Option<syn::Type> = None;
+    let mut timestamps = true;
+    let mut naming = NamingStrategy::Snake;
+    let mut path: Option<String> = None;
+    let mut create_payload = PayloadOptions::default();
+    let mut update_payload = PayloadOptions::default();
+    let mut replace_payload = PayloadOptions::default();
+    let mut state: Option<syn::Type> = None;
+    let mut referenced_by: Vec<syn::Path> = Vec::new();
+
     // Parse macro arguments
-    let args = Punctuated::<Ident, Token![,]>::parse_terminated
-        .parse(attr)
-        .expect("Failed to parse macro arguments");
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("pagination") {
+            let mode: Ident = meta.value()?.parse()?;
+            pagination = match mode.to_string().as_str() {
+                "offset" => PaginationMode::Offset,
+                "cursor" => PaginationMode::Cursor,
+                _ => {
+                    return Err(syn::Error::new(
+                        mode.span(),
+                        "expected `offset` or `cursor`",
+                    ));
+                }
+            };
+            Ok(())
+        } else if meta.path.is_ident("naming") {
+            let strategy: Ident = meta.value()?.parse()?;
+            naming = match strategy.to_string().as_str() {
+                "snake_case" => NamingStrategy::Snake,
+                "kebab_case" => NamingStrategy::Kebab,
+                "plural_snake_case" => NamingStrategy::PluralSnake,
+                "plural_kebab_case" => NamingStrategy::PluralKebab,
+                _ => {
+                    return Err(syn::Error::new(
+                        strategy.span(),
+                        "expected `snake_case`, `kebab_case`, `plural_snake_case` or `plural_kebab_case`",
+                    ));
+                }
+            };
+            Ok(())
+        } else if meta.path.is_ident("path") {
+            let value: syn::LitStr = meta.value()?.parse()?;
+            let custom_path = value.value();
+            if !custom_path.starts_with('/') || custom_path.len() < 2 || custom_path.ends_with('/')
+            {
+                return Err(syn::Error::new(
+                    value.span(),
+                    "expected a path like \"/articles\" with a leading and no trailing slash",
+                ));
+            }
+            path = Some(custom_path);
+            Ok(())
+        } else if meta.path.is_ident("id") {
+            id_type = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("create_payload") {
+            create_payload = PayloadOptions::parse(&meta)?;
+            Ok(())
+        } else if meta.path.is_ident("update_payload") {
+            update_payload = PayloadOptions::parse(&meta)?;
+            Ok(())
+        } else if meta.path.is_ident("replace_payload") {
+            replace_payload = PayloadOptions::parse(&meta)?;
+            Ok(())
+        } else if meta.path.is_ident("state") {
+            state = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("referenced_by") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            referenced_by.extend(Punctuated::<syn::Path, Token![,]>::parse_terminated(
+                &content,
+            )?);
+            Ok(())
+        } else if meta.path.is_ident("no_timestamps") {
+            timestamps = false;
+            Ok(())
+        } else if let Some(action) = meta.path.get_ident() {
+            actions.push(action.clone());
+            Ok(())
+        } else {
+            Err(meta.error("unsupported endpoint argument"))
+        }
+    });
+    parser.parse(attr)?;
+
+    // Report every unknown action at once, suggesting the closest known one
+    let known_actions: Vec<String> = route_gen::Routes::iter()
+        .map(|route| route.as_snake_case())
+        .chain(SHORTHANDS.iter().map(|shorthand| shorthand.to_string()))
+        .collect();
+    let unknown_action_error = actions
+        .iter()
+        .filter(|action| !known_actions.contains(&action.to_string()))
+        .map(|action| {
+            let name = action.to_string();
+            let message = match closest_match(&name, &known_actions) {
+                Some(suggestion) => {
+                    format!("unknown action `{}`, did you mean `{}`?", name, suggestion)
+                }
+                None => format!(
+                    "unknown action `{}`, expected one of {}",
+                    name,
+                    known_actions
+                        .iter()
+                        .map(|action| format!("`{}`", action))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            syn::Error::new(action.span(), message)
+        })
+        .reduce(|mut combined, error| {
+            combined.combine(error);
+            combined
+        });
+    if let Some(error) = unknown_action_error {
+        return Err(error);
+    }
 
     // Create boolean flags based on the parsed arguments
-    let enabled_actions: HashSet<String> = if args.is_empty() {
+    let enabled_actions: HashMap<String, Span> = if actions.is_empty() {
         route_gen::Routes::iter()
-            .map(|route| route.as_snake_case().to_owned())
+            .map(|route| (route.as_snake_case(), Span::call_site()))
             .collect()
     } else {
-        args.iter()
-            .flat_map(|ident| crate::endpoint::expand_shorthand(&ident.to_string()))
+        actions
+            .iter()
+            .flat_map(|ident| {
+                expand_shorthand(&ident.to_string())
+                    .into_iter()
+                    .map(|action| (action, ident.span()))
+            })
             .collect()
     };
 
-    enabled_actions
+    Ok(EndpointArgs {
+        enabled_actions,
+        pagination,
+        id_type,
+        timestamps,
+        naming,
+        path,
+        create_payload,
+        update_payload,
+        replace_payload,
+        state,
+        referenced_by,
+    })
 }
 
 pub fn generate_endpoint_tokens(
-    struct_ast: syn::ItemStruct,
+    mut struct_ast: syn::ItemStruct,
     struct_names: &StructNames,
     attr: TokenStream,
-) -> proc_macro2::TokenStream {
+) -> syn::Result<proc_macro2::TokenStream> {
     // Parse macro arguments
-    let enabled_actions: HashSet<String> = parse_macro_args(attr);
+    let args: EndpointArgs = parse_macro_args(attr)?;
+
+    // Tuple and unit structs have no field names to expose
+    if !matches!(struct_ast.fields, syn::Fields::Named(_)) {
+        return Err(syn::Error::new_spanned(
+            &struct_ast.ident,
+            "the endpoint macro only supports structs with named fields",
+        ));
+    }
+
+    // Parse and strip the persistent attribute placed below the endpoint macro
+    let persistent_args = persistent::take_persistent_attr(&mut struct_ast)?;
+
+    // Parse and strip the field attributes
+    field_attrs::reject_serde_renames(&struct_ast)?;
+    let mut field_attrs = field_attrs::take_field_attrs(&mut struct_ast)?;
+    let metadata = metadata::Metadata::resolve(&struct_ast, &mut field_attrs, &args)?;
+
+    // Create filter and sort fields of the exposed fields, including the metadata fields
+    let exposed_struct_ast = |keep: fn(&field_attrs::FieldAttrs) -> bool| {
+        let mut exposed_struct_ast = struct_ast.clone();
+        field_attrs::retain_fields(&mut exposed_struct_ast, &field_attrs, keep);
+        metadata.add_missing_fields(&mut exposed_struct_ast);
+        exposed_struct_ast
+    };
+    let full_struct_ast = exposed_struct_ast(field_attrs::FieldAttrs::is_exposed);
+    let filter_tokens = filter::generate_filter(
+        &exposed_struct_ast(field_attrs::FieldAttrs::is_filterable),
+        struct_names,
+    );
+    let sort_field_tokens = sort::generate_sort_field(
+        &exposed_struct_ast(field_attrs::FieldAttrs::is_sortable),
+        struct_names,
+    );
+    let field_names: Vec<String> = full_struct_ast
+        .fields
+        .iter()
+        .filter_map(|field| field.ident.as_ref().map(|name| name.to_string()))
+        .collect();
+    let payload_field_names = |in_payload: fn(&field_attrs::FieldAttrs) -> bool| -> Vec<String> {
+        struct_ast
+            .fields
+            .iter()
+            .zip(field_attrs.iter())
+            .filter(|(_, field_attrs)| in_payload(field_attrs))
+            .filter_map(|(field, _)| field.ident.as_ref().map(|name| name.to_string()))
+            .collect()
+    };
+    let create_field_names = payload_field_names(field_attrs::FieldAttrs::in_create_payload);
+    let update_field_names = payload_field_names(field_attrs::FieldAttrs::in_update_payload);
+
+    let relations = relations::collect_relations(&struct_ast, &field_attrs)?;
+    relations::check_on_delete(&relations, &args.enabled_actions)?;
+    let relations_tokens = relations::generate_relations(&relations);
+    let dependent_of_tokens =
+        relations::generate_dependents(&struct_names.original, &relations, &args.enabled_actions);
+    let (dependents_tokens, referenced_by_tokens) =
+        relations::generate_referenced_by(&struct_names.original, &args.referenced_by);
+    let includes_tokens = relations::generate_includes(&struct_names.original, &relations);
+
+    // Create the storage of persistent endpoints
+    let persistence_tokens = match &persistent_args {
+        Some(persistent_args) => persistent::generate_persistence(
+            &struct_ast,
+            struct_names,
+            persistent_args,
+            &args.enabled_actions,
+            &metadata,
+            &field_attrs,
+        )?,
+        None => proc_macro2::TokenStream::new(),
+    };
 
     // Create payloads
-    let payload_tokens = payload::generate_payload(struct_ast, &struct_names);
+    let payload_tokens =
+        payload::generate_payload(struct_ast, struct_names, &args, &metadata, &field_attrs);
 
     // Create routes
     let route_builder_tokens: proc_macro2::TokenStream =
-        route_gen::get_route_builder(&struct_names, &enabled_actions);
+        route_gen::get_route_builder(struct_names, &args, &relations);
 
     // Create implementation for Endpoint trait
     let original_struct_name: &syn::Ident = &struct_names.original;
     let create_payload_name: &syn::Ident = &struct_names.create_payload_name;
     let update_payload_name: &syn::Ident = &struct_names.update_payload_name;
-    let snake_name: &String = &struct_names.snake_case;
+    let replace_payload_name: &syn::Ident = &struct_names.replace_payload_name;
+    let filter_name: &syn::Ident = &struct_names.filter_name;
+    let sort_field_name: &syn::Ident = &struct_names.sort_field_name;
+    let resource_name: String = args.naming.apply(&struct_names.snake_case);
+    let path_tokens = match &args.path {
+        Some(path) => quote! { #path.to_owned() },
+        None => quote! { format!("/{}", <Self as ::lily::Endpoint>::get_name()) },
+    };
+    let id_type: &syn::Type = &metadata.id_type;
+    let id_field: &syn::Ident = &metadata.id_field;
+    let state_type = match &args.state {
+        Some(state) => quote! { #state },
+        None => quote! { () },
+    };
     let impl_endpoint_tokens = quote! {
-        impl Endpoint for #original_struct_name {
-            type Id = String;
+        impl ::lily::Endpoint for #original_struct_name {
+            type Id = #id_type;
+            type State = #state_type;
             type CreatePayload = #create_payload_name;
             type UpdatePayload = #update_payload_name;
+            type ReplacePayload = #replace_payload_name;
+            type Filter = #filter_name;
+            type SortField = #sort_field_name;
+
+            const FIELDS: &'static [&'static str] = &[#(#field_names),*];
+            const CREATE_FIELDS: &'static [&'static str] = &[#(#create_field_names),*];
+            const UPDATE_FIELDS: &'static [&'static str] = &[#(#update_field_names),*];
+            const RELATIONS: &'static [::lily::Relation] = #relations_tokens;
+            const DEPENDENTS: &'static [&'static [::lily::Dependent]] = #dependents_tokens;
 
             fn get_name() -> String {
-                #snake_name.to_owned()
+                #resource_name.to_owned()
             }
             fn get_path() -> String {
-                format!("/{}", Self::get_name())
+                #path_tokens
             }
             fn get_path_with_id() -> String {
-                format!("/{}/{{id}}", Self::get_name())
+                format!("{}/{{id}}", <Self as ::lily::Endpoint>::get_path())
+            }
+            fn get_bulk_path() -> String {
+                format!("{}/_bulk", <Self as ::lily::Endpoint>::get_path())
+            }
+
+            fn id(&self) -> &Self::Id {
+                &self.#id_field
             }
         }
 
     };
 
-    quote! {
+    Ok(quote! {
         #payload_tokens
+        #filter_tokens
+        #sort_field_tokens
         #route_builder_tokens
         #impl_endpoint_tokens
-    }
+        #includes_tokens
+        #dependent_of_tokens
+        #referenced_by_tokens
+        #persistence_tokens
+    })
 }
@@ -0,0 +1,381 @@
+use crate::util::{is_collection, option_inner_type};
+use syn::parse_quote;
+
+/// The parsed `#[lily(...)]` attributes of a single struct field
+///
+/// # Examples
+/// ```ignore
+/// #[endpoint]
+/// pub struct User {
+///     name: String,
+///     #[lily(create_only)]
+///     email: String,
+///     #[lily(write_only)]
+///     password: String,
+///     #[lily(read_only)]
+///     login_count: u64,
+///     #[lily(hidden)]
+///     internal_note: Option<String>,
+///     #[lily(id)]
+///     user_id: u64,
+///     #[lily(created_at)]
+///     registered_at: chrono::DateTime<chrono::Utc>,
+///     #[lily(belongs_to = Team, on_delete = cascade)]
+///     team_id: u64,
+///     #[lily(many = Role)]
+///     roles: Vec<String>,
+///     #[lily(no_filter, no_sort)]
+///     last_login_ip: std::net::IpAddr,
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct FieldAttrs {
+    /// Serialized in responses, but never accepted in a payload
+    pub read_only: bool,
+    /// Accepted when creating a resource, but can not be changed afterwards
+    pub create_only: bool,
+    /// Accepted in payloads, but never serialized in responses
+    pub write_only: bool,
+    /// Neither accepted in payloads nor serialized in responses
+    pub hidden: bool,
+    /// The checks applied to the field when a payload is validated
+    pub validations: Vec<Validation>,
+    /// The metadata role of the field, which is never accepted in a payload
+    pub metadata: Option<MetadataField>,
+    /// The relation to another endpoint whose ids the field holds
+    pub relation: Option<(RelationKind, syn::Path)>,
+    /// The policy applied when the resource referenced by a `belongs_to` field is deleted
+    pub on_delete: Option<OnDelete>,
+    /// Not filterable, for types that do not implement `FilterValue`
+    pub no_filter: bool,
+    /// Not sortable, for types that do not implement `PartialOrd`
+    pub no_sort: bool,
+}
+
+/// A metadata field, which is managed by the implementation instead of the client
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataField {
+    Id,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl MetadataField {
+    /// The marker of the role, which is also the conventional field name
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetadataField::Id => "id",
+            MetadataField::CreatedAt => "created_at",
+            MetadataField::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+/// The kind of a relation declared on a field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelationKind {
+    /// `#[lily(belongs_to = ..)]`, the field holds a single id
+    BelongsTo,
+    /// `#[lily(many = ..)]`, the field holds a collection of ids
+    ManyToMany,
+}
+
+impl RelationKind {
+    /// The marker of the relation
+    pub fn name(&self) -> &'static str {
+        match self {
+            RelationKind::BelongsTo => "belongs_to",
+            RelationKind::ManyToMany => "many",
+        }
+    }
+}
+
+/// The `on_delete` policy of a `belongs_to` relation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnDelete {
+    Restrict,
+    Cascade,
+    SetNull,
+}
+
+impl OnDelete {
+    /// The value of the policy in `on_delete = ..`
+    pub fn name(&self) -> &'static str {
+        match self {
+            OnDelete::Restrict => "restrict",
+            OnDelete::Cascade => "cascade",
+            OnDelete::SetNull => "set_null",
+        }
+    }
+}
+
+/// A declarative check of a payload field
+///
+/// # Examples
+/// ```ignore
+/// #[lily(length(min = 1, max = 200))]
+/// #[lily(range(min = 0, max = 5))]
+/// #[lily(regex = "^[a-z-]+$")]
+/// #[lily(email)]
+/// ```
+#[derive(Clone)]
+pub enum Validation {
+    Length {
+        min: Option<syn::LitInt>,
+        max: Option<syn::LitInt>,
+    },
+    Range {
+        min: Option<Box<syn::Expr>>,
+        max: Option<Box<syn::Expr>>,
+    },
+    Regex(syn::LitStr),
+    Email,
+}
+
+impl FieldAttrs {
+    /// Parses all `#[lily(...)]` attributes of a field
+    pub fn from_field(field: &syn::Field) -> syn::Result<Self> {
+        let mut field_attrs = FieldAttrs::default();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("lily"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("read_only") {
+                    field_attrs.read_only = true;
+                } else if meta.path.is_ident("create_only") {
+                    field_attrs.create_only = true;
+                } else if meta.path.is_ident("write_only") {
+                    field_attrs.write_only = true;
+                } else if meta.path.is_ident("hidden") {
+                    field_attrs.hidden = true;
+                } else if meta.path.is_ident("no_filter") {
+                    field_attrs.no_filter = true;
+                } else if meta.path.is_ident("no_sort") {
+                    field_attrs.no_sort = true;
+                } else if let Some(role) = [
+                    MetadataField::Id,
+                    MetadataField::CreatedAt,
+                    MetadataField::UpdatedAt,
+                ]
+                .into_iter()
+                .find(|role| meta.path.is_ident(role.name()))
+                {
+                    if let Some(other) = field_attrs.metadata.replace(role) {
+                        return Err(meta.error(format!(
+                            "the field is already marked as `{}`",
+                            other.name()
+                        )));
+                    }
+                } else if meta.path.is_ident("length") {
+                    let (min, max) = parse_bounds(&meta)?;
+                    field_attrs.validations.push(Validation::Length { min, max });
+                } else if meta.path.is_ident("range") {
+                    let (min, max) = parse_bounds(&meta)?;
+                    field_attrs.validations.push(Validation::Range { min, max });
+                } else if meta.path.is_ident("regex") {
+                    let pattern: syn::LitStr = meta.value()?.parse()?;
+                    // Invalid patterns are reported at compile time instead of on the first request
+                    if let Err(error) = regex::Regex::new(&pattern.value()) {
+                        return Err(syn::Error::new(pattern.span(), error));
+                    }
+                    field_attrs.validations.push(Validation::Regex(pattern));
+                } else if meta.path.is_ident("email") {
+                    field_attrs.validations.push(Validation::Email);
+                } else if let Some(kind) = [RelationKind::BelongsTo, RelationKind::ManyToMany]
+                    .into_iter()
+                    .find(|kind| meta.path.is_ident(kind.name()))
+                {
+                    let target: syn::Path = meta.value()?.parse()?;
+                    if let Some((other, _)) = field_attrs.relation.replace((kind, target)) {
+                        return Err(meta.error(format!(
+                            "the field already has a `{}` relation",
+                            other.name()
+                        )));
+                    }
+                } else if meta.path.is_ident("on_delete") {
+                    let policy: syn::Ident = meta.value()?.parse()?;
+                    let Some(on_delete) = [OnDelete::Restrict, OnDelete::Cascade, OnDelete::SetNull]
+                        .into_iter()
+                        .find(|on_delete| policy == on_delete.name())
+                    else {
+                        return Err(syn::Error::new(
+                            policy.span(),
+                            "expected `restrict`, `cascade` or `set_null`",
+                        ));
+                    };
+                    field_attrs.on_delete = Some(on_delete);
+                } else {
+                    return Err(meta.error(
+                        "expected `read_only`, `create_only`, `write_only`, `hidden`, `no_filter`, `no_sort`, `id`, `created_at`, `updated_at`, `length`, `range`, `regex`, `email`, `belongs_to`, `many` or `on_delete`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+        match &field_attrs.relation {
+            // Nested routes filter on the field, so it has to be exposed
+            Some((RelationKind::BelongsTo, target)) if !field_attrs.is_filterable() => {
+                return Err(syn::Error::new_spanned(
+                    target,
+                    "a `belongs_to` field can not be `write_only`, `hidden` or `no_filter`",
+                ));
+            }
+            Some((RelationKind::ManyToMany, _))
+                if !is_collection(option_inner_type(&field.ty).unwrap_or(&field.ty)) =>
+            {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "a `many` field must be a collection of ids, like `Vec<String>`",
+                ));
+            }
+            _ => {}
+        }
+        match (&field_attrs.relation, field_attrs.on_delete) {
+            (Some((RelationKind::BelongsTo, _)), _) | (_, None) => {}
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "`on_delete` requires a `belongs_to` relation",
+                ));
+            }
+        }
+        // Clearing the reference goes through the update payload
+        if field_attrs.on_delete == Some(OnDelete::SetNull)
+            && (option_inner_type(&field.ty).is_none() || !field_attrs.in_update_payload())
+        {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "`on_delete = set_null` requires an `Option` field that can be updated",
+            ));
+        }
+        Ok(field_attrs)
+    }
+
+    /// Whether the field is part of the create payload (POST)
+    pub fn in_create_payload(&self) -> bool {
+        !self.read_only && !self.hidden && self.metadata.is_none()
+    }
+
+    /// Whether the field is part of the update (PATCH) and replace (PUT) payloads
+    pub fn in_update_payload(&self) -> bool {
+        self.in_create_payload() && !self.create_only
+    }
+
+    /// Whether the field is serialized in responses, and thereby filterable,
+    /// sortable and selectable in sparse fieldsets
+    pub fn is_exposed(&self) -> bool {
+        !self.write_only && !self.hidden
+    }
+
+    /// Whether the field can be used in query filters
+    pub fn is_filterable(&self) -> bool {
+        self.is_exposed() && !self.no_filter
+    }
+
+    /// Whether the field can be used as a sort key
+    pub fn is_sortable(&self) -> bool {
+        self.is_exposed() && !self.no_sort
+    }
+}
+
+/// Parses the `min` and `max` bounds of `length(..)` and `range(..)`
+fn parse_bounds<T: syn::parse::Parse>(
+    meta: &syn::meta::ParseNestedMeta,
+) -> syn::Result<(Option<T>, Option<T>)> {
+    let (mut min, mut max) = (None, None);
+    meta.parse_nested_meta(|bound| {
+        if bound.path.is_ident("min") {
+            min = Some(bound.value()?.parse()?);
+        } else if bound.path.is_ident("max") {
+            max = Some(bound.value()?.parse()?);
+        } else {
+            return Err(bound.error("expected `min` or `max`"));
+        }
+        Ok(())
+    })?;
+    if min.is_none() && max.is_none() {
+        return Err(meta.error("expected at least one of `min` and `max`"));
+    }
+    Ok((min, max))
+}
+
+/// Parses the `#[lily(...)]` attributes of all fields and strips them from the struct,
+/// as they are not known to the compiler
+pub fn take_field_attrs(struct_ast: &mut syn::ItemStruct) -> syn::Result<Vec<FieldAttrs>> {
+    struct_ast
+        .fields
+        .iter_mut()
+        .map(|field| {
+            let field_attrs = FieldAttrs::from_field(field)?;
+            field.attrs.retain(|attr| !attr.path().is_ident("lily"));
+            Ok(field_attrs)
+        })
+        .collect()
+}
+
+/// Rejects `#[serde(rename..)]` on the struct and its fields
+///
+/// The names of the fields in queries and payloads are their Rust names, so a
+/// renamed field could be filtered and sorted by a name it is not serialized with.
+pub fn reject_serde_renames(struct_ast: &syn::ItemStruct) -> syn::Result<()> {
+    let attrs = struct_ast
+        .attrs
+        .iter()
+        .chain(struct_ast.fields.iter().flat_map(|field| &field.attrs))
+        .filter(|attr| attr.path().is_ident("serde"));
+    for attr in attrs {
+        let Ok(list) = attr.meta.require_list() else {
+            continue;
+        };
+        // Only the top-level idents are names of serde attributes
+        for token in list.tokens.clone() {
+            if let proc_macro2::TokenTree::Ident(ident) = token
+                && ["rename", "rename_all", "rename_all_fields"]
+                    .contains(&ident.to_string().as_str())
+            {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!(
+                        "`#[serde({})]` is not supported by the endpoint macro, as fields are \
+                         filtered, sorted and selected by their Rust names; rename the field instead",
+                        ident
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes all fields whose attributes do not satisfy `keep`
+///
+/// # Examples
+/// ```ignore
+/// retain_fields(&mut struct_ast, &field_attrs, FieldAttrs::is_exposed);
+/// ```
+pub fn retain_fields(
+    struct_ast: &mut syn::ItemStruct,
+    field_attrs: &[FieldAttrs],
+    keep: fn(&FieldAttrs) -> bool,
+) {
+    if let syn::Fields::Named(fields) = &mut struct_ast.fields {
+        let mut field_attrs = field_attrs.iter();
+        fields.named = std::mem::take(&mut fields.named)
+            .into_iter()
+            .filter(|_| field_attrs.next().is_none_or(keep))
+            .collect();
+    }
+}
+
+/// Skips the serialization of all fields that are not exposed in responses
+pub fn skip_unexposed_fields(struct_ast: &mut syn::ItemStruct, field_attrs: &[FieldAttrs]) {
+    for (field, field_attrs) in struct_ast.fields.iter_mut().zip(field_attrs) {
+        if !field_attrs.is_exposed() {
+            field
+                .attrs
+                .push(parse_quote! { #[serde(skip_serializing)] });
+        }
+    }
+}
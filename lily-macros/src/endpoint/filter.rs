@@ -0,0 +1,105 @@
+use crate::StructNames;
+use crate::util::{is_collection, option_inner_type};
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+
+pub fn generate_filter(
+    struct_ast: &syn::ItemStruct,
+    struct_names: &StructNames,
+) -> proc_macro2::TokenStream {
+    let original_struct_name: &syn::Ident = &struct_names.original;
+    let filter_name: &syn::Ident = &struct_names.filter_name;
+
+    // Collections can not be compared to a single value, so they are not filterable
+    let filterable_fields: Vec<&syn::Field> = struct_ast
+        .fields
+        .iter()
+        .filter(|field| !is_collection(option_inner_type(&field.ty).unwrap_or(&field.ty)))
+        .collect();
+
+    let mut filter_fields: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut field_names: Vec<String> = Vec::new();
+    let mut add_condition_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut is_empty_checks: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut matches_checks: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut conditions: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut value_checks: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    for field in filterable_fields {
+        let name = &field.ident;
+        let name_string = name
+            .as_ref()
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+
+        // Conditions on optional fields compare against the inner value
+        let inner_ty = option_inner_type(&field.ty);
+        let nullable = inner_ty.is_some();
+        let value_ty = inner_ty.unwrap_or(&field.ty);
+        let value_tokens = if nullable {
+            quote! { item.#name.as_ref() }
+        } else {
+            quote! { Some(&item.#name) }
+        };
+
+        // The check points at the type of a field that can not be filtered
+        value_checks.push(quote_spanned! {value_ty.span()=>
+            const _: fn() = || {
+                fn assert_filter_value<T: ::lily::FilterValue>() {}
+                assert_filter_value::<#value_ty>();
+            };
+        });
+        filter_fields.push(quote! { pub #name: Vec<::lily::Condition<#value_ty>> });
+        add_condition_arms.push(quote! {
+            #name_string => self.#name.push(::lily::Condition::parse(field, operator, value, #nullable)?)
+        });
+        is_empty_checks.push(quote! { self.#name.is_empty() });
+        matches_checks.push(quote! {
+            self.#name.iter().all(|condition| condition.matches(#value_tokens))
+        });
+        conditions.push(quote! {
+            self.#name.iter().map(|condition| (#name_string, condition.to_value()))
+        });
+        field_names.push(name_string);
+    }
+
+    quote! {
+        #(#value_checks)*
+
+        #[derive(Clone, Debug, Default)]
+        pub struct #filter_name {
+            #(#filter_fields),*
+        }
+
+        impl ::lily::Filter<#original_struct_name> for #filter_name {
+            const FIELDS: &'static [&'static str] = &[#(#field_names),*];
+
+            fn add_condition(&mut self, field: &str, operator: ::lily::Operator, value: &str) -> Result<(), ::lily::FilterError> {
+                match field {
+                    #(#add_condition_arms,)*
+                    _ => {
+                        return Err(::lily::FilterError {
+                            parameter: field.to_owned(),
+                            detail: format!("Unknown field '{}'.", field),
+                        });
+                    }
+                }
+                Ok(())
+            }
+
+            fn is_empty(&self) -> bool {
+                true #(&& #is_empty_checks)*
+            }
+
+            fn matches(&self, item: &#original_struct_name) -> bool {
+                true #(&& #matches_checks)*
+            }
+
+            fn conditions(&self) -> Vec<(&'static str, ::lily::Condition<serde_json::Value>)> {
+                std::iter::empty()
+                    #(.chain(#conditions))*
+                    .collect()
+            }
+        }
+    }
+}
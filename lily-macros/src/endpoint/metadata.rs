@@ -0,0 +1,133 @@
+use crate::endpoint::EndpointArgs;
+use crate::endpoint::field_attrs::{FieldAttrs, MetadataField};
+use quote::quote;
+use syn::parse::Parser;
+
+/// The metadata fields of an endpoint, either declared by the user or injected by the macro
+///
+/// A field is a metadata field if it is marked with `#[lily(id)]`, `#[lily(created_at)]` or
+/// `#[lily(updated_at)]`, or if it is unmarked but has the conventional name of a role that no
+/// other field is marked with. Only the id and `created_at` are injected if they are missing,
+/// the latter unless `#[endpoint(no_timestamps)]` is given.
+pub struct Metadata {
+    pub id_field: syn::Ident,
+    pub id_type: syn::Type,
+    inject_id: bool,
+    inject_created_at: bool,
+}
+
+impl Metadata {
+    /// Finds the metadata fields of a struct, marking fields with a conventional name
+    pub fn resolve(
+        struct_ast: &syn::ItemStruct,
+        field_attrs: &mut [FieldAttrs],
+        args: &EndpointArgs,
+    ) -> syn::Result<Self> {
+        let roles = [
+            MetadataField::Id,
+            MetadataField::CreatedAt,
+            MetadataField::UpdatedAt,
+        ];
+
+        // Every role may only be taken by one field
+        for role in roles {
+            let mut marked = struct_ast
+                .fields
+                .iter()
+                .zip(field_attrs.iter())
+                .filter(|(_, field_attrs)| field_attrs.metadata == Some(role))
+                .map(|(field, _)| field);
+            if let (Some(_), Some(duplicate)) = (marked.next(), marked.next()) {
+                return Err(syn::Error::new_spanned(
+                    &duplicate.ident,
+                    format!("only one field may be marked as `{}`", role.name()),
+                ));
+            }
+        }
+
+        // Unmarked fields with a conventional name take the role, unless another field has it
+        for role in roles {
+            if field_attrs
+                .iter()
+                .all(|field_attrs| field_attrs.metadata != Some(role))
+                && let Some(field_attrs) = struct_ast
+                    .fields
+                    .iter()
+                    .zip(field_attrs.iter_mut())
+                    .find(|(field, field_attrs)| {
+                        field_attrs.metadata.is_none()
+                            && field.ident.as_ref().is_some_and(|name| name == role.name())
+                    })
+                    .map(|(_, field_attrs)| field_attrs)
+            {
+                field_attrs.metadata = Some(role);
+            }
+        }
+
+        let field_with_role = |role: MetadataField| {
+            struct_ast
+                .fields
+                .iter()
+                .zip(field_attrs.iter())
+                .find(|(_, field_attrs)| field_attrs.metadata == Some(role))
+                .map(|(field, _)| field)
+        };
+
+        let (id_field, id_type, inject_id) = match field_with_role(MetadataField::Id) {
+            Some(field) => {
+                if let Some(id_type) = &args.id_type {
+                    return Err(syn::Error::new_spanned(
+                        id_type,
+                        "the id type is already given by the field marked as `id`",
+                    ));
+                }
+                let name = field.ident.clone().expect("Named fields have an ident");
+                (name, field.ty.clone(), false)
+            }
+            None => (
+                syn::Ident::new("id", proc_macro2::Span::call_site()),
+                args.id_type
+                    .clone()
+                    .unwrap_or_else(|| syn::parse_quote!(String)),
+                true,
+            ),
+        };
+        let inject_created_at =
+            args.timestamps && field_with_role(MetadataField::CreatedAt).is_none();
+
+        Ok(Metadata {
+            id_field,
+            id_type,
+            inject_id,
+            inject_created_at,
+        })
+    }
+
+    /// The metadata fields `id` and `created_at` injected by the macro, with their role
+    pub fn missing_fields(&self) -> Vec<(syn::Field, MetadataField)> {
+        let mut missing_fields = Vec::new();
+        if self.inject_id {
+            let id_type = &self.id_type;
+            let id_field: syn::Field = syn::Field::parse_named
+                .parse2(quote! { id: #id_type })
+                .expect("Failed to parse named field");
+            missing_fields.push((id_field, MetadataField::Id));
+        }
+        if self.inject_created_at {
+            let created_at_field = syn::Field::parse_named
+                .parse_str("created_at: chrono::DateTime<chrono::Utc>")
+                .expect("Failed to parse created_at field");
+            missing_fields.push((created_at_field, MetadataField::CreatedAt));
+        }
+        missing_fields
+    }
+
+    /// Adds the missing metadata fields `id` and `created_at` to the beginning of the struct
+    pub fn add_missing_fields(&self, struct_ast: &mut syn::ItemStruct) {
+        if let syn::Fields::Named(fields) = &mut struct_ast.fields {
+            for (position, (field, _)) in self.missing_fields().into_iter().enumerate() {
+                fields.named.insert(position, field);
+            }
+        }
+    }
+}
@@ -1,79 +1,204 @@
-use crate::{StructNames, util::is_option};
+use crate::StructNames;
+use crate::endpoint::field_attrs::{FieldAttrs, skip_unexposed_fields};
+use crate::endpoint::metadata::Metadata;
+use crate::endpoint::relations::generate_references;
+use crate::endpoint::validate::generate_validate;
+use crate::endpoint::{EndpointArgs, PayloadOptions};
+use crate::util::{derived_paths, merge_derives};
+use crate::util::{is_option, option_inner_type};
 use quote::quote;
-use syn::{Fields, parse::Parser, parse_quote};
+use syn::parse_quote;
 
 pub fn generate_payload(
     mut struct_ast: syn::ItemStruct,
     struct_names: &StructNames,
+    args: &EndpointArgs,
+    metadata: &Metadata,
+    field_attrs: &[FieldAttrs],
 ) -> proc_macro2::TokenStream {
-    // let original_struct_name: &syn::Ident = &struct_names.original;
     let create_payload_name: &syn::Ident = &struct_names.create_payload_name;
     let update_payload_name: &syn::Ident = &struct_names.update_payload_name;
+    let replace_payload_name: &syn::Ident = &struct_names.replace_payload_name;
     // let snake_name: &String = &struct_names.snake_case;
 
-    // Get all struct fields
-    let original_fields = if let syn::Fields::Named(fields) = &struct_ast.fields {
-        &fields.named
-    } else {
-        panic!("This macro only works on structs with named fields");
-    };
+    // Get all struct fields, which are named as checked by the endpoint macro
+    let original_fields = &struct_ast.fields;
+
+    // Read-only and hidden fields are never accepted, create-only fields only on creation
+    let create_fields_with_attrs: Vec<(&syn::Field, &FieldAttrs)> = original_fields
+        .iter()
+        .zip(field_attrs)
+        .filter(|(_, field_attrs)| field_attrs.in_create_payload())
+        .collect();
+    let replace_fields_with_attrs: Vec<(&syn::Field, &FieldAttrs)> = original_fields
+        .iter()
+        .zip(field_attrs)
+        .filter(|(_, field_attrs)| field_attrs.in_update_payload())
+        .collect();
+    let create_fields = create_fields_with_attrs.iter().map(|(field, _)| field);
+    let replace_fields = replace_fields_with_attrs.iter().map(|(field, _)| field);
 
     // Derive set of optional fields from original struct fields
-    let optional_fields: Vec<proc_macro2::TokenStream> = original_fields
+    // Optional fields become a Patch, so that an explicit null can be told apart from an absent field
+    let optional_fields: Vec<proc_macro2::TokenStream> = replace_fields_with_attrs
         .iter()
-        .map(|field| {
+        .map(|(field, _)| {
             let name = &field.ident;
             let ty = &field.ty;
             let attrs = &field.attrs;
 
-            if is_option(ty) {
-                quote! { #(#attrs)* #name: #ty }
+            match option_inner_type(ty) {
+                Some(inner_ty) => {
+                    quote! { #(#attrs)* #[serde(default)] #name: ::lily::Patch<#inner_ty> }
+                }
+                None => quote! { #(#attrs)* #name: Option<#ty> },
+            }
+        })
+        .collect();
+
+    // Create the code merging the update-payload into an existing resource
+    let apply_statements: Vec<proc_macro2::TokenStream> = replace_fields_with_attrs
+        .iter()
+        .map(|(field, _)| {
+            let name = &field.ident;
+            if is_option(&field.ty) {
+                quote! { self.#name.apply_to(&mut target.#name); }
             } else {
-                quote! { #(#attrs)* #name: Option<#ty> }
+                quote! {
+                    if let Some(value) = &self.#name {
+                        target.#name = value.clone();
+                    }
+                }
             }
         })
         .collect();
+    let original_struct_name: &syn::Ident = &struct_names.original;
+    let apply_tokens: proc_macro2::TokenStream = quote! {
+        impl #update_payload_name {
+            /// Applies every field that was sent to an existing resource, keeping all others
+            #[allow(dead_code)]
+            pub fn apply_to(&self, target: &mut #original_struct_name) {
+                #(#apply_statements)*
+            }
+        }
+    };
+
+    // Payloads derive at least Clone, Debug and Deserialize, in addition to the configured derives
+    let create_payload_attrs = payload_attrs(&args.create_payload);
+    let update_payload_attrs = payload_attrs(&args.update_payload);
+    let replace_payload_attrs = payload_attrs(&args.replace_payload);
 
     // Create the code for the create-payload (POST) struct
+    // Payloads are generated for all actions, so their fields may be unused if an action is disabled
     let create_payload_tokens: proc_macro2::TokenStream = quote! {
-        #[derive(Clone, Debug, serde::Deserialize)]
+        #create_payload_attrs
+        #[allow(dead_code)]
         pub struct #create_payload_name {
-            #original_fields
+            #(#create_fields),*
         }
     };
 
     // Create the code for the update-payload (PATCH) struct
     let update_payload_tokens: proc_macro2::TokenStream = quote! {
-        #[derive(Clone, Debug, serde::Deserialize)]
+        #update_payload_attrs
+        #[allow(dead_code)]
         pub struct #update_payload_name {
             #(#optional_fields),*
         }
     };
 
-    // Add metadata to the original struct
-    // TODO: Check if this makes sense here, maybe in a later step move it to persistence module
-    if let Fields::Named(fields) = &mut struct_ast.fields {
-        let id_field: syn::Field = syn::Field::parse_named
-            .parse_str("id: String")
-            .expect("Failed to parse named field");
-        fields.named.insert(0, id_field);
-
-        let created_at_field = syn::Field::parse_named
-            .parse_str("created_at: chrono::DateTime<chrono::Utc>")
-            .expect("Failed to parse created_at field");
-        fields.named.insert(1, created_at_field);
-    }
-
-    // Add derive attributes to the original struct
-    let derives: syn::Attribute = parse_quote! {
-        #[derive(Clone, Debug, serde::Serialize)]
+    // Create the code for the replace-payload (PUT) struct
+    // All non-optional fields are required, as the payload replaces the whole resource
+    let replace_payload_tokens: proc_macro2::TokenStream = quote! {
+        #replace_payload_attrs
+        #[allow(dead_code)]
+        pub struct #replace_payload_name {
+            #(#replace_fields),*
+        }
     };
-    struct_ast.attrs.push(derives);
+
+    // Create the validation of all payloads
+    let validate_tokens: proc_macro2::TokenStream = [
+        generate_validate(create_payload_name, &create_fields_with_attrs, false),
+        generate_validate(update_payload_name, &replace_fields_with_attrs, true),
+        generate_validate(replace_payload_name, &replace_fields_with_attrs, false),
+    ]
+    .into_iter()
+    .collect();
+
+    // Create the lookup of the referenced resources of all payloads
+    let references_tokens: proc_macro2::TokenStream = [
+        generate_references(
+            original_struct_name,
+            create_payload_name,
+            &create_fields_with_attrs,
+            false,
+        ),
+        generate_references(
+            original_struct_name,
+            update_payload_name,
+            &replace_fields_with_attrs,
+            true,
+        ),
+        generate_references(
+            original_struct_name,
+            replace_payload_name,
+            &replace_fields_with_attrs,
+            false,
+        ),
+    ]
+    .into_iter()
+    .collect();
+
+    // Write-only and hidden fields are never serialized
+    skip_unexposed_fields(&mut struct_ast, field_attrs);
+
+    // Add the missing metadata to the original struct
+    metadata.add_missing_fields(&mut struct_ast);
+
+    // Add the derive attributes the struct does not derive yet
+    // Derives placed above `#[endpoint]` are not passed to the macro, so they can not be detected
+    let missing_derives = merge_derives(
+        &derived_paths(&struct_ast.attrs),
+        [
+            parse_quote!(Clone),
+            parse_quote!(Debug),
+            parse_quote!(serde::Serialize),
+        ],
+    );
+    if !missing_derives.is_empty() {
+        struct_ast
+            .attrs
+            .push(parse_quote! { #[derive(#(#missing_derives),*)] });
+    }
 
     // Return token stream
     quote! {
         #struct_ast
         #create_payload_tokens
         #update_payload_tokens
+        #replace_payload_tokens
+        #validate_tokens
+        #references_tokens
+        #apply_tokens
+    }
+}
+
+/// Creates the derive and custom attributes of a payload
+fn payload_attrs(options: &PayloadOptions) -> proc_macro2::TokenStream {
+    let derives = merge_derives(
+        &[],
+        [
+            parse_quote!(Clone),
+            parse_quote!(Debug),
+            parse_quote!(serde::Deserialize),
+        ]
+        .into_iter()
+        .chain(options.derives.iter().cloned()),
+    );
+    let attrs = &options.attrs;
+    quote! {
+        #[derive(#(#derives),*)]
+        #(#[#attrs])*
     }
 }
@@ -0,0 +1,394 @@
+use crate::endpoint::field_attrs::{FieldAttrs, OnDelete, RelationKind};
+use crate::endpoint::validate::value_access;
+use crate::util::to_pascal_case;
+use quote::{ToTokens, format_ident, quote, quote_spanned};
+use std::collections::HashMap;
+use syn::spanned::Spanned;
+
+/// A field holding ids of another endpoint, declared with `#[lily(belongs_to = ..)]` or
+/// `#[lily(many = ..)]`
+pub struct Relation {
+    pub kind: RelationKind,
+    pub field: syn::Field,
+    pub target: syn::Path,
+    /// The `on_delete` policy, if one is declared
+    pub on_delete: Option<OnDelete>,
+}
+
+impl Relation {
+    pub fn field_name(&self) -> String {
+        self.field
+            .ident
+            .as_ref()
+            .map(|name| name.to_string())
+            .unwrap_or_default()
+    }
+
+    /// The name of the relation
+    ///
+    /// A `belongs_to` relation is named after its field without the `_id` suffix, a many-to-many
+    /// relation after its field.
+    pub fn name(&self) -> String {
+        let field_name = self.field_name();
+        match (self.kind, field_name.strip_suffix("_id")) {
+            (RelationKind::BelongsTo, Some(name)) if !name.is_empty() => name.to_owned(),
+            _ => field_name,
+        }
+    }
+
+    /// The variant of the list scope restricting a list to the resources linked to a resource
+    /// of the target
+    pub fn scope_variant(&self) -> syn::Ident {
+        format_ident!("{}", to_pascal_case(&self.field_name()))
+    }
+}
+
+/// Collects the relations of a struct
+///
+/// Only one many-to-many relation per target is supported, as `ManyToMany` is implemented once
+/// for every target.
+pub fn collect_relations(
+    struct_ast: &syn::ItemStruct,
+    field_attrs: &[FieldAttrs],
+) -> syn::Result<Vec<Relation>> {
+    let relations: Vec<Relation> = struct_ast
+        .fields
+        .iter()
+        .zip(field_attrs)
+        .filter_map(|(field, field_attrs)| {
+            let (kind, target) = field_attrs.relation.clone()?;
+            Some(Relation {
+                kind,
+                field: field.clone(),
+                target,
+                on_delete: field_attrs.on_delete,
+            })
+        })
+        .collect();
+
+    let many_relations: Vec<&Relation> = relations
+        .iter()
+        .filter(|relation| relation.kind == RelationKind::ManyToMany)
+        .collect();
+    let target_names: Vec<String> = many_relations
+        .iter()
+        .map(|relation| relation.target.to_token_stream().to_string())
+        .collect();
+    for (index, relation) in many_relations.iter().enumerate() {
+        if target_names[..index].contains(&target_names[index]) {
+            return Err(syn::Error::new_spanned(
+                &relation.target,
+                "only one `many` relation to the same endpoint is supported",
+            ));
+        }
+    }
+    Ok(relations)
+}
+
+/// Checks that the actions needed by the declared `on_delete` policies are enabled
+///
+/// The referencing resources are found with `read_multiple`, then deleted with `delete_single`
+/// or cleared with `update_single`.
+pub fn check_on_delete(
+    relations: &[Relation],
+    enabled_actions: &HashMap<String, proc_macro2::Span>,
+) -> syn::Result<()> {
+    for relation in relations {
+        let Some(on_delete) = relation.on_delete else {
+            continue;
+        };
+        let required_actions: &[&str] = match on_delete {
+            OnDelete::Restrict => &["read_multiple"],
+            OnDelete::Cascade => &["read_multiple", "delete_single"],
+            OnDelete::SetNull => &["read_multiple", "update_single"],
+        };
+        if let Some(action) = required_actions
+            .iter()
+            .find(|action| !enabled_actions.contains_key(**action))
+        {
+            return Err(syn::Error::new_spanned(
+                &relation.field,
+                format!(
+                    "`on_delete = {}` requires the `{}` action",
+                    on_delete.name(),
+                    action
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Generates the value of `Endpoint::RELATIONS`
+pub fn generate_relations(relations: &[Relation]) -> proc_macro2::TokenStream {
+    let relation_tokens = relations.iter().map(relation_tokens);
+    quote! { &[#(#relation_tokens),*] }
+}
+
+/// Generates the runtime description of a relation
+fn relation_tokens(relation: &Relation) -> proc_macro2::TokenStream {
+    let kind = match relation.kind {
+        RelationKind::BelongsTo => quote! { BelongsTo },
+        RelationKind::ManyToMany => quote! { ManyToMany },
+    };
+    let on_delete = match relation.on_delete.unwrap_or(OnDelete::Restrict) {
+        OnDelete::Restrict => quote! { Restrict },
+        OnDelete::Cascade => quote! { Cascade },
+        OnDelete::SetNull => quote! { SetNull },
+    };
+    let name = relation.name();
+    let field_name = relation.field_name();
+    let target = &relation.target;
+    quote! {
+        ::lily::Relation {
+            kind: ::lily::RelationKind::#kind,
+            on_delete: ::lily::OnDelete::#on_delete,
+            name: #name,
+            field: #field_name,
+            target: <#target as ::lily::Endpoint>::get_name,
+        }
+    }
+}
+
+/// Generates the `DependentOf` implementations handing the `belongs_to` relations to the endpoints
+/// they reference, one per referenced endpoint
+///
+/// Every relation requires the referenced endpoint to list the struct in `referenced_by`; the check
+/// points at the target of the relation. The referencing resources are found through the list
+/// route, so the relations of an endpoint without `read_multiple` are not handed over.
+pub fn generate_dependents(
+    struct_name: &syn::Ident,
+    relations: &[Relation],
+    enabled_actions: &HashMap<String, proc_macro2::Span>,
+) -> proc_macro2::TokenStream {
+    if !enabled_actions.contains_key("read_multiple") {
+        return proc_macro2::TokenStream::new();
+    }
+    let mut targets: Vec<(String, &syn::Path, Vec<proc_macro2::TokenStream>)> = Vec::new();
+    for relation in relations
+        .iter()
+        .filter(|relation| relation.kind == RelationKind::BelongsTo)
+    {
+        let field_name = relation.field_name();
+        let relation_tokens = relation_tokens(relation);
+        let target = &relation.target;
+        let resolve_tokens = match relation.on_delete.unwrap_or(OnDelete::Restrict) {
+            OnDelete::Restrict => quote! { ::lily::resolve_nothing(state, context, id) },
+            OnDelete::Cascade => {
+                quote! { Box::pin(::lily::delete_dependent::<#struct_name>(state, context, id)) }
+            }
+            OnDelete::SetNull => {
+                quote! { Box::pin(::lily::nullify_dependent::<#struct_name>(state, context, #field_name, id)) }
+            }
+        };
+        let dependent_tokens = quote! {
+            {
+                fn dependents() -> &'static [&'static [::lily::Dependent]] {
+                    <#struct_name as ::lily::Endpoint>::DEPENDENTS
+                }
+                fn find<'a>(state: &'a ::lily::AnyState, context: &'a ::lily::Context, id: &'a str) -> ::lily::DependentFuture<'a, Vec<String>> {
+                    Box::pin(::lily::find_dependents::<#struct_name>(state, context, #field_name, id))
+                }
+                fn resolve<'a>(state: &'a ::lily::AnyState, context: &'a ::lily::Context, id: &'a str) -> ::lily::DependentFuture<'a, ()> {
+                    #resolve_tokens
+                }
+                ::lily::Dependent {
+                    source: <#struct_name as ::lily::Endpoint>::get_name,
+                    dependents,
+                    relation: #relation_tokens,
+                    state: ::lily::state_from::<#struct_name, #target>,
+                    find,
+                    resolve,
+                }
+            }
+        };
+        let key = target.to_token_stream().to_string();
+        match targets.iter_mut().find(|(name, _, _)| *name == key) {
+            Some((_, _, dependents)) => dependents.push(dependent_tokens),
+            None => targets.push((key, target, vec![dependent_tokens])),
+        }
+    }
+
+    let impl_tokens = targets.iter().map(|(_, target, dependents)| {
+        let check_struct_name = syn::Ident::new(&struct_name.to_string(), target.span());
+        let check_tokens = quote_spanned! {target.span()=>
+            const _: fn() = || {
+                fn assert_referenced_by<T: ::lily::ReferencedBy<S>, S: ::lily::Endpoint>() {}
+                assert_referenced_by::<#target, #check_struct_name>();
+            };
+        };
+        quote! {
+            impl ::lily::DependentOf<#target> for #struct_name {
+                const DEPENDENTS: &'static [::lily::Dependent] = &[#(#dependents),*];
+            }
+            #check_tokens
+        }
+    });
+    quote! { #(#impl_tokens)* }
+}
+
+/// Generates the value of `Endpoint::DEPENDENTS` and the `ReferencedBy` implementations of the
+/// endpoints listed in `referenced_by`
+///
+/// A listed endpoint without a `belongs_to` relation to the struct is reported on its path.
+pub fn generate_referenced_by(
+    struct_name: &syn::Ident,
+    referenced_by: &[syn::Path],
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let dependents = referenced_by.iter().map(|source| {
+        let struct_name = syn::Ident::new(&struct_name.to_string(), source.span());
+        quote_spanned! {source.span()=> <#source as ::lily::DependentOf<#struct_name>>::DEPENDENTS }
+    });
+    let impl_tokens = referenced_by.iter().map(|source| {
+        quote! { impl ::lily::ReferencedBy<#source> for #struct_name {} }
+    });
+    (quote! { &[#(#dependents),*] }, quote! { #(#impl_tokens)* })
+}
+
+/// Generates the implementation of the `References` trait for a payload
+///
+/// Every present id of a relation is looked up with `ReadSingle` of the referenced endpoint, with
+/// its state taken from the state of the endpoint, unless the lookups of the request already know
+/// it.
+pub fn generate_references(
+    struct_name: &syn::Ident,
+    payload_name: &syn::Ident,
+    fields: &[(&syn::Field, &FieldAttrs)],
+    is_update: bool,
+) -> proc_macro2::TokenStream {
+    let reference_checks = fields.iter().filter_map(|(field, field_attrs)| {
+        let (kind, target) = field_attrs.relation.as_ref()?;
+        let pointer = format!("/{}", field.ident.as_ref()?);
+        let value_tokens = value_access(field, is_update);
+        // A mismatch between the field type and the id type is reported on the field type
+        let read_tokens = quote_spanned! {field.ty.span()=>
+            <#target as ::lily::ReadSingle>::read_single(&target_state, context, id)
+        };
+        // A missing conversion between the states is reported on the target
+        let state_tokens = quote_spanned! {target.span()=>
+            let target_state: <#target as ::lily::Endpoint>::State = axum::extract::FromRef::from_ref(state);
+        };
+        let missing_check = |pointer_tokens: proc_macro2::TokenStream| {
+            quote! {
+                let lookup = async { Ok(#read_tokens.await?.is_some()) };
+                if !lookups.exists(&<#target as ::lily::Endpoint>::get_name(), id.to_string(), lookup).await? {
+                    missing.push(::lily::ValidationError::new(
+                        #pointer_tokens,
+                        format!("the {} '{}' does not exist", <#target as ::lily::Endpoint>::get_name(), id),
+                    ));
+                }
+            }
+        };
+        Some(match kind {
+            RelationKind::BelongsTo => {
+                let check_tokens = missing_check(quote! { #pointer });
+                quote! {
+                    if let Some(id) = #value_tokens {
+                        #state_tokens
+                        #check_tokens
+                    }
+                }
+            }
+            RelationKind::ManyToMany => {
+                let check_tokens = missing_check(quote! { format!("{}/{}", #pointer, index) });
+                quote! {
+                    if let Some(ids) = #value_tokens {
+                        #state_tokens
+                        for (index, id) in ids.iter().enumerate() {
+                            #check_tokens
+                        }
+                    }
+                }
+            }
+        })
+    });
+
+    quote! {
+        impl ::lily::References<<#struct_name as ::lily::Endpoint>::State> for #payload_name {
+            #[allow(unused_variables)]
+            async fn missing_references(&self, state: &<#struct_name as ::lily::Endpoint>::State, context: &::lily::Context, lookups: &::lily::ReferenceLookups) -> Result<Vec<::lily::ValidationError>, ::lily::Error> {
+                #[allow(unused_mut)]
+                let mut missing: Vec<::lily::ValidationError> = Vec::new();
+                #(#reference_checks)*
+                Ok(missing)
+            }
+        }
+    }
+}
+
+/// Generates the implementation of the `Includes` trait for an endpoint
+///
+/// Every relation is checked and resolved through the `Includes` implementation of its target,
+/// which handles the nested relations. Many-to-many relations are included as arrays.
+pub fn generate_includes(
+    struct_name: &syn::Ident,
+    relations: &[Relation],
+) -> proc_macro2::TokenStream {
+    // Without relations, every requested name is unknown
+    if relations.is_empty() {
+        return quote! {
+            impl ::lily::Includes for #struct_name {
+                fn check_includes(includes: &::lily::IncludeTree) -> Result<(), ::lily::Problem> {
+                    match includes.iter().next() {
+                        Some((name, _)) => Err(::lily::unknown_include::<Self>(name)),
+                        None => Ok(()),
+                    }
+                }
+
+                fn resolve_include<'a>(&'a self, _: &'a Self::State, _: &'a ::lily::Context, name: &'a str, _: &'a ::lily::IncludeTree) -> ::lily::IncludeFuture<'a> {
+                    Box::pin(async move { Err(::lily::unknown_include::<Self>(name)) })
+                }
+            }
+        };
+    }
+
+    let names: Vec<String> = relations.iter().map(Relation::name).collect();
+    let targets: Vec<&syn::Path> = relations.iter().map(|relation| &relation.target).collect();
+    let resolve_tokens = relations.iter().map(|relation| {
+        let target = &relation.target;
+        let value_tokens = value_access(&relation.field, false);
+        match relation.kind {
+            RelationKind::BelongsTo => quote! {
+                match #value_tokens {
+                    Some(id) => ::lily::include_single::<#target, _>(state, context, id, includes).await,
+                    None => Ok(serde_json::Value::Null),
+                }
+            },
+            RelationKind::ManyToMany => quote! {
+                match #value_tokens {
+                    Some(ids) => {
+                        let mut values = Vec::new();
+                        for id in ids.iter() {
+                            values.push(::lily::include_single::<#target, _>(state, context, id, includes).await?);
+                        }
+                        Ok(serde_json::Value::Array(values))
+                    }
+                    None => Ok(serde_json::Value::Null),
+                }
+            },
+        }
+    });
+
+    quote! {
+        impl ::lily::Includes for #struct_name {
+            fn check_includes(includes: &::lily::IncludeTree) -> Result<(), ::lily::Problem> {
+                for (name, nested) in includes.iter() {
+                    match name.as_str() {
+                        #(#names => <#targets as ::lily::Includes>::check_includes(nested)?,)*
+                        _ => return Err(::lily::unknown_include::<Self>(name)),
+                    }
+                }
+                Ok(())
+            }
+
+            fn resolve_include<'a>(&'a self, state: &'a Self::State, context: &'a ::lily::Context, name: &'a str, includes: &'a ::lily::IncludeTree) -> ::lily::IncludeFuture<'a> {
+                Box::pin(async move {
+                    match name {
+                        #(#names => #resolve_tokens,)*
+                        _ => Err(::lily::unknown_include::<Self>(name)),
+                    }
+                })
+            }
+        }
+    }
+}
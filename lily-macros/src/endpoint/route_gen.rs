@@ -1,9 +1,12 @@
 use crate::StructNames;
-use crate::util::{to_kebab_case, to_snake_case};
+use crate::endpoint::field_attrs::RelationKind;
+use crate::endpoint::relations::Relation;
+use crate::endpoint::{EndpointArgs, PaginationMode};
+use crate::util::to_snake_case;
 use proc_macro2::TokenStream;
-use quote::quote;
-use std::collections::HashSet;
-use strum::{Display, EnumIter};
+use quote::{format_ident, quote, quote_spanned};
+use strum::{Display, EnumIter, IntoEnumIterator};
+use syn::spanned::Spanned;
 
 #[derive(Debug, Display, EnumIter)]
 pub enum Routes {
@@ -20,10 +23,6 @@ pub enum Routes {
 }
 
 impl Routes {
-    pub fn get_path(&self) -> String {
-        let variant = self.to_string();
-        to_kebab_case(&variant)
-    }
     pub fn as_snake_case(&self) -> String {
         let variant = self.to_string();
         to_snake_case(&variant)
@@ -32,146 +31,1004 @@ impl Routes {
 
 pub fn get_route_builder(
     struct_names: &StructNames,
-    enabled_actions: &HashSet<String>,
+    args: &EndpointArgs,
+    relations: &[Relation],
 ) -> TokenStream {
+    let enabled_actions = &args.enabled_actions;
     let original_struct_name = &struct_names.original;
     let snake_name = &struct_names.snake_case;
 
     // MARK: Create Single
-    let add_create_single_route_tokens: TokenStream = if enabled_actions.contains("create_single") {
+    let add_create_single_route_tokens: TokenStream = if enabled_actions
+        .contains_key("create_single")
+    {
         quote! {
-            async fn create_single_handler(axum::Json(payload): axum::Json<<#original_struct_name as Endpoint>::CreatePayload>) -> ApiResponse<#original_struct_name> {
-                let result = #original_struct_name::create_single(&payload).await;
+            async fn create_single_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::Json(payload): axum::Json<<#original_struct_name as ::lily::Endpoint>::CreatePayload>) -> ::lily::ApiResponse<#original_struct_name> {
+                if let Err(errors) = ::lily::Validate::validate(&payload) {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::ValidationFailed { errors });
+                }
+                if let Err(problem) = ::lily::check_references(&state, &context, &payload).await {
+                    return ::lily::ApiResponse::Erroneous(problem);
+                }
+                let result = <#original_struct_name as ::lily::CreateSingle>::create_single(&state, &context, &payload).await;
 
                 match result {
-                    Ok(data) => ApiResponse::Ok(data),
+                    Ok(data) => ::lily::ApiResponse::Ok(data),
                     Err(error_msg) => {
                         eprintln!(concat!("Error creating single [", #snake_name, "]: {}"), error_msg);
-                        ApiResponse::Erroneous::<#original_struct_name>(Problem::InternalError)
+                        ::lily::ApiResponse::Erroneous::<#original_struct_name>(::lily::Problem::InternalError)
+                    }
+                }
+            }
+
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_path(), axum::routing::post(create_single_handler))
+        }
+    } else {
+        return_router_code()
+    };
+
+    // MARK: Create Multiple
+    let add_create_multiple_route_tokens: TokenStream = if enabled_actions
+        .contains_key("create_multiple")
+    {
+        quote! {
+            async fn create_multiple_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::RawQuery(raw_query): axum::extract::RawQuery, axum::Json(payloads): axum::Json<Vec<<#original_struct_name as ::lily::Endpoint>::CreatePayload>>) -> ::lily::ApiResponse<::lily::BulkReport<#original_struct_name>> {
+                let query: ::lily::BulkQuery = match ::lily::parse_query(raw_query.as_deref()) {
+                    Ok(query) => query,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let max = <#original_struct_name as ::lily::CreateMultiple>::MAX_BULK_SIZE;
+                if payloads.len() > max {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::BulkTooLarge { max });
+                }
+                // Invalid items and items with missing references fail the whole request in atomic
+                // mode and are reported on their own otherwise
+                let mut rejections = ::lily::BulkRejections::new(payloads.len());
+                let payloads = match query.mode() {
+                    ::lily::BulkMode::Atomic => {
+                        if let Err(errors) = ::lily::validate_bulk(&payloads) {
+                            return ::lily::ApiResponse::Erroneous(::lily::Problem::ValidationFailed { errors });
+                        }
+                        if let Err(problem) = ::lily::check_references_bulk(&state, &context, &payloads).await {
+                            return ::lily::ApiResponse::Erroneous(problem);
+                        }
+                        payloads
+                    }
+                    ::lily::BulkMode::Partial => {
+                        let problems = ::lily::validate_each(&payloads);
+                        let payloads = rejections.reject(payloads, problems);
+                        match ::lily::check_references_each(&state, &context, &payloads).await {
+                            Ok(problems) => rejections.reject(payloads, problems),
+                            Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                        }
+                    }
+                };
+                let result = <#original_struct_name as ::lily::CreateMultiple>::create_multiple(&state, &context, &payloads, query.mode()).await;
+
+                match result {
+                    Ok(results) if results.len() == payloads.len() => {
+                        let results = results
+                            .into_iter()
+                            .map(|result| result.map_err(|error| match error {
+                                ::lily::BulkItemError::Failed(error_msg) => {
+                                    eprintln!(concat!("Error creating multiple [", #snake_name, "]: {}"), error_msg);
+                                    ::lily::Problem::InternalError
+                                }
+                                error => error.into(),
+                            }))
+                            .collect();
+                        let report = ::lily::BulkReport::from_results(rejections.merge(results), axum::http::StatusCode::CREATED, query.mode());
+                        if report.is_success() {
+                            ::lily::ApiResponse::Created(report)
+                        } else {
+                            ::lily::ApiResponse::MultiStatus(report)
+                        }
+                    }
+                    Ok(results) => {
+                        eprintln!(concat!("Error creating multiple [", #snake_name, "]: expected {} results, got {}"), payloads.len(), results.len());
+                        ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError)
+                    }
+                    Err(error_msg) => {
+                        eprintln!(concat!("Error creating multiple [", #snake_name, "]: {}"), error_msg);
+                        ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError)
                     }
                 }
             }
 
-            router.route(&#original_struct_name::get_path(), axum::routing::post(create_single_handler))
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_bulk_path(), axum::routing::post(create_multiple_handler))
         }
     } else {
         return_router_code()
     };
 
     // MARK: Read Single
-    let add_read_single_route_tokens: TokenStream = if enabled_actions.contains("read_single") {
+    let add_read_single_route_tokens: TokenStream = if enabled_actions.contains_key("read_single") {
         quote! {
-            async fn read_single_handler(axum::extract::Path(id): axum::extract::Path<<#original_struct_name as Endpoint>::Id>) -> ApiResponse<#original_struct_name> {
-                let result = #original_struct_name::read_single(&id).await;
+            async fn read_single_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::Path(raw_id): axum::extract::Path<String>, axum::extract::RawQuery(raw_query): axum::extract::RawQuery) -> ::lily::ApiResponse<::lily::Included<::lily::Projected<#original_struct_name>>> {
+                let id = match <#original_struct_name as ::lily::Endpoint>::parse_id(&raw_id) {
+                    Ok(id) => id,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let fields = match ::lily::parse_query::<::lily::FieldsQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse(<#original_struct_name as ::lily::Endpoint>::FIELDS))
+                {
+                    Ok(fields) => fields,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let includes = match ::lily::parse_query::<::lily::IncludeQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse::<#original_struct_name>())
+                {
+                    Ok(includes) => includes,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let result = <#original_struct_name as ::lily::ReadSingle>::read_single(&state, &context, &id).await;
 
                 match result {
                     Ok(option) => match option {
-                        Some(data) => ApiResponse::Ok(data),
-                        None => ApiResponse::NotFound(Problem::ResourceNotFound {
-                            resource: #snake_name.to_string(),
-                            id: id,
+                        Some(data) => match ::lily::resolve_includes(&data, &state, &context, &includes).await {
+                            Ok(included) => ::lily::ApiResponse::Ok(::lily::Included::new(fields.project(data), included)),
+                            Err(problem) => ::lily::ApiResponse::Erroneous(problem),
+                        },
+                        None => ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                            resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                            id: id.to_string(),
                         }),
                     },
                     Err(error_msg) => {
                         eprintln!(concat!("Error reading single [", #snake_name, "]: {}"), error_msg);
-                        ApiResponse::Erroneous::<#original_struct_name>(Problem::InternalError)
+                        ::lily::ApiResponse::Erroneous::<::lily::Included<::lily::Projected<#original_struct_name>>>(::lily::Problem::InternalError)
+                    }
+                }
+            }
+
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_path_with_id(), axum::routing::get(read_single_handler))
+        }
+    } else {
+        return_router_code()
+    };
+
+    // MARK: Read Multiple
+    // The list is shared with the nested routes, which restrict it to the resources of a parent
+    let many_relations: Vec<&Relation> = relations
+        .iter()
+        .filter(|relation| relation.kind == RelationKind::ManyToMany)
+        .collect();
+    let scope_variants: Vec<syn::Ident> = many_relations
+        .iter()
+        .map(|relation| relation.scope_variant())
+        .collect();
+    let many_targets: Vec<&syn::Path> = many_relations
+        .iter()
+        .map(|relation| &relation.target)
+        .collect();
+    let scope_tokens: TokenStream = quote! {
+        /// The resources a list is restricted to
+        #[allow(dead_code)]
+        #[derive(Clone, Copy)]
+        enum Scope<'a> {
+            All,
+            /// The resources whose field holds the id
+            BelongsTo(&'static str, &'a str),
+            #(#scope_variants(&'a <#many_targets as ::lily::Endpoint>::Id),)*
+        }
+    };
+    // Linked resources are listed by the implementation of the many-to-many relation
+    let read_scope_tokens: TokenStream = if many_relations.is_empty() {
+        quote! { <#original_struct_name as ::lily::ReadMultiple>::read_multiple(state, context, &list_query).await }
+    } else {
+        quote! {
+            match scope {
+                #(Scope::#scope_variants(related_id) => <#original_struct_name as ::lily::ManyToMany<#many_targets>>::read_linked(state, context, related_id, &list_query).await,)*
+                _ => <#original_struct_name as ::lily::ReadMultiple>::read_multiple(state, context, &list_query).await,
+            }
+        }
+    };
+    let read_multiple_tokens: TokenStream = if args.pagination == PaginationMode::Cursor {
+        quote! {
+            async fn read_multiple(state: &<#original_struct_name as ::lily::Endpoint>::State, context: &::lily::Context, uri: &axum::http::Uri, raw_query: Option<String>, scope: Scope<'_>) -> axum::response::Response {
+                use axum::response::IntoResponse;
+
+                let query: ::lily::CursorQuery = match ::lily::parse_query(raw_query.as_deref()) {
+                    Ok(query) => query,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous::<()>(problem).into_response(),
+                };
+                if query.offset.is_some() {
+                    return ::lily::ApiResponse::Erroneous::<()>(::lily::Problem::InvalidQuery {
+                        detail: "The parameter 'offset' is not supported, use 'cursor' instead.".to_string(),
+                    })
+                    .into_response();
+                }
+                let mut filter = match <<#original_struct_name as ::lily::Endpoint>::Filter as ::lily::Filter<#original_struct_name>>::from_query(raw_query.as_deref()) {
+                    Ok(filter) => filter,
+                    Err(error) => return ::lily::ApiResponse::Erroneous::<()>(error.into()).into_response(),
+                };
+                if let Scope::BelongsTo(field, id) = scope {
+                    if let Err(error) = ::lily::Filter::add_condition(&mut filter, field, ::lily::Operator::Eq, id) {
+                        return ::lily::ApiResponse::Erroneous::<()>(error.into()).into_response();
+                    }
+                }
+                let sort = match ::lily::parse_query::<::lily::SortQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse::<#original_struct_name, <#original_struct_name as ::lily::Endpoint>::SortField>())
+                {
+                    Ok(sort) => sort,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous::<()>(problem).into_response(),
+                };
+                let fields = match ::lily::parse_query::<::lily::FieldsQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse(<#original_struct_name as ::lily::Endpoint>::FIELDS))
+                {
+                    Ok(fields) => fields,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous::<()>(problem).into_response(),
+                };
+                let includes = match ::lily::parse_query::<::lily::IncludeQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse::<#original_struct_name>())
+                {
+                    Ok(includes) => includes,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous::<()>(problem).into_response(),
+                };
+                let cursor = match query.cursor.as_deref().map(::lily::Cursor::decode).transpose() {
+                    Ok(cursor) => cursor,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous::<()>(problem).into_response(),
+                };
+                if let Some(Err(problem)) = cursor.as_ref().map(|cursor| {
+                    cursor.check::<#original_struct_name, _, <#original_struct_name as ::lily::Endpoint>::Id>(&sort)
+                }) {
+                    return ::lily::ApiResponse::Erroneous::<()>(problem).into_response();
+                }
+                let limit = ::lily::Pagination::from_query(
+                    &::lily::PageQuery { limit: query.limit, offset: None },
+                    <#original_struct_name as ::lily::ReadMultiple>::DEFAULT_PAGE_SIZE,
+                    <#original_struct_name as ::lily::ReadMultiple>::MAX_PAGE_SIZE,
+                )
+                .limit;
+                // One additional item is requested to find out whether there is a next page
+                let pagination = ::lily::Pagination { limit: limit + 1, offset: 0, cursor };
+                let list_query = ::lily::ListQuery { pagination, filter, sort };
+                let result = #read_scope_tokens;
+
+                match result {
+                    Ok(page) => {
+                        // The additional item is not part of the page
+                        let included = match ::lily::resolve_includes_all(page.items.iter().take(limit as usize), state, context, &includes).await {
+                            Ok(included) => included,
+                            Err(problem) => return ::lily::ApiResponse::Erroneous::<()>(problem).into_response(),
+                        };
+                        let mut included = included.into_iter();
+                        ::lily::CursorPage::new(page.items, limit, &list_query.sort, uri, |item| {
+                            ::lily::Included::new(fields.project(item), included.next().unwrap_or_default())
+                        })
+                        .into_response()
+                    }
+                    Err(error_msg) => {
+                        eprintln!(concat!("Error reading multiple [", #snake_name, "]: {}"), error_msg);
+                        ::lily::ApiResponse::Erroneous::<()>(::lily::Problem::InternalError).into_response()
                     }
                 }
             }
+        }
+    } else {
+        quote! {
+            async fn read_multiple(state: &<#original_struct_name as ::lily::Endpoint>::State, context: &::lily::Context, raw_query: Option<String>, scope: Scope<'_>) -> ::lily::ApiResponse<::lily::Page<::lily::Included<::lily::Projected<#original_struct_name>>>> {
+                let query: ::lily::PageQuery = match ::lily::parse_query(raw_query.as_deref()) {
+                    Ok(query) => query,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let mut filter = match <<#original_struct_name as ::lily::Endpoint>::Filter as ::lily::Filter<#original_struct_name>>::from_query(raw_query.as_deref()) {
+                    Ok(filter) => filter,
+                    Err(error) => return ::lily::ApiResponse::Erroneous(error.into()),
+                };
+                if let Scope::BelongsTo(field, id) = scope {
+                    if let Err(error) = ::lily::Filter::add_condition(&mut filter, field, ::lily::Operator::Eq, id) {
+                        return ::lily::ApiResponse::Erroneous(error.into());
+                    }
+                }
+                let sort = match ::lily::parse_query::<::lily::SortQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse::<#original_struct_name, <#original_struct_name as ::lily::Endpoint>::SortField>())
+                {
+                    Ok(sort) => sort,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let fields = match ::lily::parse_query::<::lily::FieldsQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse(<#original_struct_name as ::lily::Endpoint>::FIELDS))
+                {
+                    Ok(fields) => fields,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let includes = match ::lily::parse_query::<::lily::IncludeQuery>(raw_query.as_deref())
+                    .and_then(|query| query.parse::<#original_struct_name>())
+                {
+                    Ok(includes) => includes,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let pagination = ::lily::Pagination::from_query(
+                    &query,
+                    <#original_struct_name as ::lily::ReadMultiple>::DEFAULT_PAGE_SIZE,
+                    <#original_struct_name as ::lily::ReadMultiple>::MAX_PAGE_SIZE,
+                );
+                let list_query = ::lily::ListQuery { pagination, filter, sort };
+                let result = #read_scope_tokens;
+
+                match result {
+                    Ok(page) => {
+                        let included = match ::lily::resolve_includes_all(&page.items, state, context, &includes).await {
+                            Ok(included) => included,
+                            Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                        };
+                        let mut included = included.into_iter();
+                        ::lily::ApiResponse::Ok(page.map(|item| {
+                            ::lily::Included::new(fields.project(item), included.next().unwrap_or_default())
+                        }))
+                    }
+                    Err(error_msg) => {
+                        eprintln!(concat!("Error reading multiple [", #snake_name, "]: {}"), error_msg);
+                        ::lily::ApiResponse::Erroneous::<::lily::Page<::lily::Included<::lily::Projected<#original_struct_name>>>>(::lily::Problem::InternalError)
+                    }
+                }
+            }
+        }
+    };
+
+    // The original URI is only needed to build the links of cursor pages
+    let (uri_extractor_tokens, uri_argument_tokens) = match args.pagination {
+        PaginationMode::Cursor => (
+            quote! { axum::extract::OriginalUri(uri): axum::extract::OriginalUri, },
+            quote! { &uri, },
+        ),
+        PaginationMode::Offset => (quote! {}, quote! {}),
+    };
+
+    // Every relation adds a route like `/author/{id}/content`
+    let nested_route_tokens: Vec<TokenStream> = relations
+        .iter()
+        .map(|relation| {
+            let target = &relation.target;
+            let field_name = relation.field_name();
+            let handler_name = format_ident!("read_multiple_by_{}_handler", field_name);
+            let scope = match relation.kind {
+                RelationKind::BelongsTo => quote! { Scope::BelongsTo(#field_name, &raw_parent_id) },
+                RelationKind::ManyToMany => {
+                    let scope_variant = relation.scope_variant();
+                    quote! { Scope::#scope_variant(&parent_id) }
+                }
+            };
+            quote! {
+                async fn #handler_name(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::Path(raw_parent_id): axum::extract::Path<String>, #uri_extractor_tokens axum::extract::RawQuery(raw_query): axum::extract::RawQuery) -> axum::response::Response {
+                    use axum::response::IntoResponse;
+
+                    let parent_id = match <#target as ::lily::Endpoint>::parse_id(&raw_parent_id) {
+                        Ok(id) => id,
+                        Err(problem) => return ::lily::ApiResponse::Erroneous::<()>(problem).into_response(),
+                    };
+                    let parent_state: <#target as ::lily::Endpoint>::State = axum::extract::FromRef::from_ref(&state);
+                    match <#target as ::lily::ReadSingle>::read_single(&parent_state, &context, &parent_id).await {
+                        Ok(Some(_)) => {}
+                        Ok(None) => {
+                            return ::lily::ApiResponse::NotFound::<()>(::lily::Problem::ResourceNotFound {
+                                resource: <#target as ::lily::Endpoint>::get_name(),
+                                id: parent_id.to_string(),
+                            })
+                            .into_response();
+                        }
+                        Err(error_msg) => {
+                            eprintln!(concat!("Error reading parent of multiple [", #snake_name, "]: {}"), error_msg);
+                            return ::lily::ApiResponse::Erroneous::<()>(::lily::Problem::InternalError).into_response();
+                        }
+                    }
+                    read_multiple(&state, &context, #uri_argument_tokens raw_query, #scope).await.into_response()
+                }
+
+                let router = router.route(
+                    &format!("{}/{}", <#target as ::lily::Endpoint>::get_path_with_id(), <#original_struct_name as ::lily::Endpoint>::get_name()),
+                    axum::routing::get(#handler_name),
+                );
+            }
+        })
+        .collect();
+
+    let add_read_multiple_route_tokens: TokenStream = if enabled_actions
+        .contains_key("read_multiple")
+    {
+        let return_type_tokens = match args.pagination {
+            PaginationMode::Cursor => quote! { axum::response::Response },
+            PaginationMode::Offset => {
+                quote! { ::lily::ApiResponse<::lily::Page<::lily::Included<::lily::Projected<#original_struct_name>>>> }
+            }
+        };
+        quote! {
+            #scope_tokens
+
+            #read_multiple_tokens
 
-            router.route(&#original_struct_name::get_path_with_id(), axum::routing::get(read_single_handler))
+            async fn read_multiple_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, #uri_extractor_tokens axum::extract::RawQuery(raw_query): axum::extract::RawQuery) -> #return_type_tokens {
+                read_multiple(&state, &context, #uri_argument_tokens raw_query, Scope::All).await
+            }
+
+            let router = router.route(&<#original_struct_name as ::lily::Endpoint>::get_path(), axum::routing::get(read_multiple_handler));
+            #(#nested_route_tokens)*
+            router
         }
     } else {
         return_router_code()
     };
 
     // MARK: Update Single
-    let add_update_single_route_tokens: TokenStream = if enabled_actions.contains("update_single") {
+    let add_update_single_route_tokens: TokenStream = if enabled_actions
+        .contains_key("update_single")
+    {
+        // Patch documents are applied to the current resource, so they need `read_single`
+        let document_tokens = if enabled_actions.contains_key("read_single") {
+            quote! {
+                ::lily::UpdateBody::Document(patch) => {
+                    let current = match <#original_struct_name as ::lily::ReadSingle>::read_single(&state, &context, &id).await {
+                        Ok(Some(data)) => data,
+                        Ok(None) => {
+                            return ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                                resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                                id: id.to_string(),
+                            });
+                        }
+                        Err(error_msg) => {
+                            eprintln!(concat!("Error reading single [", #snake_name, "] to patch: {}"), error_msg);
+                            return ::lily::ApiResponse::Erroneous(::lily::Problem::InternalError);
+                        }
+                    };
+                    match patch.to_update_payload(&current) {
+                        Ok(payload) => payload,
+                        Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                    }
+                }
+            }
+        } else {
+            quote! {
+                ::lily::UpdateBody::Document(patch) => {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::UnsupportedMediaType {
+                        media_type: patch.media_type().to_owned(),
+                    });
+                }
+            }
+        };
+
         quote! {
-            async fn update_single_handler(axum::extract::Path(id): axum::extract::Path<<#original_struct_name as Endpoint>::Id>, axum::Json(payload): axum::Json<<#original_struct_name as Endpoint>::UpdatePayload>) -> ApiResponse<#original_struct_name> {
-                let result = #original_struct_name::update_single(&id, &payload).await;
+            async fn update_single_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::Path(raw_id): axum::extract::Path<String>, body: ::lily::UpdateBody<#original_struct_name>) -> ::lily::ApiResponse<#original_struct_name> {
+                let id = match <#original_struct_name as ::lily::Endpoint>::parse_id(&raw_id) {
+                    Ok(id) => id,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let payload = match body {
+                    ::lily::UpdateBody::Payload(payload) => payload,
+                    #document_tokens
+                };
+                if let Err(errors) = ::lily::Validate::validate(&payload) {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::ValidationFailed { errors });
+                }
+                if let Err(problem) = ::lily::check_references(&state, &context, &payload).await {
+                    return ::lily::ApiResponse::Erroneous(problem);
+                }
+                let result = <#original_struct_name as ::lily::UpdateSingle>::update_single(&state, &context, &id, &payload).await;
 
                 match result {
-                    Ok(data) => ApiResponse::Ok(data),
+                    Ok(data) => ::lily::ApiResponse::Ok(data),
+                    Err(::lily::Error::NotFound) => ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                        resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                        id: id.to_string(),
+                    }),
                     Err(error_msg) => {
                         eprintln!(concat!("Error updating single [", #snake_name, "]: {}"), error_msg);
-                        ApiResponse::Erroneous::<#original_struct_name>(Problem::InternalError)
+                        ::lily::ApiResponse::Erroneous::<#original_struct_name>(::lily::Problem::InternalError)
+                    }
+                }
+            }
+
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_path_with_id(), axum::routing::patch(update_single_handler))
+        }
+    } else {
+        return_router_code()
+    };
+
+    // MARK: Update Multiple
+    // The resources matched by a filter are counted with `read_multiple` to enforce the bulk limit
+    let filter_selection_tokens = if enabled_actions.contains_key("read_multiple") {
+        quote! {
+            None => {
+                let max = <#original_struct_name as ::lily::UpdateMultiple>::MAX_BULK_SIZE;
+                let count_query: ::lily::ListQuery<#original_struct_name> = ::lily::ListQuery {
+                    pagination: ::lily::Pagination { limit: 1, offset: 0, cursor: None },
+                    filter: filter.clone(),
+                    sort: Vec::new(),
+                };
+                match <#original_struct_name as ::lily::ReadMultiple>::read_multiple(&state, &context, &count_query).await {
+                    Ok(page) if page.total > max as u64 => {
+                        return ::lily::ApiResponse::Erroneous(::lily::Problem::BulkTooLarge { max });
+                    }
+                    Ok(_) => ::lily::Selection::Filter(filter),
+                    Err(error_msg) => {
+                        eprintln!(concat!("Error counting multiple [", #snake_name, "] to update: {}"), error_msg);
+                        return ::lily::ApiResponse::Erroneous(::lily::Problem::InternalError);
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {
+            None => {
+                return ::lily::ApiResponse::Erroneous(::lily::Problem::InvalidQuery {
+                    detail: "Selecting resources with a filter requires the 'read_multiple' action.".to_string(),
+                });
+            }
+        }
+    };
+    let add_update_multiple_route_tokens: TokenStream = if enabled_actions
+        .contains_key("update_multiple")
+    {
+        quote! {
+            async fn update_multiple_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::RawQuery(raw_query): axum::extract::RawQuery, axum::Json(payload): axum::Json<::lily::BulkUpdate<<#original_struct_name as ::lily::Endpoint>::Id, <#original_struct_name as ::lily::Endpoint>::UpdatePayload>>) -> ::lily::ApiResponse<::lily::BulkReport<#original_struct_name>> {
+                let query: ::lily::BulkQuery = match ::lily::parse_query(raw_query.as_deref()) {
+                    Ok(query) => query,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let filter = match <<#original_struct_name as ::lily::Endpoint>::Filter as ::lily::Filter<#original_struct_name>>::from_query(raw_query.as_deref()) {
+                    Ok(filter) => filter,
+                    Err(error) => return ::lily::ApiResponse::Erroneous(error.into()),
+                };
+                if let Err(errors) = ::lily::Validate::validate(&payload) {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::ValidationFailed { errors });
+                }
+                if let Err(problem) = ::lily::check_references(&state, &context, &payload).await {
+                    return ::lily::ApiResponse::Erroneous(problem);
+                }
+                let selection: ::lily::Selection<#original_struct_name> = match payload.ids {
+                    Some(_) if !filter.is_empty() => {
+                        return ::lily::ApiResponse::Erroneous(::lily::Problem::InvalidQuery {
+                            detail: "Either 'ids' or a filter may be given, not both.".to_string(),
+                        });
+                    }
+                    Some(ids) => {
+                        let max = <#original_struct_name as ::lily::UpdateMultiple>::MAX_BULK_SIZE;
+                        if ids.len() > max {
+                            return ::lily::ApiResponse::Erroneous(::lily::Problem::BulkTooLarge { max });
+                        }
+                        ::lily::Selection::Ids(ids)
+                    }
+                    None if filter.is_empty() => {
+                        return ::lily::ApiResponse::Erroneous(::lily::Problem::InvalidQuery {
+                            detail: "Either 'ids' or a filter is required.".to_string(),
+                        });
+                    }
+                    #filter_selection_tokens
+                };
+                let result = <#original_struct_name as ::lily::UpdateMultiple>::update_multiple(&state, &context, &selection, &payload.patch, query.mode()).await;
+
+                let results = match (result, &selection) {
+                    (Ok(results), ::lily::Selection::Ids(ids)) if results.len() == ids.len() => results
+                        .into_iter()
+                        .zip(ids)
+                        .map(|(result, id)| match result {
+                            Ok(Some(data)) => Ok(data),
+                            Ok(None) => Err(::lily::Problem::ResourceNotFound {
+                                resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                                id: id.to_string(),
+                            }),
+                            Err(::lily::BulkItemError::Failed(error_msg)) => {
+                                eprintln!(concat!("Error updating multiple [", #snake_name, "]: {}"), error_msg);
+                                Err(::lily::Problem::InternalError)
+                            }
+                            Err(error) => Err(error.into()),
+                        })
+                        .collect(),
+                    (Ok(results), ::lily::Selection::Ids(ids)) => {
+                        eprintln!(concat!("Error updating multiple [", #snake_name, "]: expected {} results, got {}"), ids.len(), results.len());
+                        return ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError);
+                    }
+                    // Resources matched by a filter are only reported if they still exist
+                    (Ok(results), ::lily::Selection::Filter(_)) => results
+                        .into_iter()
+                        .filter_map(|result| match result {
+                            Ok(data) => data.map(Ok),
+                            Err(::lily::BulkItemError::Failed(error_msg)) => {
+                                eprintln!(concat!("Error updating multiple [", #snake_name, "]: {}"), error_msg);
+                                Some(Err(::lily::Problem::InternalError))
+                            }
+                            Err(error) => Some(Err(error.into())),
+                        })
+                        .collect(),
+                    (Err(error_msg), _) => {
+                        eprintln!(concat!("Error updating multiple [", #snake_name, "]: {}"), error_msg);
+                        return ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError);
                     }
+                };
+                let report = ::lily::BulkReport::from_results(results, axum::http::StatusCode::OK, query.mode());
+                if report.is_success() {
+                    ::lily::ApiResponse::Ok(report)
+                } else {
+                    ::lily::ApiResponse::MultiStatus(report)
                 }
             }
 
-            router.route(&#original_struct_name::get_path_with_id(), axum::routing::patch(update_single_handler))
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_bulk_path(), axum::routing::patch(update_multiple_handler))
         }
     } else {
         return_router_code()
     };
 
     // MARK: Replace Single
-    // let add_replace_single_route_tokens: TokenStream = if enabled_actions.contains("replace_single") {
-    //     quote! {
-    //         async fn replace_single_handler(axum::extract::Path(id): axum::extract::Path<<#original_struct_name as Endpoint>::Id>, axum::Json(payload): axum::Json<<#original_struct_name as Endpoint>::ReplacePayload>) -> ApiResponse<#original_struct_name> {
-    //             let result = #original_struct_name::replace_single(&id, &payload).await;
-
-    //             match result {
-    //                 Ok(data) => ApiResponse::Ok(data),
-    //                 Err(error_msg) => {
-    //                     eprintln!(concat!("Error replacing single [", #snake_name, "]: {}"), error_msg);
-    //                     ApiResponse::Erroneous::<#original_struct_name>(Problem::InternalError)
-    //                 }
-    //             }
-    //         }
-
-    //         router.route(&#original_struct_name::get_path_with_id(), put(replace_single_handler))
-    //     }
-    // } else {
-    //     return_router_code()
-    // };
-
-    // // MARK: Delete Single
-    let add_delete_single_route_tokens: TokenStream = if enabled_actions.contains("delete_single") {
+    let add_replace_single_route_tokens: TokenStream = if enabled_actions
+        .contains_key("replace_single")
+    {
+        quote! {
+            async fn replace_single_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::Path(raw_id): axum::extract::Path<String>, axum::Json(payload): axum::Json<<#original_struct_name as ::lily::Endpoint>::ReplacePayload>) -> ::lily::ApiResponse<#original_struct_name> {
+                let id = match <#original_struct_name as ::lily::Endpoint>::parse_id(&raw_id) {
+                    Ok(id) => id,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                if let Err(errors) = ::lily::Validate::validate(&payload) {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::ValidationFailed { errors });
+                }
+                if let Err(problem) = ::lily::check_references(&state, &context, &payload).await {
+                    return ::lily::ApiResponse::Erroneous(problem);
+                }
+                let result = <#original_struct_name as ::lily::ReplaceSingle>::replace_single(&state, &context, &id, &payload).await;
+
+                match result {
+                    Ok(option) => match option {
+                        Some(::lily::Replacement::Replaced(data)) => ::lily::ApiResponse::Ok(data),
+                        Some(::lily::Replacement::Created(data)) => ::lily::ApiResponse::Created(data),
+                        None => ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                            resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                            id: id.to_string(),
+                        }),
+                    },
+                    Err(error_msg) => {
+                        eprintln!(concat!("Error replacing single [", #snake_name, "]: {}"), error_msg);
+                        ::lily::ApiResponse::Erroneous::<#original_struct_name>(::lily::Problem::InternalError)
+                    }
+                }
+            }
+
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_path_with_id(), axum::routing::put(replace_single_handler))
+        }
+    } else {
+        return_router_code()
+    };
+
+    // MARK: Replace Multiple
+    let add_replace_multiple_route_tokens: TokenStream = if enabled_actions
+        .contains_key("replace_multiple")
+    {
+        quote! {
+            async fn replace_multiple_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::RawQuery(raw_query): axum::extract::RawQuery, axum::Json(items): axum::Json<Vec<::lily::BulkReplaceItem<<#original_struct_name as ::lily::Endpoint>::Id, <#original_struct_name as ::lily::Endpoint>::ReplacePayload>>>) -> ::lily::ApiResponse<::lily::BulkReport<#original_struct_name>> {
+                let query: ::lily::BulkQuery = match ::lily::parse_query(raw_query.as_deref()) {
+                    Ok(query) => query,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let max = <#original_struct_name as ::lily::ReplaceMultiple>::MAX_BULK_SIZE;
+                if items.len() > max {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::BulkTooLarge { max });
+                }
+                let mut rejections = ::lily::BulkRejections::new(items.len());
+                let items = match query.mode() {
+                    ::lily::BulkMode::Atomic => {
+                        if let Err(errors) = ::lily::validate_bulk(&items) {
+                            return ::lily::ApiResponse::Erroneous(::lily::Problem::ValidationFailed { errors });
+                        }
+                        if let Err(problem) = ::lily::check_references_bulk(&state, &context, &items).await {
+                            return ::lily::ApiResponse::Erroneous(problem);
+                        }
+                        items
+                    }
+                    ::lily::BulkMode::Partial => {
+                        let problems = ::lily::validate_each(&items);
+                        let items = rejections.reject(items, problems);
+                        match ::lily::check_references_each(&state, &context, &items).await {
+                            Ok(problems) => rejections.reject(items, problems),
+                            Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                        }
+                    }
+                };
+                let result = <#original_struct_name as ::lily::ReplaceMultiple>::replace_multiple(&state, &context, &items, query.mode()).await;
+
+                match result {
+                    Ok(results) if results.len() == items.len() => {
+                        let results = results
+                            .into_iter()
+                            .zip(items)
+                            .map(|(result, item)| match result {
+                                Ok(Some(::lily::Replacement::Replaced(data))) => Ok((axum::http::StatusCode::OK, data)),
+                                Ok(Some(::lily::Replacement::Created(data))) => Ok((axum::http::StatusCode::CREATED, data)),
+                                Ok(None) => Err(::lily::Problem::ResourceNotFound {
+                                    resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                                    id: item.id.to_string(),
+                                }),
+                                Err(::lily::BulkItemError::Failed(error_msg)) => {
+                                    eprintln!(concat!("Error replacing multiple [", #snake_name, "]: {}"), error_msg);
+                                    Err(::lily::Problem::InternalError)
+                                }
+                                Err(error) => Err(error.into()),
+                            })
+                            .collect();
+                        let report = ::lily::BulkReport::from_statuses(rejections.merge(results), query.mode());
+                        if report.is_success() {
+                            ::lily::ApiResponse::Ok(report)
+                        } else {
+                            ::lily::ApiResponse::MultiStatus(report)
+                        }
+                    }
+                    Ok(results) => {
+                        eprintln!(concat!("Error replacing multiple [", #snake_name, "]: expected {} results, got {}"), items.len(), results.len());
+                        ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError)
+                    }
+                    Err(error_msg) => {
+                        eprintln!(concat!("Error replacing multiple [", #snake_name, "]: {}"), error_msg);
+                        ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError)
+                    }
+                }
+            }
+
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_bulk_path(), axum::routing::put(replace_multiple_handler))
+        }
+    } else {
+        return_router_code()
+    };
+
+    // MARK: Delete Single
+    let add_delete_single_route_tokens: TokenStream = if enabled_actions
+        .contains_key("delete_single")
+    {
         quote! {
-            async fn delete_single_handler(axum::extract::Path(id): axum::extract::Path<<#original_struct_name as Endpoint>::Id>) -> ApiResponse<#original_struct_name> {
-                let result = #original_struct_name::delete_single(&id).await;
+            async fn delete_single_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::Path(raw_id): axum::extract::Path<String>) -> ::lily::ApiResponse<#original_struct_name> {
+                let id = match <#original_struct_name as ::lily::Endpoint>::parse_id(&raw_id) {
+                    Ok(id) => id,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                if let Err(problem) = ::lily::check_on_delete::<#original_struct_name>(&id, &state, &context).await {
+                    return ::lily::ApiResponse::Erroneous(problem);
+                }
+                let result = <#original_struct_name as ::lily::DeleteSingle>::delete_single(&state, &context, &id).await;
 
                 match result {
                     Ok(option) => match option {
-                        Some(data) => ApiResponse::Ok(data),
-                        None => ApiResponse::NotFound(Problem::ResourceNotFound {
-                            resource: #snake_name.to_string(),
-                            id: id,
+                        // The referencing resources are only changed once the resource is deleted
+                        Some(data) => match ::lily::apply_on_delete::<#original_struct_name>(&id, &state, &context).await {
+                            Ok(()) => ::lily::ApiResponse::Ok(data),
+                            Err(problem) => ::lily::ApiResponse::Erroneous(problem),
+                        },
+                        None => ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                            resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                            id: id.to_string(),
                         }),
                     },
                     Err(error_msg) => {
                         eprintln!(concat!("Error deleting single [", #snake_name, "]: {}"), error_msg);
-                        ApiResponse::Erroneous::<#original_struct_name>(Problem::InternalError)
+                        ::lily::ApiResponse::Erroneous::<#original_struct_name>(::lily::Problem::InternalError)
+                    }
+                }
+            }
+
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_path_with_id(), axum::routing::delete(delete_single_handler))
+        }
+    } else {
+        return_router_code()
+    };
+
+    // MARK: Delete Multiple
+    let add_delete_multiple_route_tokens: TokenStream = if enabled_actions
+        .contains_key("delete_multiple")
+    {
+        quote! {
+            async fn delete_multiple_handler(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::RawQuery(raw_query): axum::extract::RawQuery, axum::Json(payload): axum::Json<::lily::BulkDelete<<#original_struct_name as ::lily::Endpoint>::Id>>) -> ::lily::ApiResponse<::lily::BulkReport<#original_struct_name>> {
+                let query: ::lily::BulkQuery = match ::lily::parse_query(raw_query.as_deref()) {
+                    Ok(query) => query,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let max = <#original_struct_name as ::lily::DeleteMultiple>::MAX_BULK_SIZE;
+                if payload.ids.len() > max {
+                    return ::lily::ApiResponse::Erroneous(::lily::Problem::BulkTooLarge { max });
+                }
+                // Resources that are still referenced are reported on their own, in atomic mode
+                // nothing is deleted then
+                let problems = match ::lily::check_on_delete_each::<#original_struct_name>(&payload.ids, &state, &context).await {
+                    Ok(problems) => problems,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let mut rejections = ::lily::BulkRejections::new(payload.ids.len());
+                let ids = rejections.reject(payload.ids, problems);
+                if query.mode() == ::lily::BulkMode::Atomic && rejections.is_any_rejected() {
+                    let results = ids.iter().map(|_| Err(::lily::Problem::BulkItemNotApplied)).collect();
+                    return ::lily::ApiResponse::MultiStatus(::lily::BulkReport::from_results(rejections.merge(results), axum::http::StatusCode::OK, query.mode()));
+                }
+                let result = <#original_struct_name as ::lily::DeleteMultiple>::delete_multiple(&state, &context, &ids, query.mode()).await;
+
+                match result {
+                    Ok(results) if results.len() == ids.len() => {
+                        let mut reported = Vec::with_capacity(ids.len());
+                        for (result, id) in results.into_iter().zip(ids) {
+                            reported.push(match result {
+                                // The referencing resources are only changed for deleted resources
+                                Ok(Some(data)) => ::lily::apply_on_delete::<#original_struct_name>(&id, &state, &context).await.map(|()| data),
+                                Ok(None) => Err(::lily::Problem::ResourceNotFound {
+                                    resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                                    id: id.to_string(),
+                                }),
+                                Err(::lily::BulkItemError::Failed(error_msg)) => {
+                                    eprintln!(concat!("Error deleting multiple [", #snake_name, "]: {}"), error_msg);
+                                    Err(::lily::Problem::InternalError)
+                                }
+                                Err(error) => Err(error.into()),
+                            });
+                        }
+                        let report = ::lily::BulkReport::from_results(rejections.merge(reported), axum::http::StatusCode::OK, query.mode());
+                        if report.is_success() {
+                            ::lily::ApiResponse::Ok(report)
+                        } else {
+                            ::lily::ApiResponse::MultiStatus(report)
+                        }
+                    }
+                    Ok(results) => {
+                        eprintln!(concat!("Error deleting multiple [", #snake_name, "]: expected {} results, got {}"), ids.len(), results.len());
+                        ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError)
+                    }
+                    Err(error_msg) => {
+                        eprintln!(concat!("Error deleting multiple [", #snake_name, "]: {}"), error_msg);
+                        ::lily::ApiResponse::Erroneous::<::lily::BulkReport<#original_struct_name>>(::lily::Problem::InternalError)
                     }
                 }
             }
 
-            router.route(&#original_struct_name::get_path_with_id(), axum::routing::delete(delete_single_handler))
+            router.route(&<#original_struct_name as ::lily::Endpoint>::get_bulk_path(), axum::routing::delete(delete_multiple_handler))
         }
     } else {
         return_router_code()
     };
 
+    // MARK: Relation Routes
+    // Every many-to-many relation adds routes like `/content/{id}/tags/{related_id}`
+    let relation_route_tokens: Vec<TokenStream> = many_relations
+        .iter()
+        .map(|relation| {
+            let target = &relation.target;
+            let field_name = relation.field_name();
+            let link_handler_name = format_ident!("link_{}_handler", field_name);
+            let unlink_handler_name = format_ident!("unlink_{}_handler", field_name);
+            let parse_ids_tokens = quote! {
+                let id = match <#original_struct_name as ::lily::Endpoint>::parse_id(&raw_id) {
+                    Ok(id) => id,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+                let related_id = match <#target as ::lily::Endpoint>::parse_id(&raw_related_id) {
+                    Ok(id) => id,
+                    Err(problem) => return ::lily::ApiResponse::Erroneous(problem),
+                };
+            };
+            quote! {
+                async fn #link_handler_name(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::Path((raw_id, raw_related_id)): axum::extract::Path<(String, String)>) -> ::lily::ApiResponse<()> {
+                    #parse_ids_tokens
+                    let related_state: <#target as ::lily::Endpoint>::State = axum::extract::FromRef::from_ref(&state);
+                    match <#target as ::lily::ReadSingle>::read_single(&related_state, &context, &related_id).await {
+                        Ok(Some(_)) => {}
+                        Ok(None) => {
+                            return ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                                resource: <#target as ::lily::Endpoint>::get_name(),
+                                id: related_id.to_string(),
+                            });
+                        }
+                        Err(error_msg) => {
+                            eprintln!(concat!("Error reading linked resource of [", #snake_name, "]: {}"), error_msg);
+                            return ::lily::ApiResponse::Erroneous(::lily::Problem::InternalError);
+                        }
+                    }
+                    let result = <#original_struct_name as ::lily::ManyToMany<#target>>::link(&state, &context, &id, &related_id).await;
+
+                    match result {
+                        Ok(true) => ::lily::ApiResponse::NoContent,
+                        Ok(false) => ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                            resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                            id: id.to_string(),
+                        }),
+                        Err(error_msg) => {
+                            eprintln!(concat!("Error linking [", #snake_name, "]: {}"), error_msg);
+                            ::lily::ApiResponse::Erroneous(::lily::Problem::InternalError)
+                        }
+                    }
+                }
+
+                async fn #unlink_handler_name(axum::extract::State(state): axum::extract::State<<#original_struct_name as ::lily::Endpoint>::State>, context: ::lily::Context, axum::extract::Path((raw_id, raw_related_id)): axum::extract::Path<(String, String)>) -> ::lily::ApiResponse<()> {
+                    #parse_ids_tokens
+                    let result = <#original_struct_name as ::lily::ManyToMany<#target>>::unlink(&state, &context, &id, &related_id).await;
+
+                    match result {
+                        Ok(Some(true)) => ::lily::ApiResponse::NoContent,
+                        Ok(Some(false)) => ::lily::ApiResponse::NotFound(::lily::Problem::LinkNotFound {
+                            resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                            id: id.to_string(),
+                            related_resource: <#target as ::lily::Endpoint>::get_name(),
+                            related_id: related_id.to_string(),
+                        }),
+                        Ok(None) => ::lily::ApiResponse::NotFound(::lily::Problem::ResourceNotFound {
+                            resource: <#original_struct_name as ::lily::Endpoint>::get_name(),
+                            id: id.to_string(),
+                        }),
+                        Err(error_msg) => {
+                            eprintln!(concat!("Error unlinking [", #snake_name, "]: {}"), error_msg);
+                            ::lily::ApiResponse::Erroneous(::lily::Problem::InternalError)
+                        }
+                    }
+                }
+
+                let router = router.route(
+                    &format!("{}/{}/{{related_id}}", <#original_struct_name as ::lily::Endpoint>::get_path_with_id(), #field_name),
+                    axum::routing::post(#link_handler_name).delete(#unlink_handler_name),
+                );
+            }
+        })
+        .collect();
+
+    // MARK: Implementation checks
+    // Every enabled action requires its trait; the check points at the argument that enabled it
+    let implementation_check_tokens: Vec<TokenStream> = Routes::iter()
+        .filter_map(|route| {
+            let span = *enabled_actions.get(&route.as_snake_case())?;
+            let trait_name = format_ident!("{}", route.to_string(), span = span);
+            let struct_name = syn::Ident::new(&original_struct_name.to_string(), span);
+            Some(quote_spanned! {span=>
+                const _: fn() = || {
+                    fn assert_implemented<T: ::lily::#trait_name>() {}
+                    assert_implemented::<#struct_name>();
+                };
+            })
+        })
+        .collect();
+    // Every many-to-many relation requires its trait; the check points at the target of the relation
+    let relation_check_tokens: Vec<TokenStream> = many_relations
+        .iter()
+        .map(|relation| {
+            let target = &relation.target;
+            quote_spanned! {target.span()=>
+                const _: fn() = || {
+                    fn assert_implemented<T: ::lily::ManyToMany<#target>>() {}
+                    assert_implemented::<#original_struct_name>();
+                };
+            }
+        })
+        .collect();
+
     // MARK: RouteBuilder
     quote! {
-        impl RouteBuilder for #original_struct_name {
-            fn add_create_single_route(router: axum::Router) -> axum::Router {
+        #(#implementation_check_tokens)*
+        #(#relation_check_tokens)*
+
+        impl ::lily::RouteBuilder for #original_struct_name {
+            fn add_create_single_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
                 #add_create_single_route_tokens
             }
-            fn add_read_single_route(router: axum::Router) -> axum::Router {
+            fn add_create_multiple_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
+                #add_create_multiple_route_tokens
+            }
+            fn add_read_single_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
                 #add_read_single_route_tokens
             }
-            fn add_update_single_route(router: axum::Router) -> axum::Router {
+            fn add_read_multiple_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
+                #add_read_multiple_route_tokens
+            }
+            fn add_update_single_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
                 #add_update_single_route_tokens
             }
-            // fn add_replace_single_route(router: axum::Router) -> axum::Router {
-            //     #add_replace_single_route_tokens
-            // }
-            fn add_delete_single_route(router: axum::Router) -> axum::Router {
+            fn add_update_multiple_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
+                #add_update_multiple_route_tokens
+            }
+            fn add_replace_single_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
+                #add_replace_single_route_tokens
+            }
+            fn add_replace_multiple_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
+                #add_replace_multiple_route_tokens
+            }
+            fn add_delete_single_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
                 #add_delete_single_route_tokens
             }
+            fn add_delete_multiple_route(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
+                #add_delete_multiple_route_tokens
+            }
+            fn add_relation_routes(router: axum::Router<<#original_struct_name as ::lily::Endpoint>::State>) -> axum::Router<<#original_struct_name as ::lily::Endpoint>::State> {
+                #(#relation_route_tokens)*
+                router
+            }
         }
     }
 }
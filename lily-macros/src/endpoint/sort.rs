@@ -0,0 +1,114 @@
+use crate::StructNames;
+use crate::util::{is_collection, option_inner_type, to_pascal_case};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+
+pub fn generate_sort_field(
+    struct_ast: &syn::ItemStruct,
+    struct_names: &StructNames,
+) -> proc_macro2::TokenStream {
+    let original_struct_name: &syn::Ident = &struct_names.original;
+    let sort_field_name: &syn::Ident = &struct_names.sort_field_name;
+
+    // Collections have no natural order, so they are not sortable
+    let sortable_fields: Vec<&syn::Field> = struct_ast
+        .fields
+        .iter()
+        .filter(|field| !is_collection(option_inner_type(&field.ty).unwrap_or(&field.ty)))
+        .collect();
+
+    let mut variants: Vec<syn::Ident> = Vec::new();
+    let mut field_names: Vec<String> = Vec::new();
+    let mut compare_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut value_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut accepts_value_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut compare_value_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut order_checks: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    for field in sortable_fields {
+        let name = &field.ident;
+        let name_string = name
+            .as_ref()
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+        let variant = format_ident!("{}", to_pascal_case(&name_string));
+
+        let ty = &field.ty;
+
+        // The check points at the type of a field that can not be sorted, which is excluded
+        // with `#[lily(no_sort)]`
+        order_checks.push(quote_spanned! {ty.span()=>
+            const _: fn() = || {
+                fn assert_partial_ord<T: PartialOrd>() {}
+                assert_partial_ord::<#ty>();
+            };
+        });
+
+        compare_arms.push(quote! {
+            Self::#variant => a.#name.partial_cmp(&b.#name).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        value_arms.push(quote! {
+            Self::#variant => serde_json::to_value(&item.#name).unwrap_or_default()
+        });
+        accepts_value_arms.push(quote! {
+            Self::#variant => serde_json::from_value::<#ty>(value.clone()).is_ok()
+        });
+        compare_value_arms.push(quote! {
+            Self::#variant => serde_json::from_value::<#ty>(value.clone())
+                .ok()
+                .map(|value| item.#name.partial_cmp(&value).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        variants.push(variant);
+        field_names.push(name_string);
+    }
+
+    quote! {
+        #(#order_checks)*
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum #sort_field_name {
+            #(#variants),*
+        }
+
+        impl ::lily::SortField<#original_struct_name> for #sort_field_name {
+            const FIELDS: &'static [&'static str] = &[#(#field_names),*];
+
+            fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    #(#field_names => Some(Self::#variants),)*
+                    _ => None,
+                }
+            }
+
+            fn name(&self) -> &'static str {
+                match self {
+                    #(Self::#variants => #field_names),*
+                }
+            }
+
+            fn compare(&self, a: &#original_struct_name, b: &#original_struct_name) -> std::cmp::Ordering {
+                match self {
+                    #(#compare_arms),*
+                }
+            }
+
+            fn value(&self, item: &#original_struct_name) -> serde_json::Value {
+                match self {
+                    #(#value_arms),*
+                }
+            }
+
+            fn accepts_value(&self, value: &serde_json::Value) -> bool {
+                match self {
+                    #(#accepts_value_arms),*
+                }
+            }
+
+            fn compare_value(&self, item: &#original_struct_name, value: &serde_json::Value) -> Option<std::cmp::Ordering> {
+                match self {
+                    #(#compare_value_arms),*
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::endpoint::field_attrs::{FieldAttrs, Validation};
+use crate::util::is_option;
+use quote::quote;
+
+/// Generates the implementation of the `Validate` trait for a payload
+///
+/// Optional fields are only checked if they are present. In the update payload, every field is
+/// optional and optional fields are a `Patch`, so `is_update` has to be set.
+pub fn generate_validate(
+    payload_name: &syn::Ident,
+    fields: &[(&syn::Field, &FieldAttrs)],
+    is_update: bool,
+) -> proc_macro2::TokenStream {
+    let field_checks: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .filter(|(_, field_attrs)| !field_attrs.validations.is_empty())
+        .map(|(field, field_attrs)| {
+            let name = &field.ident;
+            let pointer = format!(
+                "/{}",
+                name.as_ref()
+                    .map(|name| name.to_string())
+                    .unwrap_or_default()
+            );
+            let value_tokens = value_access(field, is_update);
+            let checks = field_attrs.validations.iter().map(|validation| {
+                let check_tokens = generate_check(validation);
+                quote! {
+                    if let Some(message) = #check_tokens {
+                        errors.push(::lily::ValidationError::new(#pointer, message));
+                    }
+                }
+            });
+
+            quote! {
+                if let Some(value) = #value_tokens {
+                    #(#checks)*
+                }
+            }
+        })
+        .collect();
+
+    quote! {
+        impl ::lily::Validate for #payload_name {
+            fn validate(&self) -> Result<(), Vec<::lily::ValidationError>> {
+                #[allow(unused_mut)]
+                let mut errors: Vec<::lily::ValidationError> = Vec::new();
+                #(#field_checks)*
+                if errors.is_empty() { Ok(()) } else { Err(errors) }
+            }
+        }
+    }
+}
+
+/// Generates an expression evaluating to the value of a payload field, if it is present
+///
+/// In the update payload, every field is optional and optional fields are a `Patch`.
+pub fn value_access(field: &syn::Field, is_update: bool) -> proc_macro2::TokenStream {
+    let name = &field.ident;
+    match (is_update, is_option(&field.ty)) {
+        (true, true) => quote! { self.#name.value() },
+        (true, false) | (false, true) => quote! { self.#name.as_ref() },
+        (false, false) => quote! { Some(&self.#name) },
+    }
+}
+
+/// Generates an expression checking `value`, which evaluates to an error message on failure
+fn generate_check(validation: &Validation) -> proc_macro2::TokenStream {
+    fn bound<T: quote::ToTokens>(bound: &Option<T>) -> proc_macro2::TokenStream {
+        match bound {
+            Some(bound) => quote! { Some(#bound) },
+            None => quote! { None },
+        }
+    }
+
+    match validation {
+        Validation::Length { min, max } => {
+            let (min, max) = (bound(min), bound(max));
+            quote! { ::lily::check_length(value, #min, #max) }
+        }
+        Validation::Range { min, max } => {
+            let (min, max) = (bound(min), bound(max));
+            quote! { ::lily::check_range(value, #min, #max) }
+        }
+        Validation::Regex(pattern) => quote! {
+            {
+                static PATTERN: ::lily::Pattern = ::lily::Pattern::new(#pattern);
+                PATTERN.check(value)
+            }
+        },
+        Validation::Email => quote! { ::lily::check_email(value) },
+    }
+}
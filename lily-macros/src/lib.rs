@@ -4,6 +4,7 @@ use quote::quote;
 use syn::{ItemStruct, parse_macro_input};
 
 mod endpoint;
+mod persistent;
 mod util;
 
 #[proc_macro_attribute]
@@ -14,34 +15,44 @@ pub fn endpoint(attr: TokenStream, item: TokenStream) -> TokenStream {
     // Generate struct names
     let struct_names = StructNames::from(&struct_ast);
 
-    // Generate endpoint tokens
-    let endpoint_tokens = endpoint::generate_endpoint_tokens(struct_ast, &struct_names, attr);
-
-    // Generate persistence tokens
-    let persistence_tokens = proc_macro2::TokenStream::new();
+    // Generate endpoint tokens, including the persistence of a `#[persistent]` struct
+    let endpoint_tokens = endpoint::generate_endpoint_tokens(struct_ast, &struct_names, attr)
+        .unwrap_or_else(syn::Error::into_compile_error);
 
     // Return token stream
     let output = quote! {
         #endpoint_tokens
-        #persistence_tokens
     };
 
     output.into()
 }
 
+/// Stores the resources of an endpoint, implementing its single-resource actions.
+///
+/// The attribute is read by `#[endpoint]`, so it must be placed below it. On its own,
+/// it only reports where it belongs.
 #[proc_macro_attribute]
-pub fn persistent(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let tmp_item: proc_macro2::TokenStream = item.clone().into();
-
+pub fn persistent(_attr: TokenStream, item: TokenStream) -> TokenStream {
     // Parse struct
     let struct_ast: ItemStruct = parse_macro_input!(item as ItemStruct);
 
-    // Generate struct names
-    let struct_names = StructNames::from(&struct_ast);
+    let has_endpoint = struct_ast.attrs.iter().any(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "endpoint")
+    });
+    let message = if has_endpoint {
+        "`#[persistent]` must be placed below `#[endpoint]`"
+    } else {
+        "`#[persistent]` requires `#[endpoint]` above it"
+    };
+    let error = syn::Error::new_spanned(&struct_ast.ident, message).into_compile_error();
 
     // Return token stream
     let output = quote! {
-        #tmp_item
+        #struct_ast
+        #error
     };
 
     output.into()
@@ -0,0 +1,348 @@
+use crate::StructNames;
+use crate::endpoint::field_attrs::{FieldAttrs, MetadataField};
+use crate::endpoint::metadata::Metadata;
+use crate::util::option_inner_type;
+use proc_macro2::Span;
+use quote::{quote, quote_spanned};
+use std::collections::HashMap;
+use syn::spanned::Spanned;
+
+/// The actions `#[persistent]` implements on top of the store
+const PERSISTENT_ACTIONS: [&str; 5] = [
+    "create_single",
+    "read_single",
+    "read_multiple",
+    "update_single",
+    "delete_single",
+];
+
+/// The parsed arguments of the persistent attribute
+///
+/// # Examples
+/// ```ignore
+/// #[endpoint(state = AppState)]
+/// #[persistent(store = MemoryStore)]
+/// #[persistent(store = MemoryStore, read_single, update_single)]
+/// ```
+pub struct PersistentArgs {
+    /// The store taken from the state of the endpoint
+    pub store: syn::Type,
+    /// The actions implemented through the store, all enabled ones by default
+    pub actions: Vec<syn::Ident>,
+}
+
+impl PersistentArgs {
+    fn parse(attr: &syn::Attribute) -> syn::Result<Self> {
+        let mut store: Option<syn::Type> = None;
+        let mut actions: Vec<syn::Ident> = Vec::new();
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("store") {
+                store = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if let Some(action) = meta.path.get_ident() {
+                if !PERSISTENT_ACTIONS.contains(&action.to_string().as_str()) {
+                    return Err(syn::Error::new(
+                        action.span(),
+                        format!(
+                            "`#[persistent]` only implements {}",
+                            PERSISTENT_ACTIONS
+                                .iter()
+                                .map(|action| format!("`{}`", action))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    ));
+                }
+                actions.push(action.clone());
+                Ok(())
+            } else {
+                Err(meta.error("unsupported persistent argument"))
+            }
+        })?;
+        let store = store.ok_or_else(|| {
+            syn::Error::new_spanned(
+                attr,
+                "expected a store, e.g. `#[persistent(store = MemoryStore)]`",
+            )
+        })?;
+        Ok(PersistentArgs { store, actions })
+    }
+}
+
+/// Returns whether an attribute is `#[persistent]`, also if it is given with its crate path
+fn is_persistent_attr(attr: &syn::Attribute) -> bool {
+    attr.path()
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "persistent")
+}
+
+/// Parses and strips the persistent attribute placed below `#[endpoint]`
+pub fn take_persistent_attr(
+    struct_ast: &mut syn::ItemStruct,
+) -> syn::Result<Option<PersistentArgs>> {
+    let mut persistent_attrs = struct_ast
+        .attrs
+        .iter()
+        .filter(|attr| is_persistent_attr(attr));
+    let args = match persistent_attrs.next() {
+        Some(attr) => {
+            if let Some(duplicate) = persistent_attrs.next() {
+                return Err(syn::Error::new_spanned(
+                    duplicate,
+                    "`#[persistent]` may only be given once",
+                ));
+            }
+            Some(PersistentArgs::parse(attr)?)
+        }
+        None => None,
+    };
+    struct_ast.attrs.retain(|attr| !is_persistent_attr(attr));
+    Ok(args)
+}
+
+/// Creates the `Persistent` implementation and the CRUD traits implemented through the store
+pub fn generate_persistence(
+    struct_ast: &syn::ItemStruct,
+    struct_names: &StructNames,
+    persistent_args: &PersistentArgs,
+    enabled_actions: &HashMap<String, Span>,
+    metadata: &Metadata,
+    field_attrs: &[FieldAttrs],
+) -> syn::Result<proc_macro2::TokenStream> {
+    let original_struct_name: &syn::Ident = &struct_names.original;
+
+    // Every field of the stored struct, including the injected metadata fields
+    let fields: Vec<(syn::Field, Option<MetadataField>, bool)> = metadata
+        .missing_fields()
+        .into_iter()
+        .map(|(field, role)| (field, Some(role), false))
+        .chain(
+            struct_ast
+                .fields
+                .iter()
+                .zip(field_attrs)
+                .map(|(field, field_attrs)| {
+                    (
+                        field.clone(),
+                        field_attrs.metadata,
+                        field_attrs.in_create_payload(),
+                    )
+                }),
+        )
+        .collect();
+
+    // Metadata fields are set by the store, fields missing from the payload start with their default
+    let create_fields: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .map(|(field, role, in_create_payload)| {
+            let name = &field.ident;
+            match role {
+                Some(MetadataField::Id) => quote! { #name: id },
+                Some(MetadataField::CreatedAt | MetadataField::UpdatedAt) => {
+                    quote! { #name: now.into() }
+                }
+                None if *in_create_payload => quote! { #name: payload.#name.clone() },
+                None => quote! { #name: Default::default() },
+            }
+        })
+        .collect();
+    let touch_tokens = fields
+        .iter()
+        .find(|(_, role, _)| *role == Some(MetadataField::UpdatedAt))
+        .map(|(field, _, _)| {
+            let name = &field.ident;
+            quote! { self.#name = now.into(); }
+        });
+    let encode_fields: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .map(|(field, _, _)| {
+            let name = &field.ident;
+            let key = name.as_ref().map(|name| name.to_string());
+            quote! { record.insert(#key.to_owned(), ::lily::encode_field(#key, &self.#name)?); }
+        })
+        .collect();
+    let decode_fields: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .map(|(field, _, _)| {
+            let name = &field.ident;
+            let key = name.as_ref().map(|name| name.to_string());
+            quote! { #name: ::lily::decode_field(&mut record, #key)? }
+        })
+        .collect();
+
+    let columns: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .map(|(field, role, _)| {
+            let name = field.ident.as_ref().map(|name| name.to_string());
+            let nullable = option_inner_type(&field.ty).is_some();
+            let kind = column_kind(option_inner_type(&field.ty).unwrap_or(&field.ty));
+            let primary_key = *role == Some(MetadataField::Id);
+            quote! {
+                ::lily::Column {
+                    name: #name,
+                    kind: ::lily::ColumnKind::#kind,
+                    nullable: #nullable,
+                    primary_key: #primary_key,
+                }
+            }
+        })
+        .collect();
+
+    // The id type decides how the ids of new resources are derived
+    let assert_new_id_tokens = fields
+        .iter()
+        .find(|(_, role, _)| *role == Some(MetadataField::Id))
+        .map(|(field, _, _)| {
+            let ty = &field.ty;
+            quote_spanned! {ty.span()=>
+                const _: fn() = || {
+                    fn assert_new_id<T: ::lily::NewId>() {}
+                    assert_new_id::<#ty>();
+                };
+            }
+        });
+
+    let impl_persistent_tokens = quote! {
+        #assert_new_id_tokens
+
+        impl ::lily::Persistent for #original_struct_name {
+            const COLUMNS: &'static [::lily::Column] = &[#(#columns),*];
+
+            #[allow(unused_variables)]
+            fn create(id: Self::Id, payload: &Self::CreatePayload, now: chrono::DateTime<chrono::Utc>) -> Self {
+                #original_struct_name {
+                    #(#create_fields),*
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn apply_update(&mut self, payload: &Self::UpdatePayload, now: chrono::DateTime<chrono::Utc>) {
+                payload.apply_to(self);
+                #touch_tokens
+            }
+
+            fn to_record(&self) -> Result<::lily::Record, ::lily::Error> {
+                let mut record = ::lily::Record::new();
+                #(#encode_fields)*
+                Ok(record)
+            }
+
+            fn from_record(mut record: ::lily::Record) -> Result<Self, ::lily::Error> {
+                Ok(#original_struct_name {
+                    #(#decode_fields),*
+                })
+            }
+        }
+    };
+
+    // Without an explicit list, every enabled action the store can implement is implemented
+    let actions: Vec<(String, Span)> = if persistent_args.actions.is_empty() {
+        PERSISTENT_ACTIONS
+            .iter()
+            .filter(|action| enabled_actions.contains_key(**action))
+            .map(|action| (action.to_string(), Span::call_site()))
+            .collect()
+    } else {
+        persistent_args
+            .actions
+            .iter()
+            .map(|action| (action.to_string(), action.span()))
+            .collect()
+    };
+    for (action, span) in &actions {
+        if !enabled_actions.contains_key(action) {
+            return Err(syn::Error::new(
+                *span,
+                format!("`{}` is not enabled on the endpoint", action),
+            ));
+        }
+    }
+
+    // The store must be reachable from the state of the endpoint
+    let store = &persistent_args.store;
+    let repository = quote_spanned! {store.span()=>
+        ::lily::Repository::<Self, #store>::from_state(state)
+    };
+    let impl_actions_tokens: Vec<proc_macro2::TokenStream> = actions
+        .iter()
+        .map(|(action, _)| match action.as_str() {
+            "create_single" => quote! {
+                impl ::lily::CreateSingle for #original_struct_name {
+                    async fn create_single(state: &Self::State, _: &::lily::Context, payload: &Self::CreatePayload) -> Result<Self, ::lily::Error> {
+                        #repository.create(payload).await
+                    }
+                }
+            },
+            "read_single" => quote! {
+                impl ::lily::ReadSingle for #original_struct_name {
+                    async fn read_single(state: &Self::State, _: &::lily::Context, id: &Self::Id) -> Result<Option<Self>, ::lily::Error> {
+                        #repository.find(id).await
+                    }
+                }
+            },
+            "read_multiple" => quote! {
+                impl ::lily::ReadMultiple for #original_struct_name {
+                    async fn read_multiple(state: &Self::State, _: &::lily::Context, query: &::lily::ListQuery<Self>) -> Result<::lily::Page<Self>, ::lily::Error> {
+                        #repository.list(query).await
+                    }
+                }
+            },
+            "update_single" => quote! {
+                impl ::lily::UpdateSingle for #original_struct_name {
+                    async fn update_single(state: &Self::State, _: &::lily::Context, id: &Self::Id, payload: &Self::UpdatePayload) -> Result<Self, ::lily::Error> {
+                        #repository.update(id, payload).await
+                    }
+                }
+            },
+            "delete_single" => quote! {
+                impl ::lily::DeleteSingle for #original_struct_name {
+                    async fn delete_single(state: &Self::State, _: &::lily::Context, id: &Self::Id) -> Result<Option<Self>, ::lily::Error> {
+                        #repository.delete(id).await
+                    }
+                }
+            },
+            _ => unreachable!("Only the persistent actions are parsed"),
+        })
+        .collect();
+
+    Ok(quote! {
+        #impl_persistent_tokens
+        #(#impl_actions_tokens)*
+    })
+}
+
+/// Derives the storage type of a field from its type, without the `Option`
+///
+/// Types that are not recognized are stored as JSON, which holds any serializable value.
+fn column_kind(ty: &syn::Type) -> syn::Ident {
+    const INTEGERS: [&str; 12] = [
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    let kind = match ty {
+        syn::Type::Path(type_path) => match type_path.path.segments.last() {
+            Some(segment) if segment.ident == "String" || segment.ident == "NaiveDate" => "Text",
+            Some(segment) if INTEGERS.iter().any(|integer| segment.ident == integer) => "Integer",
+            Some(segment) if segment.ident == "f32" || segment.ident == "f64" => "Real",
+            Some(segment) if segment.ident == "bool" => "Boolean",
+            // Only UTC timestamps are normalized, other offsets would be lost
+            Some(segment) if segment.ident == "DateTime" && is_utc(&segment.arguments) => {
+                "Timestamp"
+            }
+            _ => "Json",
+        },
+        _ => "Json",
+    };
+    syn::Ident::new(kind, Span::call_site())
+}
+
+/// Returns whether the generic arguments of a `DateTime` are `<Utc>`
+fn is_utc(arguments: &syn::PathArguments) -> bool {
+    if let syn::PathArguments::AngleBracketed(arguments) = arguments
+        && let Some(syn::GenericArgument::Type(syn::Type::Path(type_path))) = arguments.args.first()
+        && let Some(segment) = type_path.path.segments.last()
+    {
+        return segment.ident == "Utc";
+    }
+    false
+}
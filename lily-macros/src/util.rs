@@ -1,11 +1,14 @@
 use quote::format_ident;
-use syn::ItemStruct;
+use syn::{ItemStruct, Token, punctuated::Punctuated};
 
 pub struct StructNames {
     pub original: syn::Ident,
     pub snake_case: String,
     pub create_payload_name: syn::Ident,
     pub update_payload_name: syn::Ident,
+    pub replace_payload_name: syn::Ident,
+    pub filter_name: syn::Ident,
+    pub sort_field_name: syn::Ident,
 }
 impl From<&ItemStruct> for StructNames {
     fn from(ast: &ItemStruct) -> Self {
@@ -14,8 +17,11 @@ impl From<&ItemStruct> for StructNames {
         StructNames {
             create_payload_name: format_ident!("Create{}", &struct_name),
             update_payload_name: format_ident!("Update{}", &struct_name),
+            replace_payload_name: format_ident!("Replace{}", &struct_name),
+            filter_name: format_ident!("{}Filter", &struct_name),
+            sort_field_name: format_ident!("{}SortField", &struct_name),
             original: struct_name,
-            snake_case: snake_case,
+            snake_case,
         }
     }
 }
@@ -23,7 +29,7 @@ impl From<&ItemStruct> for StructNames {
 /// Converts a string from lowerCamelCase to snake_case
 ///
 /// # Examples
-/// ```
+/// ```ignore
 /// let snake_case: String = to_snake_case("lowerCamelCase");
 /// ```
 ///
@@ -48,27 +54,251 @@ pub fn to_snake_case(input: &str) -> String {
     result
 }
 
-/// Converts a string from lowerCamelCase to kebab case
+/// Converts a string from snake_case to kebab-case
 ///
 /// # Examples
-/// ```
-/// let kebab_case: String = to_kebab_case("lowerCamelCase");
+/// ```ignore
+/// let kebab_case: String = to_kebab_case("snake_case");
 /// ```
 pub fn to_kebab_case(input: &str) -> String {
-    to_snake_case(input).replace("_", "-")
+    input.replace('_', "-")
 }
 
-/// Checks wether a value of type syn::data::Field::Type is of type Option
+/// Pluralizes the last word of a snake_case string using common English rules
+///
+/// # Examples
+/// ```ignore
+/// let plural: String = pluralize("blog_category"); // "blog_categories"
+/// ```
+pub fn pluralize(input: &str) -> String {
+    const VOWELS: [char; 5] = ['a', 'e', 'i', 'o', 'u'];
+    if let Some(stem) = input.strip_suffix('y')
+        && !stem.ends_with(VOWELS)
+        && !stem.is_empty()
+    {
+        format!("{}ies", stem)
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| input.ends_with(suffix))
+    {
+        format!("{}es", input)
+    } else {
+        format!("{}s", input)
+    }
+}
+
+/// Converts a string from snake_case to PascalCase
 ///
 /// # Examples
+/// ```ignore
+/// let pascal_case: String = to_pascal_case("snake_case");
 /// ```
+pub fn to_pascal_case(input: &str) -> String {
+    input
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the candidate closest to the input, if it is close enough to be a likely typo
+///
+/// # Examples
+/// ```ignore
+/// let suggestion = closest_match("raed", &["read", "read_single"]); // Some("read")
+/// ```
+pub fn closest_match<'a>(input: &str, candidates: &'a [impl AsRef<str>]) -> Option<&'a str> {
+    let max_distance = (input.chars().count() / 3).max(2);
+    candidates
+        .iter()
+        .map(|candidate| (candidate.as_ref(), edit_distance(input, candidate.as_ref())))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(candidate, _)| candidate)
+}
+
+/// Computes the Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the paths of all derives of an item
+///
+/// # Examples
+/// ```ignore
+/// let derives: Vec<syn::Path> = derived_paths(&struct_ast.attrs);
+/// ```
+pub fn derived_paths(attrs: &[syn::Attribute]) -> Vec<syn::Path> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .collect()
+}
+
+/// Returns the additional derives that are not derived yet, comparing them by their last path
+/// segment, so that `Serialize` and `serde::Serialize` are considered equal
+///
+/// # Examples
+/// ```ignore
+/// let missing = merge_derives(&derived_paths(&struct_ast.attrs), [parse_quote!(Debug)]);
+/// ```
+pub fn merge_derives(
+    existing: &[syn::Path],
+    additional: impl IntoIterator<Item = syn::Path>,
+) -> Vec<syn::Path> {
+    let last_segment = |path: &syn::Path| path.segments.last().map(|segment| segment.ident.clone());
+    let mut merged: Vec<syn::Path> = Vec::new();
+    for path in additional {
+        let name = last_segment(&path);
+        if !existing
+            .iter()
+            .chain(merged.iter())
+            .any(|other| last_segment(other) == name)
+        {
+            merged.push(path);
+        }
+    }
+    merged
+}
+
+/// Checks wether a value of type syn::data::Field::Type is of type Option
+///
+/// # Examples
+/// ```ignore
 /// if is_option(&field.ty) {}
 /// ```
 pub fn is_option(ty: &syn::Type) -> bool {
-    if let syn::Type::Path(type_path) = ty {
-        if let Some(segment) = type_path.path.segments.last() {
-            return segment.ident == "Option";
-        }
+    if let syn::Type::Path(type_path) = ty
+        && let Some(segment) = type_path.path.segments.last()
+    {
+        return segment.ident == "Option";
+    }
+    false
+}
+
+/// Returns the inner type `T` of a type `Option<T>`
+///
+/// # Examples
+/// ```ignore
+/// let inner_ty = option_inner_type(&field.ty).unwrap_or(&field.ty);
+/// ```
+pub fn option_inner_type(ty: &syn::Type) -> Option<&syn::Type> {
+    if let syn::Type::Path(type_path) = ty
+        && let Some(segment) = type_path.path.segments.last()
+        && segment.ident == "Option"
+        && let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments
+        && let Some(syn::GenericArgument::Type(inner_ty)) = arguments.args.first()
+    {
+        return Some(inner_ty);
+    }
+    None
+}
+
+/// Checks wether a value of type syn::data::Field::Type is a collection like Vec or HashMap
+///
+/// # Examples
+/// ```ignore
+/// if is_collection(&field.ty) {}
+/// ```
+pub fn is_collection(ty: &syn::Type) -> bool {
+    const COLLECTIONS: [&str; 6] = [
+        "Vec", "VecDeque", "HashMap", "HashSet", "BTreeMap", "BTreeSet",
+    ];
+    if let syn::Type::Path(type_path) = ty
+        && let Some(segment) = type_path.path.segments.last()
+    {
+        return COLLECTIONS
+            .iter()
+            .any(|collection| segment.ident == collection);
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pluralizes_the_last_word() {
+        let cases = [
+            ("content", "contents"),
+            ("blog_category", "blog_categories"),
+            ("day", "days"),
+            ("key", "keys"),
+            ("status", "statuses"),
+            ("box", "boxes"),
+            ("buzz", "buzzes"),
+            ("batch", "batches"),
+            ("wish", "wishes"),
+            ("y", "ys"),
+        ];
+        for (singular, plural) in cases {
+            assert_eq!(pluralize(singular), plural, "{}", singular);
+        }
+    }
+
+    #[test]
+    fn converts_snake_case_to_kebab_case() {
+        assert_eq!(to_kebab_case("blog_category"), "blog-category");
+        assert_eq!(
+            to_kebab_case(&pluralize("blog_category")),
+            "blog-categories"
+        );
+        assert_eq!(to_kebab_case("content"), "content");
+    }
+
+    #[test]
+    fn converts_between_cases() {
+        assert_eq!(to_snake_case("BlogCategory"), "blog_category");
+        assert_eq!(to_pascal_case("blog_category"), "BlogCategory");
+    }
+
+    #[test]
+    fn computes_the_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("read", "read"), 0);
+        assert_eq!(edit_distance("", "read"), 4);
+        assert_eq!(edit_distance("raed", "read"), 2);
+    }
+
+    #[test]
+    fn suggests_close_matches_only() {
+        let actions = ["read", "read_single", "read_multiple", "delete_single"];
+        assert_eq!(closest_match("raed", &actions), Some("read"));
+        assert_eq!(closest_match("read_singel", &actions), Some("read_single"));
+        // Up to a third of a long name may differ
+        assert_eq!(
+            closest_match("delet_singl", &actions),
+            Some("delete_single")
+        );
+        assert_eq!(closest_match("update", &actions), None);
+        assert_eq!(closest_match("write", &actions), None);
+    }
+
+    #[test]
+    fn allows_two_edits_in_short_names() {
+        let actions = ["read"];
+        assert_eq!(closest_match("rd", &actions), Some("read"));
+        assert_eq!(closest_match("r", &actions), None);
+    }
+}
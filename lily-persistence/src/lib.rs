@@ -0,0 +1,29 @@
+//! Provides the storage of endpoints marked with `#[persistent]`.
+//!
+//! A [`Store`](store::Store) is a storage backend holding the resources of
+//! any number of endpoints, each in its own collection. The
+//! [`Repository`](repository::Repository) is the typed view of a single
+//! collection, which the generated `CreateSingle`, `ReadSingle`,
+//! `ReadMultiple`, `UpdateSingle` and `DeleteSingle` implementations use.
+//!
+//! ```ignore
+//! #[endpoint(state = AppState)]
+//! #[persistent(store = MemoryStore)]
+//! pub struct Content {
+//!     title: String,
+//! }
+//! ```
+//!
+//! The store is taken from the state of the endpoint with
+//! [`FromRef`](axum::extract::FromRef), so it is created once and shared by
+//! all endpoints of the application. The [`MemoryStore`](memory::MemoryStore)
+//! is the store for development and tests, the
+//! [`SqliteStore`](sqlite::SqliteStore) keeps the resources in an embedded
+//! SQLite database and requires the `sqlite` feature.
+
+pub mod memory;
+pub mod record;
+pub mod repository;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
@@ -0,0 +1,569 @@
+//! Provides a store keeping the resources in memory, for development and tests.
+//!
+//! The [`MemoryStore`] loses its resources when the application stops, unless
+//! it is created with a snapshot file. The snapshot is restored on startup and
+//! written when the last clone of the store is dropped, so the application only
+//! has to shut down gracefully:
+//!
+//! ```no_run
+//! use lily_persistence::memory::MemoryStore;
+//!
+//! # async fn run(app: axum::Router) -> Result<(), lily_core::Error> {
+//! let store = MemoryStore::with_snapshot("snapshot.json")?;
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+//! axum::serve(listener, app)
+//!     .with_graceful_shutdown(async {
+//!         tokio::signal::ctrl_c().await.ok();
+//!     })
+//!     .await
+//!     .unwrap();
+//! // Saves the snapshot, as the router holding the other clones is gone
+//! drop(store);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::record::{Persistent, Record};
+use crate::store::Store;
+use lily_core::Error;
+use lily_endpoint::filtering::Filter;
+use lily_endpoint::pagination::Page;
+use lily_endpoint::routing::ListQuery;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A thread-safe store keeping every collection in memory, keyed by the id of its resources.
+///
+/// Clones share the same collections. A store created with
+/// [`MemoryStore::with_snapshot`] saves the snapshot when its last clone is
+/// dropped, reporting a failure on stderr.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    shared: Arc<Shared>,
+}
+
+/// The state shared by the clones of a store
+#[derive(Default)]
+struct Shared {
+    collections: Mutex<Collections>,
+    snapshot_path: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Collections {
+    /// The collections that were used since the store was created
+    loaded: HashMap<String, LoadedCollection>,
+    /// The collections of the snapshot that were not used yet, as their type is not known before
+    unloaded: HashMap<String, CollectionSnapshot>,
+}
+
+/// A collection of a single endpoint, erased to be kept next to the collections of other endpoints
+struct LoadedCollection {
+    collection: Box<dyn Any + Send>,
+    snapshot: fn(&(dyn Any + Send)) -> Result<CollectionSnapshot, Error>,
+}
+
+struct Collection<E: Persistent> {
+    sequence: u64,
+    items: BTreeMap<E::Id, E>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct CollectionSnapshot {
+    sequence: u64,
+    records: Vec<Record>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Creates a store saving its collections to a snapshot file, restoring
+    /// them from the file if it exists.
+    pub fn with_snapshot(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let unloaded = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|error| {
+                Error::InvalidRecord(format!("snapshot `{}`: {}", path.display(), error))
+            })?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(Error::Storage(error.to_string())),
+        };
+        Ok(MemoryStore {
+            shared: Arc::new(Shared {
+                collections: Mutex::new(Collections {
+                    loaded: HashMap::new(),
+                    unloaded,
+                }),
+                snapshot_path: Some(path),
+            }),
+        })
+    }
+
+    /// Writes every collection to the snapshot file, replacing it as a whole.
+    ///
+    /// Called when the last clone of the store is dropped, and only needed to
+    /// save a snapshot earlier. Does nothing if the store was not created with
+    /// [`MemoryStore::with_snapshot`].
+    pub fn save_snapshot(&self) -> Result<(), Error> {
+        self.shared.save_snapshot()
+    }
+
+    /// Returns the requested page of the resources matching both the filter and the predicate,
+    /// for conditions a filter can not express.
+    pub fn list_matching<E: Persistent>(
+        &self,
+        query: &ListQuery<E>,
+        predicate: impl Fn(&E) -> bool,
+    ) -> Result<Page<E>, Error> {
+        self.with_collection(|collection: &mut Collection<E>| {
+            let mut matching: Vec<&E> = collection
+                .items
+                .values()
+                .filter(|item| query.filter.matches(item) && predicate(item))
+                .collect();
+            matching.sort_by(|a, b| query.compare(a, b));
+            let total = matching.len() as u64;
+            let items = matching
+                .into_iter()
+                .filter(|item| query.is_after_cursor(item))
+                .skip(query.pagination.offset as usize)
+                .take(query.pagination.limit as usize)
+                .cloned()
+                .collect();
+            Page::new(items, total, &query.pagination)
+        })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Collections>, Error> {
+        self.shared.lock()
+    }
+
+    /// Runs `f` on the collection of an endpoint, restoring it from the snapshot on first use
+    fn with_collection<E: Persistent, R>(
+        &self,
+        f: impl FnOnce(&mut Collection<E>) -> R,
+    ) -> Result<R, Error> {
+        let mut collections = self.lock()?;
+        let name = E::collection();
+        if !collections.loaded.contains_key(&name) {
+            let collection = match collections.unloaded.get(&name) {
+                Some(snapshot) => Collection::<E>::restore(snapshot)?,
+                None => Collection::<E>::new(),
+            };
+            collections.unloaded.remove(&name);
+            collections.loaded.insert(
+                name.clone(),
+                LoadedCollection {
+                    collection: Box::new(collection),
+                    snapshot: snapshot_collection::<E>,
+                },
+            );
+        }
+        let collection = collections
+            .loaded
+            .get_mut(&name)
+            .and_then(|loaded| loaded.collection.downcast_mut::<Collection<E>>())
+            .ok_or_else(|| {
+                Error::Storage(format!(
+                    "the collection `{}` is used by more than one endpoint",
+                    name
+                ))
+            })?;
+        Ok(f(collection))
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> Result<MutexGuard<'_, Collections>, Error> {
+        self.collections
+            .lock()
+            .map_err(|_| Error::Storage("the memory store is poisoned".to_owned()))
+    }
+
+    fn save_snapshot(&self) -> Result<(), Error> {
+        let Some(path) = &self.snapshot_path else {
+            return Ok(());
+        };
+        let snapshot: BTreeMap<String, CollectionSnapshot> = {
+            let collections = self.lock()?;
+            let mut snapshot: BTreeMap<String, CollectionSnapshot> = collections
+                .unloaded
+                .iter()
+                .map(|(name, collection)| (name.clone(), collection.clone()))
+                .collect();
+            for (name, loaded) in &collections.loaded {
+                snapshot.insert(name.clone(), (loaded.snapshot)(loaded.collection.as_ref())?);
+            }
+            snapshot
+        };
+        let bytes = serde_json::to_vec_pretty(&snapshot)
+            .map_err(|error| Error::Storage(error.to_string()))?;
+
+        // Write to a temporary file first, so a failed write keeps the previous snapshot
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, bytes)
+            .and_then(|_| fs::rename(&temporary_path, path))
+            .map_err(|error| Error::Storage(error.to_string()))
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        if let Err(error) = self.save_snapshot() {
+            eprintln!("Error saving the snapshot of the memory store: {}", error);
+        }
+    }
+}
+
+impl<E: Persistent> Collection<E> {
+    fn new() -> Self {
+        Collection {
+            sequence: 0,
+            items: BTreeMap::new(),
+        }
+    }
+
+    fn restore(snapshot: &CollectionSnapshot) -> Result<Self, Error> {
+        let items = snapshot
+            .records
+            .iter()
+            .map(|record| {
+                let item = E::from_record(record.clone())?;
+                Ok((item.id().clone(), item))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Collection {
+            sequence: snapshot.sequence,
+            items,
+        })
+    }
+}
+
+fn snapshot_collection<E: Persistent>(
+    collection: &(dyn Any + Send),
+) -> Result<CollectionSnapshot, Error> {
+    let collection = collection
+        .downcast_ref::<Collection<E>>()
+        .expect("The snapshot function is stored with its collection");
+    Ok(CollectionSnapshot {
+        sequence: collection.sequence,
+        records: collection
+            .items
+            .values()
+            .map(Persistent::to_record)
+            .collect::<Result<_, Error>>()?,
+    })
+}
+
+impl Store for MemoryStore {
+    async fn next_sequence<E: Persistent>(&self) -> Result<u64, Error> {
+        self.with_collection(|collection: &mut Collection<E>| {
+            collection.sequence += 1;
+            collection.sequence
+        })
+    }
+
+    async fn insert<E: Persistent>(&self, item: &E) -> Result<(), Error> {
+        self.with_collection(|collection: &mut Collection<E>| {
+            if collection.items.contains_key(item.id()) {
+                return Err(Error::IdTaken(format!(
+                    "`{}` in `{}`",
+                    item.id(),
+                    E::collection()
+                )));
+            }
+            collection.items.insert(item.id().clone(), item.clone());
+            Ok(())
+        })?
+    }
+
+    async fn get<E: Persistent>(&self, id: &E::Id) -> Result<Option<E>, Error> {
+        self.with_collection(|collection: &mut Collection<E>| collection.items.get(id).cloned())
+    }
+
+    async fn update<E: Persistent>(&self, item: &E) -> Result<bool, Error> {
+        self.with_collection(|collection: &mut Collection<E>| {
+            match collection.items.get_mut(item.id()) {
+                Some(stored) => {
+                    *stored = item.clone();
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    async fn delete<E: Persistent>(&self, id: &E::Id) -> Result<Option<E>, Error> {
+        self.with_collection(|collection: &mut Collection<E>| collection.items.remove(id))
+    }
+
+    async fn list<E: Persistent>(&self, query: &ListQuery<E>) -> Result<Page<E>, Error> {
+        self.list_matching(query, |_| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use lily_endpoint::context::Context;
+    use lily_endpoint::filtering::{Condition, FilterError, Operator};
+    use lily_endpoint::relations::{Dependent, ReferenceLookups, References, Relation};
+    use lily_endpoint::routing::Endpoint;
+    use lily_endpoint::sorting::SortField;
+    use lily_endpoint::validation::{Validate, ValidationError};
+    use serde_json::{Value, json};
+    use std::cmp::Ordering;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        id: u32,
+        text: String,
+    }
+
+    #[derive(Deserialize)]
+    struct NotePayload {
+        text: String,
+    }
+
+    impl Validate for NotePayload {
+        fn validate(&self) -> Result<(), Vec<ValidationError>> {
+            Ok(())
+        }
+    }
+
+    impl References<()> for NotePayload {
+        async fn missing_references(
+            &self,
+            _: &(),
+            _: &Context,
+            _: &ReferenceLookups,
+        ) -> Result<Vec<ValidationError>, Error> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[derive(Default)]
+    struct NoteFilter;
+
+    impl Filter<Note> for NoteFilter {
+        const FIELDS: &'static [&'static str] = &[];
+
+        fn add_condition(&mut self, field: &str, _: Operator, _: &str) -> Result<(), FilterError> {
+            Err(FilterError {
+                parameter: field.to_owned(),
+                detail: "notes can not be filtered".to_owned(),
+            })
+        }
+
+        fn is_empty(&self) -> bool {
+            true
+        }
+
+        fn matches(&self, _: &Note) -> bool {
+            true
+        }
+
+        fn conditions(&self) -> Vec<(&'static str, Condition<Value>)> {
+            Vec::new()
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    enum NoteSortField {}
+
+    impl SortField<Note> for NoteSortField {
+        const FIELDS: &'static [&'static str] = &[];
+
+        fn from_name(_: &str) -> Option<Self> {
+            None
+        }
+
+        fn name(&self) -> &'static str {
+            match *self {}
+        }
+
+        fn compare(&self, _: &Note, _: &Note) -> Ordering {
+            match *self {}
+        }
+
+        fn value(&self, _: &Note) -> Value {
+            match *self {}
+        }
+
+        fn accepts_value(&self, _: &Value) -> bool {
+            match *self {}
+        }
+
+        fn compare_value(&self, _: &Note, _: &Value) -> Option<Ordering> {
+            match *self {}
+        }
+    }
+
+    impl Endpoint for Note {
+        type Id = u32;
+        type State = ();
+        type CreatePayload = NotePayload;
+        type UpdatePayload = NotePayload;
+        type ReplacePayload = NotePayload;
+        type Filter = NoteFilter;
+        type SortField = NoteSortField;
+
+        const FIELDS: &'static [&'static str] = &["id", "text"];
+        const CREATE_FIELDS: &'static [&'static str] = &["text"];
+        const UPDATE_FIELDS: &'static [&'static str] = &["text"];
+        const RELATIONS: &'static [Relation] = &[];
+        const DEPENDENTS: &'static [&'static [Dependent]] = &[];
+
+        fn get_name() -> String {
+            "notes".to_owned()
+        }
+
+        fn get_path() -> String {
+            "/notes".to_owned()
+        }
+
+        fn get_path_with_id() -> String {
+            "/notes/{id}".to_owned()
+        }
+
+        fn get_bulk_path() -> String {
+            "/notes/_bulk".to_owned()
+        }
+
+        fn id(&self) -> &u32 {
+            &self.id
+        }
+    }
+
+    impl Persistent for Note {
+        const COLUMNS: &'static [crate::record::Column] = &[];
+
+        fn create(id: u32, payload: &NotePayload, _: DateTime<Utc>) -> Self {
+            Note {
+                id,
+                text: payload.text.clone(),
+            }
+        }
+
+        fn apply_update(&mut self, payload: &NotePayload, _: DateTime<Utc>) {
+            self.text = payload.text.clone();
+        }
+
+        fn to_record(&self) -> Result<Record, Error> {
+            match serde_json::to_value(self) {
+                Ok(Value::Object(record)) => Ok(record),
+                _ => Err(Error::InvalidRecord("a note is an object".to_owned())),
+            }
+        }
+
+        fn from_record(record: Record) -> Result<Self, Error> {
+            serde_json::from_value(Value::Object(record))
+                .map_err(|error| Error::InvalidRecord(error.to_string()))
+        }
+    }
+
+    /// A snapshot path of its own for every test, removed when the test ends
+    struct SnapshotFile(PathBuf);
+
+    impl SnapshotFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "lily-memory-{}-{}.json",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_file(&path);
+            SnapshotFile(path)
+        }
+    }
+
+    impl Drop for SnapshotFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn note(id: u32, text: &str) -> Note {
+        Note {
+            id,
+            text: text.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn dropping_the_last_clone_saves_the_snapshot() {
+        let file = SnapshotFile::new("drop");
+        let store = MemoryStore::with_snapshot(&file.0).unwrap();
+        let clone = store.clone();
+        store.insert(&note(1, "first")).await.unwrap();
+        drop(store);
+        assert!(!file.0.exists());
+        drop(clone);
+        assert!(file.0.exists());
+    }
+
+    #[tokio::test]
+    async fn snapshots_restore_resources_and_sequences() {
+        let file = SnapshotFile::new("round-trip");
+        let store = MemoryStore::with_snapshot(&file.0).unwrap();
+        assert_eq!(store.next_sequence::<Note>().await.unwrap(), 1);
+        store.insert(&note(1, "first")).await.unwrap();
+        store.insert(&note(7, "second")).await.unwrap();
+        drop(store);
+
+        let store = MemoryStore::with_snapshot(&file.0).unwrap();
+        assert_eq!(
+            store.get::<Note>(&7).await.unwrap(),
+            Some(note(7, "second"))
+        );
+        let page = store.list(&ListQuery::<Note>::all()).await.unwrap();
+        assert_eq!(page.items, [note(1, "first"), note(7, "second")]);
+        assert_eq!(store.next_sequence::<Note>().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn unused_collections_are_kept_until_restored_by_name() {
+        let file = SnapshotFile::new("lazy");
+        let snapshot = json!({
+            "notes": { "sequence": 3, "records": [{ "id": 3, "text": "restored" }] },
+            "others": { "sequence": 5, "records": [{ "anything": true }] },
+        });
+        fs::write(&file.0, snapshot.to_string()).unwrap();
+
+        // A collection that is never used is saved unchanged
+        let store = MemoryStore::with_snapshot(&file.0).unwrap();
+        store.insert(&note(4, "added")).await.unwrap();
+        drop(store);
+        let saved: Value = serde_json::from_slice(&fs::read(&file.0).unwrap()).unwrap();
+        assert_eq!(saved["others"], snapshot["others"]);
+        assert_eq!(saved["notes"]["records"].as_array().map(Vec::len), Some(2));
+
+        // Records are only checked when their collection is first used
+        let store = MemoryStore::with_snapshot(&file.0).unwrap();
+        assert_eq!(
+            store.get::<Note>(&3).await.unwrap(),
+            Some(note(3, "restored"))
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_records_fail_on_first_use() {
+        let file = SnapshotFile::new("invalid");
+        let snapshot = json!({ "notes": { "sequence": 1, "records": [{ "id": "one" }] } });
+        fs::write(&file.0, snapshot.to_string()).unwrap();
+
+        let store = MemoryStore::with_snapshot(&file.0).unwrap();
+        assert!(matches!(
+            store.get::<Note>(&1).await,
+            Err(Error::InvalidRecord(_))
+        ));
+    }
+}
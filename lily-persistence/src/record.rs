@@ -0,0 +1,164 @@
+//! Provides the conversion of resources to the records kept by a store.
+
+use chrono::{DateTime, Utc};
+use lily_core::Error;
+use lily_endpoint::routing::Endpoint;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// A stored resource, mapping the name of every field to its value.
+///
+/// Unlike the serialized response, a record contains the write-only and
+/// hidden fields as well.
+pub type Record = Map<String, Value>;
+
+/// The storage type of a field, derived from its Rust type by `#[persistent]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// `String`, as well as `NaiveDate` and other types serialized as strings.
+    Text,
+    /// Any integer type.
+    Integer,
+    /// `f32` and `f64`.
+    Real,
+    Boolean,
+    /// `DateTime<Utc>`.
+    Timestamp,
+    /// Every other type, stored as serialized JSON, e.g. `Vec<String>`.
+    Json,
+}
+
+/// A field of a stored resource, for stores with a schema like SQL tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnKind,
+    /// Whether the field is an `Option`.
+    pub nullable: bool,
+    /// Whether the field is the id of the resource.
+    pub primary_key: bool,
+}
+
+/// An endpoint whose resources are kept by a [`Store`](crate::store::Store).
+///
+/// Implemented by `#[persistent]`, which is placed below `#[endpoint]`. The
+/// ids must be ordered, as stores list resources by id if no other order is
+/// requested, and implement [`NewId`].
+pub trait Persistent:
+    Endpoint<Id: NewId + Ord + Clone + Send + Sync + 'static> + Clone + Send + Sync + 'static
+{
+    /// The fields of a record, in the order of the struct.
+    const COLUMNS: &'static [Column];
+
+    /// The name of the collection holding the resources, unique per store.
+    fn collection() -> String {
+        Self::get_name()
+    }
+
+    /// Creates a new resource from a payload.
+    ///
+    /// The metadata fields are set from `id` and `now`, all fields missing
+    /// from the payload start with their default value.
+    fn create(id: Self::Id, payload: &Self::CreatePayload, now: DateTime<Utc>) -> Self;
+
+    /// Applies an update payload, setting the `updated_at` field to `now` if there is one.
+    fn apply_update(&mut self, payload: &Self::UpdatePayload, now: DateTime<Utc>);
+
+    /// Converts the resource into a record.
+    fn to_record(&self) -> Result<Record, Error>;
+
+    /// Restores a resource from a record.
+    fn from_record(record: Record) -> Result<Self, Error>;
+}
+
+/// The id type of a persistent endpoint, deciding how the ids of new resources are derived.
+///
+/// Integers and strings are numbered by the collection's sequence, UUIDs are
+/// random. Other id types implement it themselves.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can not be the id of a persistent endpoint",
+    label = "the ids of new resources can not be derived",
+    note = "implement `NewId` for `{Self}` to choose how the ids of new resources are derived"
+)]
+pub trait NewId: Sized {
+    /// Derives the id of a new resource from the next number of the
+    /// collection's sequence, or returns `None` if the id type can not
+    /// represent it.
+    fn new_id(sequence: u64) -> Option<Self>;
+}
+
+macro_rules! impl_new_id_for_integers {
+    ($($integer:ty),*) => {
+        $(
+            impl NewId for $integer {
+                fn new_id(sequence: u64) -> Option<Self> {
+                    Self::try_from(sequence).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_new_id_for_integers!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
+
+impl NewId for String {
+    fn new_id(sequence: u64) -> Option<Self> {
+        Some(sequence.to_string())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl NewId for uuid::Uuid {
+    fn new_id(_: u64) -> Option<Self> {
+        Some(uuid::Uuid::new_v4())
+    }
+}
+
+/// Converts a field into the value stored in a record.
+pub fn encode_field<T: Serialize>(name: &str, value: &T) -> Result<Value, Error> {
+    serde_json::to_value(value)
+        .map_err(|error| Error::InvalidRecord(format!("field `{}`: {}", name, error)))
+}
+
+/// Takes a field out of a record, treating a missing field as `null`, so
+/// that optional fields added after a resource was stored are restored as `None`.
+pub fn decode_field<T: DeserializeOwned>(record: &mut Record, name: &str) -> Result<T, Error> {
+    let value = record.remove(name).unwrap_or(Value::Null);
+    serde_json::from_value(value)
+        .map_err(|error| Error::InvalidRecord(format!("field `{}`: {}", name, error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_ids_follow_the_sequence() {
+        assert_eq!(u32::new_id(7), Some(7));
+        assert_eq!(i64::new_id(7), Some(7));
+    }
+
+    #[test]
+    fn integer_ids_end_with_their_type() {
+        assert_eq!(u8::new_id(255), Some(255));
+        assert_eq!(u8::new_id(256), None);
+        assert_eq!(i8::new_id(128), None);
+    }
+
+    #[test]
+    fn string_ids_follow_the_sequence() {
+        assert_eq!(String::new_id(42), Some("42".to_owned()));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_ids_are_random() {
+        let first = uuid::Uuid::new_id(1).unwrap();
+        let second = uuid::Uuid::new_id(1).unwrap();
+        assert_eq!(first.get_version_num(), 4);
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,102 @@
+//! Provides the typed access to the collection of a persistent endpoint.
+
+use crate::record::{NewId, Persistent};
+use crate::store::Store;
+use axum::extract::FromRef;
+use chrono::Utc;
+use lily_core::Error;
+use lily_endpoint::pagination::Page;
+use lily_endpoint::routing::ListQuery;
+use std::marker::PhantomData;
+
+/// The resources of a persistent endpoint in a store.
+///
+/// The generated trait implementations create one repository per request:
+///
+/// ```ignore
+/// let repository = Repository::<Content, MemoryStore>::from_state(state);
+/// let content = repository.create(&payload).await?;
+/// ```
+pub struct Repository<E, S> {
+    store: S,
+    endpoint: PhantomData<fn() -> E>,
+}
+
+impl<E: Persistent, S: Store> Repository<E, S> {
+    pub fn new(store: S) -> Self {
+        Repository {
+            store,
+            endpoint: PhantomData,
+        }
+    }
+
+    /// Takes the store from the state of the endpoint.
+    pub fn from_state(state: &E::State) -> Self
+    where
+        S: FromRef<E::State>,
+    {
+        Repository::new(S::from_ref(state))
+    }
+
+    /// The store holding the collection.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Creates and stores a new resource, assigning the next id of the collection.
+    ///
+    /// Ids that are already taken, e.g. by a resource created with an upsert,
+    /// are skipped. As every attempt advances the sequence, a free id is found
+    /// after at most as many attempts as there are resources.
+    pub async fn create(&self, payload: &E::CreatePayload) -> Result<E, Error> {
+        let now = Utc::now();
+        loop {
+            let sequence = self.store.next_sequence::<E>().await?;
+            let id = E::Id::new_id(sequence).ok_or_else(|| {
+                Error::Storage(format!("the ids of [{}] are exhausted", E::get_name()))
+            })?;
+            let item = E::create(id, payload, now);
+            match self.store.insert(&item).await {
+                Ok(()) => return Ok(item),
+                Err(Error::IdTaken(_)) => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Returns the resource with the given id, if any.
+    pub async fn find(&self, id: &E::Id) -> Result<Option<E>, Error> {
+        self.store.get::<E>(id).await
+    }
+
+    /// Returns the requested page of the resources matching the filter.
+    pub async fn list(&self, query: &ListQuery<E>) -> Result<Page<E>, Error> {
+        self.store.list(query).await
+    }
+
+    /// Applies an update payload to an existing resource.
+    ///
+    /// Fails with [`Error::NotFound`] if there is no resource with the given id.
+    pub async fn update(&self, id: &E::Id, payload: &E::UpdatePayload) -> Result<E, Error> {
+        let mut item = self.find(id).await?.ok_or(Error::NotFound)?;
+        item.apply_update(payload, Utc::now());
+        if !self.store.update(&item).await? {
+            return Err(Error::NotFound);
+        }
+        Ok(item)
+    }
+
+    /// Removes the resource with the given id, returning it if it existed.
+    pub async fn delete(&self, id: &E::Id) -> Result<Option<E>, Error> {
+        self.store.delete::<E>(id).await
+    }
+}
+
+impl<E, S: Clone> Clone for Repository<E, S> {
+    fn clone(&self) -> Self {
+        Repository {
+            store: self.store.clone(),
+            endpoint: PhantomData,
+        }
+    }
+}
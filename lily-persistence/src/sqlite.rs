@@ -0,0 +1,860 @@
+//! Provides a store keeping the resources in an embedded SQLite database,
+//! enabled with the `sqlite` feature.
+//!
+//! Every collection is a table, with one column per field of the endpoint.
+//! `#[persistent]` derives the [`Column`]s from the field types:
+//!
+//! | Field type                            | Column                        |
+//! |---------------------------------------|-------------------------------|
+//! | `String`, `NaiveDate`                 | `TEXT`                        |
+//! | integers                              | `INTEGER`                     |
+//! | `f32`, `f64`                          | `REAL`                        |
+//! | `bool`                                | `BOOLEAN`, stored as 0 or 1   |
+//! | `DateTime<Utc>`                       | `TIMESTAMP`, stored as text   |
+//! | anything else, e.g. `Vec<String>`     | `TEXT`, holding JSON          |
+//!
+//! Columns are `NOT NULL` unless the field is an `Option`. Timestamps are
+//! stored as RFC 3339 text in UTC with a fixed number of fractional digits,
+//! so that they compare and sort like the timestamps themselves.
+//!
+//! The tables are created with [`SqliteStore::create_table`] on startup, or
+//! on the first use of a collection otherwise. Optional fields added to an
+//! endpoint later are added to an existing table as nullable columns, so they
+//! can be introduced without a migration. A new required field is rejected,
+//! as the stored rows have no value for it.
+//!
+//! ```ignore
+//! let store = SqliteStore::open("site.db")?;
+//! store.create_table::<Content>()?;
+//! ```
+//!
+//! List requests are answered by a single query: the filter conditions, the
+//! sort keys and the cursor are translated to SQL, so only the requested page
+//! is read from the database.
+
+use crate::record::{Column, ColumnKind, Persistent, Record};
+use crate::store::Store;
+use chrono::{DateTime, SecondsFormat, Utc};
+use lily_core::Error;
+use lily_endpoint::cursor::Cursor;
+use lily_endpoint::filtering::{Condition, Filter};
+use lily_endpoint::pagination::Page;
+use lily_endpoint::routing::ListQuery;
+use lily_endpoint::sorting::{Sort, SortDirection, SortField};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension, Row, params_from_iter};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The table holding the sequence of every collection
+const SEQUENCES_TABLE: &str = "lily_sequences";
+
+/// A store keeping every collection in a table of a SQLite database.
+///
+/// Clones share the same connection. The queries run on the blocking thread
+/// pool of tokio, one at a time.
+#[derive(Clone)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    /// The tables that were created or updated since the store was opened
+    tables: Arc<Mutex<HashSet<String>>>,
+}
+
+impl SqliteStore {
+    /// Opens the database file at `path`, creating it if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        SqliteStore::new(Connection::open(path).map_err(storage_error)?)
+    }
+
+    /// Opens a database that only lives as long as the store, e.g. for tests.
+    pub fn open_in_memory() -> Result<Self, Error> {
+        SqliteStore::new(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn new(connection: Connection) -> Result<Self, Error> {
+        connection
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (collection TEXT NOT NULL PRIMARY KEY, value INTEGER NOT NULL)",
+                quote_identifier(SEQUENCES_TABLE)
+            ))
+            .map_err(storage_error)?;
+        Ok(SqliteStore {
+            connection: Arc::new(Mutex::new(connection)),
+            tables: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// Creates the table of an endpoint, or adds the columns it is missing if it exists.
+    ///
+    /// Called on startup, this reports a schema that does not fit the
+    /// endpoint before the first request.
+    pub fn create_table<E: Persistent>(&self) -> Result<(), Error> {
+        let connection = self.connection.lock().map_err(|_| poisoned())?;
+        create_table::<E>(&connection, &self.tables)
+    }
+
+    /// Runs `f` on the blocking thread pool, after making sure the table of the endpoint exists
+    async fn with_table<E: Persistent, R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<R, Error> + Send + 'static,
+    ) -> Result<R, Error> {
+        let connection = self.connection.clone();
+        let tables = self.tables.clone();
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.lock().map_err(|_| poisoned())?;
+            let created = tables
+                .lock()
+                .map_err(|_| poisoned())?
+                .contains(&E::collection());
+            if !created {
+                create_table::<E>(&connection, &tables)?;
+            }
+            f(&connection)
+        })
+        .await
+        .map_err(|error| Error::Storage(error.to_string()))?
+    }
+}
+
+fn create_table<E: Persistent>(
+    connection: &Connection,
+    tables: &Mutex<HashSet<String>>,
+) -> Result<(), Error> {
+    let table = E::collection();
+    ensure_table(connection, &table, E::COLUMNS)?;
+    tables.lock().map_err(|_| poisoned())?.insert(table);
+    Ok(())
+}
+
+/// Creates a table with the given columns, or adds the nullable columns it is missing
+fn ensure_table(connection: &Connection, table: &str, columns: &[Column]) -> Result<(), Error> {
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let mut definition = format!(
+                "{} {}",
+                quote_identifier(column.name),
+                column_type(column.kind)
+            );
+            if !column.nullable {
+                definition.push_str(" NOT NULL");
+            }
+            if column.primary_key {
+                definition.push_str(" PRIMARY KEY");
+            }
+            definition
+        })
+        .collect();
+    connection
+        .execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quote_identifier(table),
+            definitions.join(", ")
+        ))
+        .map_err(storage_error)?;
+
+    // Existing rows have no value for new columns, so only nullable columns can be added
+    let existing: HashSet<String> = connection
+        .prepare(&format!("PRAGMA table_info({})", quote_identifier(table)))
+        .and_then(|mut statement| {
+            statement
+                .query_map([], |row| row.get::<_, String>("name"))?
+                .collect()
+        })
+        .map_err(storage_error)?;
+    let missing: Vec<&Column> = columns
+        .iter()
+        .filter(|column| !existing.contains(column.name))
+        .collect();
+    // The table is left unchanged if any column can not be added
+    if let Some(column) = missing.iter().find(|column| !column.nullable) {
+        return Err(Error::Storage(format!(
+            "the table `{}` has no column for the required field `{}`; \
+             make the field an `Option` or add the column with a migration",
+            table, column.name
+        )));
+    }
+    for column in missing {
+        connection
+            .execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                quote_identifier(table),
+                quote_identifier(column.name),
+                column_type(column.kind)
+            ))
+            .map_err(storage_error)?;
+    }
+    Ok(())
+}
+
+/// The declared type of a column, which determines how SQLite converts the stored values
+fn column_type(kind: ColumnKind) -> &'static str {
+    match kind {
+        ColumnKind::Text | ColumnKind::Json => "TEXT",
+        ColumnKind::Integer => "INTEGER",
+        ColumnKind::Real => "REAL",
+        ColumnKind::Boolean => "BOOLEAN",
+        ColumnKind::Timestamp => "TIMESTAMP",
+    }
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn storage_error(error: rusqlite::Error) -> Error {
+    Error::Storage(error.to_string())
+}
+
+fn poisoned() -> Error {
+    Error::Storage("the SQLite store is poisoned".to_owned())
+}
+
+fn column<E: Persistent>(name: &str) -> Result<&'static Column, Error> {
+    E::COLUMNS
+        .iter()
+        .find(|column| column.name == name)
+        .ok_or_else(|| {
+            Error::Storage(format!(
+                "the field `{}` is not a column of `{}`",
+                name,
+                E::collection()
+            ))
+        })
+}
+
+fn primary_key<E: Persistent>() -> Result<&'static Column, Error> {
+    E::COLUMNS
+        .iter()
+        .find(|column| column.primary_key)
+        .ok_or_else(|| Error::Storage(format!("`{}` has no id column", E::collection())))
+}
+
+/// The quoted column names of an endpoint, as selected by every query
+fn column_list<E: Persistent>() -> String {
+    E::COLUMNS
+        .iter()
+        .map(|column| quote_identifier(column.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Converts the value of a record field into the value stored in its column
+fn to_sql(column: &Column, value: Value) -> Result<SqlValue, Error> {
+    let invalid = |value: &Value| {
+        Error::InvalidRecord(format!(
+            "field `{}`: {} can not be stored as {:?}",
+            column.name, value, column.kind
+        ))
+    };
+    Ok(match (column.kind, value) {
+        (_, Value::Null) => SqlValue::Null,
+        (ColumnKind::Json, value) => SqlValue::Text(value.to_string()),
+        (ColumnKind::Text, Value::String(text)) => SqlValue::Text(text),
+        (ColumnKind::Integer, Value::Number(number)) => SqlValue::Integer(
+            number
+                .as_i64()
+                .ok_or_else(|| invalid(&Value::Number(number)))?,
+        ),
+        (ColumnKind::Real, Value::Number(number)) => SqlValue::Real(
+            number
+                .as_f64()
+                .ok_or_else(|| invalid(&Value::Number(number)))?,
+        ),
+        (ColumnKind::Boolean, Value::Bool(value)) => SqlValue::Integer(value.into()),
+        (ColumnKind::Timestamp, Value::String(text)) => {
+            let timestamp = DateTime::parse_from_rfc3339(&text)
+                .map_err(|_| invalid(&Value::String(text.clone())))?;
+            SqlValue::Text(
+                timestamp
+                    .with_timezone(&Utc)
+                    .to_rfc3339_opts(SecondsFormat::Nanos, true),
+            )
+        }
+        (_, value) => return Err(invalid(&value)),
+    })
+}
+
+/// Converts the value of a column back into the value of a record field
+fn from_sql(column: &Column, value: SqlValue) -> Result<Value, Error> {
+    let invalid = |value: &SqlValue| {
+        Error::InvalidRecord(format!(
+            "column `{}`: {:?} is not a valid {:?}",
+            column.name, value, column.kind
+        ))
+    };
+    Ok(match (column.kind, value) {
+        (_, SqlValue::Null) => Value::Null,
+        (ColumnKind::Json, SqlValue::Text(text)) => {
+            serde_json::from_str(&text).map_err(|error| {
+                Error::InvalidRecord(format!("column `{}`: {}", column.name, error))
+            })?
+        }
+        (ColumnKind::Text | ColumnKind::Timestamp, SqlValue::Text(text)) => Value::String(text),
+        (ColumnKind::Integer | ColumnKind::Real, SqlValue::Integer(number)) => number.into(),
+        (ColumnKind::Real, SqlValue::Real(number)) => serde_json::Number::from_f64(number)
+            .map(Value::Number)
+            .ok_or_else(|| invalid(&SqlValue::Real(number)))?,
+        (ColumnKind::Boolean, SqlValue::Integer(number)) => Value::Bool(number != 0),
+        (_, value) => return Err(invalid(&value)),
+    })
+}
+
+/// Converts a resource into the values of its columns, in the order of [`Persistent::COLUMNS`]
+fn to_row<E: Persistent>(item: &E) -> Result<Vec<SqlValue>, Error> {
+    let mut record = item.to_record()?;
+    E::COLUMNS
+        .iter()
+        .map(|column| to_sql(column, record.remove(column.name).unwrap_or(Value::Null)))
+        .collect()
+}
+
+/// Restores a resource from a row selected with [`column_list`]
+fn from_row<E: Persistent>(row: &Row) -> Result<E, Error> {
+    let mut record = Record::new();
+    for (index, column) in E::COLUMNS.iter().enumerate() {
+        let value = row.get::<_, SqlValue>(index).map_err(storage_error)?;
+        record.insert(column.name.to_owned(), from_sql(column, value)?);
+    }
+    E::from_record(record)
+}
+
+/// Converts an id into the value of the primary key column
+fn id_to_sql<E: Persistent>(id: &E::Id) -> Result<SqlValue, Error> {
+    let value =
+        serde_json::to_value(id).map_err(|error| Error::InvalidRecord(error.to_string()))?;
+    to_sql(primary_key::<E>()?, value)
+}
+
+/// A part of a `WHERE` clause with the values of its placeholders
+struct Clause {
+    sql: String,
+    params: Vec<SqlValue>,
+}
+
+impl Clause {
+    fn new(sql: impl Into<String>, params: Vec<SqlValue>) -> Self {
+        Clause {
+            sql: sql.into(),
+            params,
+        }
+    }
+
+    /// Joins clauses with `AND`, where no clauses always hold
+    fn all(clauses: Vec<Clause>) -> Self {
+        Clause::join(clauses, " AND ", "1")
+    }
+
+    /// Joins clauses with `OR`, where no clauses never hold
+    fn any(clauses: Vec<Clause>) -> Self {
+        Clause::join(clauses, " OR ", "0")
+    }
+
+    fn join(clauses: Vec<Clause>, separator: &str, empty: &str) -> Self {
+        if clauses.is_empty() {
+            return Clause::new(empty, Vec::new());
+        }
+        let sql = clauses
+            .iter()
+            .map(|clause| format!("({})", clause.sql))
+            .collect::<Vec<_>>()
+            .join(separator);
+        Clause::new(
+            sql,
+            clauses
+                .into_iter()
+                .flat_map(|clause| clause.params)
+                .collect(),
+        )
+    }
+}
+
+/// Translates a filter condition, matching [`Condition::matches`]: every
+/// condition except `null` fails on null values, as comparisons with `NULL`
+/// are never true in SQL.
+fn condition_clause(column: &Column, condition: Condition<Value>) -> Result<Clause, Error> {
+    let name = quote_identifier(column.name);
+    let compare = |operator: &str, value: Value| -> Result<Clause, Error> {
+        Ok(Clause::new(
+            format!("{} {} ?", name, operator),
+            vec![to_sql(column, value)?],
+        ))
+    };
+    match condition {
+        Condition::Eq(value) => compare("=", value),
+        Condition::Ne(value) => compare("<>", value),
+        Condition::Gt(value) => compare(">", value),
+        Condition::Gte(value) => compare(">=", value),
+        Condition::Lt(value) => compare("<", value),
+        Condition::Lte(value) => compare("<=", value),
+        // `LIKE` ignores the case, unlike the filter language
+        Condition::Contains(needle) => Ok(Clause::new(
+            format!("instr({}, ?) > 0", name),
+            vec![SqlValue::Text(needle)],
+        )),
+        Condition::StartsWith(prefix) => Ok(Clause::new(
+            format!("substr({}, 1, length(?)) = ?", name),
+            vec![SqlValue::Text(prefix.clone()), SqlValue::Text(prefix)],
+        )),
+        // `substr` returns the whole text for a length of 0
+        Condition::EndsWith(suffix) if suffix.is_empty() => {
+            Ok(Clause::new(format!("{} IS NOT NULL", name), Vec::new()))
+        }
+        Condition::EndsWith(suffix) => Ok(Clause::new(
+            format!("substr({}, -length(?)) = ?", name),
+            vec![SqlValue::Text(suffix.clone()), SqlValue::Text(suffix)],
+        )),
+        Condition::In(values) if values.is_empty() => Ok(Clause::new("0", Vec::new())),
+        Condition::In(values) => Ok(Clause::new(
+            format!("{} IN ({})", name, vec!["?"; values.len()].join(", ")),
+            values
+                .into_iter()
+                .map(|value| to_sql(column, value))
+                .collect::<Result<_, _>>()?,
+        )),
+        Condition::Null(true) => Ok(Clause::new(format!("{} IS NULL", name), Vec::new())),
+        Condition::Null(false) => Ok(Clause::new(format!("{} IS NOT NULL", name), Vec::new())),
+    }
+}
+
+/// Translates [`Cursor::precedes`], selecting the rows after the cursor in
+/// the order of the sort keys and then the id.
+///
+/// `NULL` sorts first in SQLite like `None` does in Rust, so it comes before
+/// every value in ascending and after every value in descending order.
+fn cursor_clause<E: Persistent>(
+    cursor: &Cursor,
+    sort: &[Sort<E::SortField>],
+) -> Result<Clause, Error> {
+    let mut keys: Vec<(&Column, SortDirection, Value)> = sort
+        .iter()
+        .zip(&cursor.key)
+        .map(|(sort, value)| {
+            Ok((
+                column::<E>(sort.field.name())?,
+                sort.direction,
+                value.clone(),
+            ))
+        })
+        .collect::<Result<_, Error>>()?;
+    keys.push((
+        primary_key::<E>()?,
+        SortDirection::Ascending,
+        cursor.id.clone(),
+    ));
+    keyset_clause(&keys)
+}
+
+/// Selects the rows after the given values of the sort keys, the last key being unique
+fn keyset_clause(keys: &[(&Column, SortDirection, Value)]) -> Result<Clause, Error> {
+    let mut levels: Vec<Clause> = Vec::new();
+    for (level, (column, direction, value)) in keys.iter().enumerate() {
+        // All earlier keys are equal to those of the cursor
+        let mut clauses: Vec<Clause> = keys[..level]
+            .iter()
+            .map(|(column, _, value)| {
+                Ok(Clause::new(
+                    format!("{} IS ?", quote_identifier(column.name)),
+                    vec![to_sql(column, value.clone())?],
+                ))
+            })
+            .collect::<Result<_, Error>>()?;
+        let name = quote_identifier(column.name);
+        clauses.push(match (direction, value) {
+            (SortDirection::Ascending, Value::Null) => {
+                Clause::new(format!("{} IS NOT NULL", name), Vec::new())
+            }
+            (SortDirection::Ascending, value) => Clause::new(
+                format!("{} > ?", name),
+                vec![to_sql(column, value.clone())?],
+            ),
+            (SortDirection::Descending, Value::Null) => Clause::new("0", Vec::new()),
+            (SortDirection::Descending, value) => Clause::new(
+                format!("{} < ? OR {} IS NULL", name, name),
+                vec![to_sql(column, value.clone())?],
+            ),
+        });
+        levels.push(Clause::all(clauses));
+    }
+    Ok(Clause::any(levels))
+}
+
+impl Store for SqliteStore {
+    async fn next_sequence<E: Persistent>(&self) -> Result<u64, Error> {
+        let collection = E::collection();
+        self.with_table::<E, _>(move |connection| {
+            connection
+                .query_row(
+                    &format!(
+                        "INSERT INTO {} (collection, value) VALUES (?1, 1) \
+                         ON CONFLICT (collection) DO UPDATE SET value = value + 1 \
+                         RETURNING value",
+                        quote_identifier(SEQUENCES_TABLE)
+                    ),
+                    [collection],
+                    |row| row.get::<_, u64>(0),
+                )
+                .map_err(storage_error)
+        })
+        .await
+    }
+
+    async fn insert<E: Persistent>(&self, item: &E) -> Result<(), Error> {
+        let row = to_row(item)?;
+        let id = item.id().to_string();
+        self.with_table::<E, _>(move |connection| {
+            let sql = format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote_identifier(&E::collection()),
+                column_list::<E>(),
+                vec!["?"; row.len()].join(", ")
+            );
+            match connection.execute(&sql, params_from_iter(row)) {
+                Ok(_) => Ok(()),
+                // Other constraint violations, like a missing required column, are storage errors
+                Err(rusqlite::Error::SqliteFailure(error, _))
+                    if error.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY =>
+                {
+                    Err(Error::IdTaken(format!("`{}` in `{}`", id, E::collection())))
+                }
+                Err(error) => Err(storage_error(error)),
+            }
+        })
+        .await
+    }
+
+    async fn get<E: Persistent>(&self, id: &E::Id) -> Result<Option<E>, Error> {
+        let id = id_to_sql::<E>(id)?;
+        self.with_table::<E, _>(move |connection| {
+            let sql = format!(
+                "SELECT {} FROM {} WHERE {} = ?",
+                column_list::<E>(),
+                quote_identifier(&E::collection()),
+                quote_identifier(primary_key::<E>()?.name)
+            );
+            connection
+                .query_row(&sql, [id], |row| Ok(from_row::<E>(row)))
+                .optional()
+                .map_err(storage_error)?
+                .transpose()
+        })
+        .await
+    }
+
+    async fn update<E: Persistent>(&self, item: &E) -> Result<bool, Error> {
+        let mut params = to_row(item)?;
+        params.push(id_to_sql::<E>(item.id())?);
+        self.with_table::<E, _>(move |connection| {
+            let assignments: Vec<String> = E::COLUMNS
+                .iter()
+                .map(|column| format!("{} = ?", quote_identifier(column.name)))
+                .collect();
+            let sql = format!(
+                "UPDATE {} SET {} WHERE {} = ?",
+                quote_identifier(&E::collection()),
+                assignments.join(", "),
+                quote_identifier(primary_key::<E>()?.name)
+            );
+            let changed = connection
+                .execute(&sql, params_from_iter(params))
+                .map_err(storage_error)?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    async fn delete<E: Persistent>(&self, id: &E::Id) -> Result<Option<E>, Error> {
+        let id = id_to_sql::<E>(id)?;
+        self.with_table::<E, _>(move |connection| {
+            let sql = format!(
+                "DELETE FROM {} WHERE {} = ? RETURNING {}",
+                quote_identifier(&E::collection()),
+                quote_identifier(primary_key::<E>()?.name),
+                column_list::<E>()
+            );
+            connection
+                .query_row(&sql, [id], |row| Ok(from_row::<E>(row)))
+                .optional()
+                .map_err(storage_error)?
+                .transpose()
+        })
+        .await
+    }
+
+    async fn list<E: Persistent>(&self, query: &ListQuery<E>) -> Result<Page<E>, Error> {
+        let filter = Clause::all(
+            query
+                .filter
+                .conditions()
+                .into_iter()
+                .map(|(field, condition)| condition_clause(column::<E>(field)?, condition))
+                .collect::<Result<_, Error>>()?,
+        );
+        let cursor = match &query.pagination.cursor {
+            Some(cursor) => cursor_clause::<E>(cursor, &query.sort)?,
+            None => Clause::all(Vec::new()),
+        };
+        let mut order: Vec<String> = query
+            .sort
+            .iter()
+            .map(|sort| {
+                let direction = match sort.direction {
+                    SortDirection::Ascending => "ASC",
+                    SortDirection::Descending => "DESC",
+                };
+                Ok(format!(
+                    "{} {}",
+                    quote_identifier(column::<E>(sort.field.name())?.name),
+                    direction
+                ))
+            })
+            .collect::<Result<_, Error>>()?;
+        order.push(format!(
+            "{} ASC",
+            quote_identifier(primary_key::<E>()?.name)
+        ));
+        // A negative limit means no limit in SQLite
+        let limit = i64::try_from(query.pagination.limit).unwrap_or(-1);
+        let offset = i64::try_from(query.pagination.offset).unwrap_or(i64::MAX);
+
+        let (items, total) = self
+            .with_table::<E, _>(move |connection| {
+                let table = quote_identifier(&E::collection());
+                let total = connection
+                    .query_row(
+                        &format!("SELECT COUNT(*) FROM {} WHERE {}", table, filter.sql),
+                        params_from_iter(&filter.params),
+                        |row| row.get::<_, u64>(0),
+                    )
+                    .map_err(storage_error)?;
+
+                let sql = format!(
+                    "SELECT {} FROM {} WHERE ({}) AND ({}) ORDER BY {} LIMIT ? OFFSET ?",
+                    column_list::<E>(),
+                    table,
+                    filter.sql,
+                    cursor.sql,
+                    order.join(", ")
+                );
+                let params = filter
+                    .params
+                    .into_iter()
+                    .chain(cursor.params)
+                    .chain([SqlValue::Integer(limit), SqlValue::Integer(offset)]);
+                let mut statement = connection.prepare(&sql).map_err(storage_error)?;
+                let items = statement
+                    .query_map(params_from_iter(params), |row| Ok(from_row::<E>(row)))
+                    .map_err(storage_error)?
+                    .map(|item| item.map_err(storage_error)?)
+                    .collect::<Result<Vec<E>, Error>>()?;
+                Ok((items, total))
+            })
+            .await?;
+        Ok(Page::new(items, total, &query.pagination))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ID: Column = Column {
+        name: "id",
+        kind: ColumnKind::Integer,
+        nullable: false,
+        primary_key: true,
+    };
+    const TITLE: Column = Column {
+        name: "title",
+        kind: ColumnKind::Text,
+        nullable: false,
+        primary_key: false,
+    };
+    const RATING: Column = Column {
+        name: "rating",
+        kind: ColumnKind::Integer,
+        nullable: true,
+        primary_key: false,
+    };
+
+    /// A table of items, some of them without rating
+    fn items() -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        ensure_table(&connection, "items", &[ID, TITLE, RATING]).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO items (id, title, rating) VALUES \
+                 (1, 'alpha', 3), (2, 'beta', NULL), (3, 'Alphabet', 5), (4, 'gamma', 3)",
+            )
+            .unwrap();
+        connection
+    }
+
+    /// The ids of the items matching a clause, in the order of the sort keys
+    fn select(connection: &Connection, clause: Clause, order: &str) -> Vec<i64> {
+        let sql = format!(
+            "SELECT id FROM items WHERE {} ORDER BY {}",
+            clause.sql, order
+        );
+        let mut statement = connection.prepare(&sql).unwrap();
+        statement
+            .query_map(params_from_iter(clause.params), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn filter(column: &Column, condition: Condition<Value>) -> Vec<i64> {
+        let clause = condition_clause(column, condition).unwrap();
+        select(&items(), clause, "id")
+    }
+
+    #[test]
+    fn comparisons_skip_null_values() {
+        assert_eq!(filter(&RATING, Condition::Eq(json!(3))), [1, 4]);
+        assert_eq!(filter(&RATING, Condition::Ne(json!(3))), [3]);
+        assert_eq!(filter(&RATING, Condition::Gt(json!(3))), [3]);
+        assert_eq!(filter(&RATING, Condition::Gte(json!(3))), [1, 3, 4]);
+        assert_eq!(filter(&RATING, Condition::Lt(json!(5))), [1, 4]);
+        assert_eq!(filter(&RATING, Condition::Lte(json!(5))), [1, 3, 4]);
+    }
+
+    #[test]
+    fn text_conditions_respect_the_case() {
+        assert_eq!(
+            filter(&TITLE, Condition::Contains("lph".to_owned())),
+            [1, 3]
+        );
+        assert_eq!(filter(&TITLE, Condition::Contains("Alph".to_owned())), [3]);
+        assert_eq!(filter(&TITLE, Condition::StartsWith("al".to_owned())), [1]);
+        assert_eq!(filter(&TITLE, Condition::EndsWith("ta".to_owned())), [2]);
+        assert_eq!(
+            filter(&TITLE, Condition::EndsWith("A".to_owned())),
+            [] as [i64; 0]
+        );
+    }
+
+    #[test]
+    fn empty_suffixes_match_every_value() {
+        assert_eq!(
+            filter(&TITLE, Condition::EndsWith(String::new())),
+            [1, 2, 3, 4]
+        );
+        assert_eq!(
+            filter(&RATING, Condition::EndsWith(String::new())),
+            [1, 3, 4]
+        );
+    }
+
+    #[test]
+    fn in_matches_any_value_and_empty_lists_nothing() {
+        assert_eq!(
+            filter(&RATING, Condition::In(vec![json!(3), json!(5)])),
+            [1, 3, 4]
+        );
+        assert_eq!(filter(&RATING, Condition::In(Vec::new())), [] as [i64; 0]);
+    }
+
+    #[test]
+    fn null_selects_missing_or_present_values() {
+        assert_eq!(filter(&RATING, Condition::Null(true)), [2]);
+        assert_eq!(filter(&RATING, Condition::Null(false)), [1, 3, 4]);
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_rejected() {
+        assert!(condition_clause(&RATING, Condition::Eq(json!("three"))).is_err());
+    }
+
+    /// The ids after a cursor on the rating and the id, in the order of the sort
+    fn after(direction: SortDirection, rating: Value, id: i64) -> Vec<i64> {
+        let clause = keyset_clause(&[
+            (&RATING, direction, rating),
+            (&ID, SortDirection::Ascending, json!(id)),
+        ])
+        .unwrap();
+        let order = match direction {
+            SortDirection::Ascending => "rating ASC, id ASC",
+            SortDirection::Descending => "rating DESC, id ASC",
+        };
+        select(&items(), clause, order)
+    }
+
+    #[test]
+    fn null_comes_first_in_ascending_order() {
+        assert_eq!(after(SortDirection::Ascending, Value::Null, 2), [1, 4, 3]);
+        assert_eq!(after(SortDirection::Ascending, json!(3), 1), [4, 3]);
+        assert_eq!(after(SortDirection::Ascending, json!(5), 3), [] as [i64; 0]);
+    }
+
+    #[test]
+    fn null_comes_last_in_descending_order() {
+        assert_eq!(after(SortDirection::Descending, json!(5), 3), [1, 4, 2]);
+        assert_eq!(after(SortDirection::Descending, json!(3), 4), [2]);
+        assert_eq!(
+            after(SortDirection::Descending, Value::Null, 2),
+            [] as [i64; 0]
+        );
+    }
+
+    #[test]
+    fn equal_sort_values_continue_by_id() {
+        assert_eq!(after(SortDirection::Descending, json!(3), 1), [4, 2]);
+    }
+
+    fn column_names(connection: &Connection) -> Vec<String> {
+        let mut statement = connection.prepare("PRAGMA table_info(items)").unwrap();
+        statement
+            .query_map([], |row| row.get("name"))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn nullable_columns_are_added_to_existing_tables() {
+        let connection = items();
+        let summary = Column {
+            name: "summary",
+            kind: ColumnKind::Text,
+            nullable: true,
+            primary_key: false,
+        };
+        ensure_table(&connection, "items", &[ID, TITLE, RATING, summary]).unwrap();
+        assert_eq!(
+            column_names(&connection),
+            ["id", "title", "rating", "summary"]
+        );
+        let nulls: i64 = connection
+            .query_row(
+                "SELECT COUNT(*) FROM items WHERE summary IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(nulls, 4);
+    }
+
+    #[test]
+    fn required_columns_are_rejected_without_changing_the_table() {
+        let connection = items();
+        let summary = Column {
+            name: "summary",
+            kind: ColumnKind::Text,
+            nullable: true,
+            primary_key: false,
+        };
+        let body = Column {
+            name: "body",
+            kind: ColumnKind::Text,
+            nullable: false,
+            primary_key: false,
+        };
+        let result = ensure_table(&connection, "items", &[ID, TITLE, RATING, summary, body]);
+        assert!(matches!(result, Err(Error::Storage(message)) if message.contains("`body`")));
+        assert_eq!(column_names(&connection), ["id", "title", "rating"]);
+    }
+}
@@ -0,0 +1,36 @@
+//! Provides the trait implemented by storage backends.
+
+use crate::record::Persistent;
+use lily_core::Error;
+use lily_endpoint::pagination::Page;
+use lily_endpoint::routing::ListQuery;
+
+/// A storage backend keeping the resources of persistent endpoints.
+///
+/// Every endpoint is kept in its own collection, named by
+/// [`Persistent::collection`]. A store is cloned into every request, so it
+/// should be a cheap handle to shared storage, e.g. an `Arc` or a pool.
+#[allow(async_fn_in_trait)]
+pub trait Store: Clone + Send + Sync + 'static {
+    /// Returns the next number of the sequence of a collection, starting at 1.
+    async fn next_sequence<E: Persistent>(&self) -> Result<u64, Error>;
+
+    /// Inserts a new resource, failing with [`Error::IdTaken`] if its id is
+    /// already taken.
+    async fn insert<E: Persistent>(&self, item: &E) -> Result<(), Error>;
+
+    /// Returns the resource with the given id, if any.
+    async fn get<E: Persistent>(&self, id: &E::Id) -> Result<Option<E>, Error>;
+
+    /// Overwrites an existing resource, returning `false` if there is none with its id.
+    async fn update<E: Persistent>(&self, item: &E) -> Result<bool, Error>;
+
+    /// Removes the resource with the given id, returning it if it existed.
+    async fn delete<E: Persistent>(&self, id: &E::Id) -> Result<Option<E>, Error>;
+
+    /// Returns the requested page of the resources matching the filter.
+    ///
+    /// The resources are ordered like [`ListQuery::compare`], i.e. by the
+    /// requested sort keys and then by id, which keeps cursor pages stable.
+    async fn list<E: Persistent>(&self, query: &ListQuery<E>) -> Result<Page<E>, Error>;
+}
@@ -1,18 +1,111 @@
 pub mod prelude {
     pub use lily_core::Error;
+    pub use lily_endpoint::bulk::{
+        BulkDelete, BulkItemError, BulkMode, BulkQuery, BulkRejections, BulkReplaceItem,
+        BulkReport, BulkUpdate,
+    };
+    pub use lily_endpoint::context::Context;
+    pub use lily_endpoint::cursor::{
+        Cursor, CursorPage, CursorQuery, set_secret as set_cursor_secret,
+    };
+    pub use lily_endpoint::document_patch::{DocumentPatch, PatchOperation, UpdateBody};
+    pub use lily_endpoint::filtering::{Condition, Filter, FilterError, FilterValue, Operator};
+    pub use lily_endpoint::include::{
+        IncludeFuture, IncludeQuery, IncludeTree, Included, Includes, include_single,
+        resolve_includes, resolve_includes_all, set_max_depth as set_max_include_depth,
+        unknown_include,
+    };
+    pub use lily_endpoint::pagination::{Page, PageQuery, Pagination};
+    pub use lily_endpoint::patch::Patch;
     pub use lily_endpoint::problems::Problem;
+    pub use lily_endpoint::projection::{FieldSet, FieldsQuery, Projected};
+    pub use lily_endpoint::query::parse_query;
+    pub use lily_endpoint::relations::{
+        AnyState, Dependent, DependentFuture, DependentOf, Dependents, ManyToMany, OnDelete,
+        ReferenceLookups, ReferencedBy, References, Relation, RelationKind, apply_on_delete,
+        check_on_delete, check_on_delete_each, check_references, check_references_bulk,
+        check_references_each, delete_dependent, find_dependents, nullify_dependent,
+        resolve_nothing, state_from,
+    };
     pub use lily_endpoint::responses::ApiResponse;
     pub use lily_endpoint::routing::{
-        CreateSingle, DeleteSingle, Endpoint, ReadSingle, RouteBuilder, UpdateSingle,
+        CreateMultiple, CreateSingle, DeleteMultiple, DeleteSingle, Endpoint, ListQuery,
+        ReadMultiple, ReadSingle, ReplaceMultiple, ReplaceSingle, Replacement, RouteBuilder,
+        Selection, UpdateMultiple, UpdateSingle, set_path_prefix,
+    };
+    pub use lily_endpoint::sorting::{Sort, SortDirection, SortField, SortQuery, compare_by};
+    pub use lily_endpoint::validation::{
+        HasLength, Pattern, Validate, ValidationError, check_email, check_length, check_range,
+        validate_bulk, validate_each,
     };
     pub use lily_macros::endpoint;
+    #[cfg(feature = "persistence")]
+    pub use lily_macros::persistent;
+    #[cfg(feature = "persistence")]
+    pub use lily_persistence::memory::MemoryStore;
+    #[cfg(feature = "persistence")]
+    pub use lily_persistence::record::{
+        Column, ColumnKind, NewId, Persistent, Record, decode_field, encode_field,
+    };
+    #[cfg(feature = "persistence")]
+    pub use lily_persistence::repository::Repository;
+    #[cfg(feature = "sqlite")]
+    pub use lily_persistence::sqlite::SqliteStore;
+    #[cfg(feature = "persistence")]
+    pub use lily_persistence::store::Store;
 }
 
+pub use lily_core::Error;
+pub use lily_endpoint::bulk::{
+    BulkDelete, BulkItemError, BulkMode, BulkQuery, BulkRejections, BulkReplaceItem, BulkReport,
+    BulkUpdate,
+};
+pub use lily_endpoint::context::Context;
+pub use lily_endpoint::cursor::{Cursor, CursorPage, CursorQuery, set_secret as set_cursor_secret};
+pub use lily_endpoint::document_patch::{DocumentPatch, PatchOperation, UpdateBody};
+pub use lily_endpoint::filtering::{Condition, Filter, FilterError, FilterValue, Operator};
+pub use lily_endpoint::include::{
+    IncludeFuture, IncludeQuery, IncludeTree, Included, Includes, include_single, resolve_includes,
+    resolve_includes_all, set_max_depth as set_max_include_depth, unknown_include,
+};
+pub use lily_endpoint::pagination::{Page, PageQuery, Pagination};
+pub use lily_endpoint::patch::Patch;
 pub use lily_endpoint::problems::Problem;
+pub use lily_endpoint::projection::{FieldSet, FieldsQuery, Projected};
+pub use lily_endpoint::query::parse_query;
+pub use lily_endpoint::relations::{
+    AnyState, Dependent, DependentFuture, DependentOf, Dependents, ManyToMany, OnDelete,
+    ReferenceLookups, ReferencedBy, References, Relation, RelationKind, apply_on_delete,
+    check_on_delete, check_on_delete_each, check_references, check_references_bulk,
+    check_references_each, delete_dependent, find_dependents, nullify_dependent, resolve_nothing,
+    state_from,
+};
 pub use lily_endpoint::responses::ApiResponse;
 pub use lily_endpoint::routing::{
-    CreateSingle, DeleteSingle, Endpoint, ReadSingle, RouteBuilder, UpdateSingle,
+    CreateMultiple, CreateSingle, DeleteMultiple, DeleteSingle, Endpoint, ListQuery, ReadMultiple,
+    ReadSingle, ReplaceMultiple, ReplaceSingle, Replacement, RouteBuilder, Selection,
+    UpdateMultiple, UpdateSingle, set_path_prefix,
+};
+pub use lily_endpoint::sorting::{Sort, SortDirection, SortField, SortQuery, compare_by};
+pub use lily_endpoint::validation::{
+    HasLength, Pattern, Validate, ValidationError, check_email, check_length, check_range,
+    validate_bulk, validate_each,
+};
+pub use lily_macros::endpoint;
+#[cfg(feature = "persistence")]
+pub use lily_macros::persistent;
+#[cfg(feature = "persistence")]
+pub use lily_persistence::memory::MemoryStore;
+#[cfg(feature = "persistence")]
+pub use lily_persistence::record::{
+    Column, ColumnKind, NewId, Persistent, Record, decode_field, encode_field,
 };
+#[cfg(feature = "persistence")]
+pub use lily_persistence::repository::Repository;
+#[cfg(feature = "sqlite")]
+pub use lily_persistence::sqlite::SqliteStore;
+#[cfg(feature = "persistence")]
+pub use lily_persistence::store::Store;
 
 // pub use axum::{
 //     Json, Router,